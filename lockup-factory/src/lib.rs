@@ -3,8 +3,9 @@ mod utils;
 
 pub use crate::types::*;
 use crate::utils::*;
-use unc_sdk::json_types::U128;
-use unc_sdk::{env, ext_contract, unc, AccountId, UncToken, Promise};
+use unc_sdk::json_types::{Base58CryptoHash, U128, U64};
+use unc_sdk::store::{IterableMap, IterableSet};
+use unc_sdk::{env, ext_contract, unc, AccountId, CryptoHash, Gas, UncToken, Promise, PromiseOrValue};
 
 /// There is no deposit balance attached.
 const NO_DEPOSIT: UncToken = UncToken::from_attounc(0);
@@ -12,6 +13,10 @@ const TRANSFERS_STARTED: u64 = 1602614338293769340; /* 13 October 2020 18:38:58.
 
 const CODE: &[u8] = include_bytes!("../../res/lockup_contract.wasm");
 
+/// The storage key of the uploaded lockup code. The code is kept outside of the contract state, so
+/// it's only read when a lockup is created.
+const LOCKUP_CODE_KEY: &[u8] = b"k";
+
 pub mod gas {
     use unc_sdk::Gas;
 
@@ -19,15 +24,48 @@ pub mod gas {
     const BASE: Gas = Gas::from_gas(25_000_000_000_000);
 
     /// The amount of Gas the contract will attach to the promise to create the lockup.
-    pub const LOCKUP_NEW: Gas = BASE;
+    /// The lockup `new` call validates and stores the vesting schedule, so it needs more gas than
+    /// the callback.
+    pub const LOCKUP_NEW: Gas = Gas::from_gas(BASE.as_gas() * 2);
 
     /// The amount of Gas the contract will attach to the callback to itself.
     /// The base for the execution and the base for cash rollback.
     pub const CALLBACK: Gas = BASE;
+
+    /// The amount of Gas reserved for the execution of `create` itself, on top of the gas it
+    /// attaches to the promises.
+    pub const CREATE: Gas = BASE;
+
+    /// The maximum amount of Gas that can be attached to a single transaction.
+    pub const MAX_TRANSACTION: Gas = Gas::from_gas(300_000_000_000_000);
+
+    /// The extra amount of Gas attached to the lockup `new` call when the lockup stakes on init.
+    /// The base for the whitelist check and 5 bases for the lockup callback, which deposits and
+    /// stakes to the staking pool.
+    pub const LOCKUP_STAKE: Gas = Gas::from_gas(BASE.as_gas() * 6);
+
+    /// The amount of Gas the contract will attach to the whitelist view call that checks the
+    /// whitelist is reachable before the lockup is created.
+    pub const WHITELIST_CHECK: Gas = BASE;
+
+    /// The amount of Gas the contract will attach to the lockup `upgrade` call.
+    /// The base for the execution, the base for the code deployment and the base for `migrate`.
+    pub const LOCKUP_UPGRADE: Gas = Gas::from_gas(BASE.as_gas() * 3);
 }
 
 const MIN_ATTACHED_BALANCE: u128 = 3_500_000_000_000_000_000_000_000;
 
+/// The number of extra bytes of the lockup state with vesting: the foundation account ID and the
+/// vesting schedule or its hash, rounded up.
+const VESTING_STORAGE_BYTES: u128 = 200;
+
+/// The maximum length of an account ID allowed by the protocol.
+const MAX_ACCOUNT_ID_LEN: usize = 64;
+
+/// The length of the lockup account ID prefix: 20 bytes of the owner account ID hash in hex (40
+/// characters) and the dot before the factory account ID.
+const LOCKUP_ACCOUNT_ID_PREFIX_LEN: usize = 41;
+
 /// External interface for the callbacks to self.
 #[ext_contract(ext_self)]
 pub trait ExtSelf {
@@ -36,13 +74,68 @@ pub trait ExtSelf {
         lockup_account_id: AccountId,
         attached_deposit: U128,
         predecessor_account_id: AccountId,
+        owner_account_id: AccountId,
+        creation_fee: U128,
     ) -> bool;
+
+    fn on_lockup_upgrade(&mut self, lockup_account_id: AccountId) -> bool;
+
+    fn on_whitelist_verified(
+        &mut self,
+        lockup_account_id: AccountId,
+        lockup_args: LockupArgs,
+        lockup_new_gas: U64,
+        attached_deposit: U128,
+        predecessor_account_id: AccountId,
+        creation_fee: U128,
+    );
+}
+
+/// External interface for the staking pool whitelist contract.
+#[ext_contract(ext_whitelist)]
+pub trait ExtWhitelist {
+    fn is_factory_whitelisted(&self, factory_account_id: AccountId) -> bool;
 }
 
 #[unc(contract_state)]
 pub struct LockupFactory {
     whitelist_account_id: AccountId,
     foundation_account_id: AccountId,
+    /// Deposits attached to `create` calls that are not settled by `on_lockup_create` yet, keyed
+    /// by the account that has to be refunded if the creation fails.
+    pending_refunds: IterableMap<AccountId, U128>,
+    /// The amount of gas attached to the lockup `new` call. Defaults to `gas::LOCKUP_NEW`.
+    lockup_new_gas: Gas,
+    /// The amount of gas attached to the `on_lockup_create` callback. Defaults to `gas::CALLBACK`.
+    callback_gas: Gas,
+    /// The lockup account IDs successfully created by this factory, keyed by the owner account ID.
+    lockups: IterableMap<AccountId, AccountId>,
+    /// The fee charged on top of the lockup funding for every created lockup. Defaults to zero.
+    creation_fee: UncToken,
+    /// The creation fees of the successfully created lockups that are not withdrawn yet.
+    collected_fees: UncToken,
+    /// The release duration of the created lockups if the caller doesn't pass one. Defaults to
+    /// no release duration.
+    default_release_duration: Option<WrappedDuration>,
+    /// The number of lockups successfully created by this factory.
+    lockups_created: u64,
+    /// The total amount transferred to the successfully created lockups, excluding the creation
+    /// fees.
+    total_deployed_balance: UncToken,
+    /// The maximum lockup amount per owner account ID. The owners without a cap are not limited.
+    owner_caps: IterableMap<AccountId, U128>,
+    /// Whether only the attested owners can get a lockup created. Defaults to `false`.
+    require_attestation: bool,
+    /// The owner account IDs attested by the foundation.
+    attested_owners: IterableSet<AccountId>,
+    /// The lockups whose creation started but `on_lockup_create` didn't resolve yet, keyed by the
+    /// lockup account ID with the owner account ID as the value.
+    pending_creations: IterableMap<AccountId, AccountId>,
+    /// The SHA-256 hash the lockup code has to match before it's deployed, if set.
+    expected_lockup_code_hash: Option<CryptoHash>,
+    /// The version of the lockup code uploaded by the foundation under `LOCKUP_CODE_KEY`. The
+    /// created lockups get the code built into the factory until it's uploaded.
+    lockup_code_version: Option<String>,
 }
 
 
@@ -56,6 +149,7 @@ pub struct LockupArgs {
     release_duration: Option<WrappedDuration>,
     staking_pool_whitelist_account_id: AccountId,
     foundation_account_id: Option<AccountId>,
+    staking_pool_account_id: Option<AccountId>,
 }
 
 impl Default for LockupFactory {
@@ -72,14 +166,33 @@ impl LockupFactory {
         foundation_account_id: AccountId,
     ) -> Self {
         assert!(!env::state_exists(), "The contract is already initialized");
+        // The derived lockup account IDs have to be valid, so the check happens here instead of
+        // failing on the first `create`.
         assert!(
-            env::current_account_id().len() <= 23,
-            "The account ID of this contract can't be more than 23 characters"
+            env::current_account_id().len() + LOCKUP_ACCOUNT_ID_PREFIX_LEN <= MAX_ACCOUNT_ID_LEN,
+            "The account ID of this contract can't be more than {} characters",
+            MAX_ACCOUNT_ID_LEN - LOCKUP_ACCOUNT_ID_PREFIX_LEN
         );
+        assert_not_self_whitelist(&whitelist_account_id);
 
         Self {
             whitelist_account_id: whitelist_account_id.into(),
             foundation_account_id: foundation_account_id.into(),
+            pending_refunds: IterableMap::new(b"r".to_vec()),
+            lockup_new_gas: gas::LOCKUP_NEW,
+            callback_gas: gas::CALLBACK,
+            lockups: IterableMap::new(b"l".to_vec()),
+            creation_fee: UncToken::from_attounc(0),
+            collected_fees: UncToken::from_attounc(0),
+            default_release_duration: None,
+            lockups_created: 0,
+            total_deployed_balance: UncToken::from_attounc(0),
+            owner_caps: IterableMap::new(b"c".to_vec()),
+            require_attestation: false,
+            attested_owners: IterableSet::new(b"a".to_vec()),
+            pending_creations: IterableMap::new(b"p".to_vec()),
+            expected_lockup_code_hash: None,
+            lockup_code_version: None,
         }
     }
 
@@ -98,6 +211,322 @@ impl LockupFactory {
         MIN_ATTACHED_BALANCE.into()
     }
 
+    /// Returns the deposit that has to be attached to create a lockup with the given amount of
+    /// tokens on top of the minimum attached balance: the storage of the vesting and the creation
+    /// fee are included.
+    pub fn get_required_deposit(&self, with_vesting: bool, lockup_amount: U128) -> U128 {
+        self.internal_required_deposit(with_vesting, UncToken::from_attounc(lockup_amount.0))
+            .as_attounc()
+            .into()
+    }
+
+    /// Returns the factory configuration in a single call.
+    pub fn get_config(&self) -> FactoryConfig {
+        FactoryConfig {
+            foundation_account_id: self.get_foundation_account_id(),
+            whitelist_account_id: self.whitelist_account_id.clone(),
+            lockup_master_account_id: self.get_lockup_master_account_id(),
+            min_attached_balance: self.get_min_attached_balance(),
+        }
+    }
+
+    /// Returns the operational statistics of the factory in a single call.
+    pub fn get_stats(&self) -> FactoryStats {
+        // Covers the settings the foundation can change as well as the fixed configuration.
+        let config_json = unc_sdk::serde_json::json!({
+            "config": self.get_config(),
+            "lockup_new_gas": self.get_lockup_new_gas(),
+            "callback_gas": self.get_callback_gas(),
+            "creation_fee": self.get_creation_fee(),
+            "default_release_duration": self.get_default_release_duration(),
+        })
+        .to_string();
+        FactoryStats {
+            lockups_created: self.lockups_created,
+            active_lockups: self.lockups.len() as u64,
+            total_deployed_balance: self.total_deployed_balance.as_attounc().into(),
+            pending_refunds_count: self.pending_refunds.len() as u64,
+            config_hash: hex::encode(env::sha256(config_json.as_bytes())),
+        }
+    }
+
+    /// Returns the amount of gas attached to the lockup `new` call.
+    pub fn get_lockup_new_gas(&self) -> U64 {
+        self.lockup_new_gas.as_gas().into()
+    }
+
+    /// Returns the amount of gas attached to the `on_lockup_create` callback.
+    pub fn get_callback_gas(&self) -> U64 {
+        self.callback_gas.as_gas().into()
+    }
+
+    /// Updates the amount of gas attached to the lockup `new` call.
+    /// Can only be called by the foundation.
+    pub fn set_lockup_new_gas(&mut self, lockup_new_gas: U64) {
+        self.assert_called_by_foundation();
+        assert!(lockup_new_gas.0 > 0, "Gas should be positive");
+        self.lockup_new_gas = Gas::from_gas(lockup_new_gas.0);
+    }
+
+    /// Updates the amount of gas attached to the `on_lockup_create` callback.
+    /// Can only be called by the foundation.
+    pub fn set_callback_gas(&mut self, callback_gas: U64) {
+        self.assert_called_by_foundation();
+        assert!(callback_gas.0 > 0, "Gas should be positive");
+        self.callback_gas = Gas::from_gas(callback_gas.0);
+    }
+
+    /// Returns the fee charged on top of the lockup funding for every created lockup.
+    pub fn get_creation_fee(&self) -> U128 {
+        self.creation_fee.as_attounc().into()
+    }
+
+    /// Returns the creation fees collected by the factory that are not withdrawn yet.
+    pub fn get_collected_fees(&self) -> U128 {
+        self.collected_fees.as_attounc().into()
+    }
+
+    /// Updates the fee charged on top of the lockup funding for every created lockup.
+    /// Can only be called by the foundation.
+    pub fn set_creation_fee(&mut self, creation_fee: U128) {
+        self.assert_called_by_foundation();
+        self.creation_fee = UncToken::from_attounc(creation_fee.0);
+    }
+
+    /// Transfers all collected creation fees to the given receiver.
+    /// Can only be called by the foundation.
+    pub fn withdraw_collected_fees(&mut self, receiver_id: AccountId) -> Promise {
+        self.assert_called_by_foundation();
+        let amount = self.collected_fees;
+        assert!(!amount.is_zero(), "There are no collected fees to withdraw");
+
+        self.collected_fees = UncToken::from_attounc(0);
+        env::log_str(
+            format!("Withdrawing collected fees of {} to {}", amount.as_attounc(), receiver_id)
+                .as_str(),
+        );
+        Promise::new(receiver_id).transfer(amount)
+    }
+
+    /// Returns the release duration of the created lockups if the caller doesn't pass one.
+    pub fn get_default_release_duration(&self) -> Option<WrappedDuration> {
+        self.default_release_duration
+    }
+
+    /// Updates the release duration of the created lockups if the caller doesn't pass one.
+    /// Passing `null` removes the default. It doesn't affect the lockups created before.
+    /// Can only be called by the foundation.
+    pub fn set_default_release_duration(
+        &mut self,
+        default_release_duration: Option<WrappedDuration>,
+    ) {
+        self.assert_called_by_foundation();
+        self.default_release_duration = default_release_duration;
+    }
+
+    /// Returns the maximum lockup amount for the given owner account ID, or `null` if the owner
+    /// is not limited.
+    pub fn get_owner_cap(&self, owner_account_id: AccountId) -> Option<U128> {
+        self.owner_caps.get(&owner_account_id).copied()
+    }
+
+    /// Updates the maximum lockup amount for the given owner account ID. The lockup amount is the
+    /// attached deposit without the creation fee. Passing `null` removes the cap.
+    /// Can only be called by the foundation.
+    pub fn set_owner_cap(&mut self, owner_account_id: AccountId, cap: Option<U128>) {
+        self.assert_called_by_foundation();
+        match cap {
+            Some(cap) => self.owner_caps.insert(owner_account_id, cap),
+            None => self.owner_caps.remove(&owner_account_id),
+        };
+    }
+
+    /// Returns `true` if only the attested owners can get a lockup created.
+    pub fn is_attestation_required(&self) -> bool {
+        self.require_attestation
+    }
+
+    /// Returns `true` if the given owner account ID is attested by the foundation.
+    pub fn is_owner_attested(&self, owner_account_id: AccountId) -> bool {
+        self.attested_owners.contains(&owner_account_id)
+    }
+
+    /// Enables or disables the requirement for the owners to be attested.
+    /// Can only be called by the foundation.
+    pub fn set_require_attestation(&mut self, require_attestation: bool) {
+        self.assert_called_by_foundation();
+        self.require_attestation = require_attestation;
+    }
+
+    /// Adds the given owner account ID to the attested owners.
+    /// Returns `false` if the owner was already attested.
+    /// Can only be called by the foundation.
+    pub fn add_attested_owner(&mut self, owner_account_id: AccountId) -> bool {
+        self.assert_called_by_foundation();
+        self.attested_owners.insert(owner_account_id)
+    }
+
+    /// Removes the given owner account ID from the attested owners.
+    /// Returns `false` if the owner was not attested.
+    /// Can only be called by the foundation.
+    pub fn remove_attested_owner(&mut self, owner_account_id: AccountId) -> bool {
+        self.assert_called_by_foundation();
+        self.attested_owners.remove(&owner_account_id)
+    }
+
+    /// Returns the hash the lockup code has to match before it's deployed, if set.
+    pub fn get_expected_lockup_code_hash(&self) -> Option<Base58CryptoHash> {
+        self.expected_lockup_code_hash.map(Base58CryptoHash::from)
+    }
+
+    /// Updates the hash the lockup code has to match before it's deployed, both on the lockup
+    /// creation and on the lockup upgrade. Passing `null` disables the check.
+    /// Can only be called by the foundation.
+    pub fn set_expected_lockup_code_hash(&mut self, expected_lockup_code_hash: Option<Base58CryptoHash>) {
+        self.assert_called_by_foundation();
+        self.expected_lockup_code_hash = expected_lockup_code_hash.map(CryptoHash::from);
+    }
+
+    /// Returns the version of the uploaded lockup code, or `None` if the created lockups get the
+    /// code built into the factory.
+    pub fn get_lockup_code_version(&self) -> Option<String> {
+        self.lockup_code_version.clone()
+    }
+
+    /// Uploads the lockup code deployed to the lockups created from now on, together with its
+    /// version. The code has to match the expected hash, if it's set.
+    /// Can only be called by the foundation.
+    pub fn set_lockup_code(&mut self, code: Vec<u8>, version: String) {
+        self.assert_called_by_foundation();
+        assert!(!code.is_empty(), "The lockup code can't be empty");
+        assert!(!version.is_empty(), "The lockup code version can't be empty");
+        self.assert_lockup_code_hash(&code);
+
+        env::log_str(format!("Uploading the lockup code version {}", version).as_str());
+        env::storage_write(LOCKUP_CODE_KEY, &code);
+        self.lockup_code_version = Some(version);
+    }
+
+    /// Returns the deposits that are not settled by the creation callback yet, together with the
+    /// accounts they have to be refunded to.
+    pub fn get_pending_refunds(&self) -> Vec<(AccountId, U128)> {
+        self.pending_refunds.iter().map(|(account_id, amount)| (account_id.clone(), *amount)).collect()
+    }
+
+    /// Returns the lockup account IDs whose creation started but the creation callback didn't
+    /// resolve yet, e.g. to diagnose stuck callbacks.
+    pub fn get_pending_creations(&self) -> Vec<AccountId> {
+        self.pending_creations.keys().cloned().collect()
+    }
+
+    /// Transfers the given amount from the factory balance to the given receiver.
+    /// Intended to manually recover deposits stranded when `on_lockup_create` didn't run.
+    /// Can only be called by the foundation.
+    pub fn refund_stuck_deposit(&mut self, receiver_id: AccountId, amount: U128) -> Promise {
+        self.assert_called_by_foundation();
+        assert!(amount.0 > 0, "Amount should be positive");
+        assert!(
+            env::account_balance() >= UncToken::from_attounc(amount.0),
+            "Not enough balance on the factory to refund"
+        );
+
+        // The refund is allowed even if the deposit isn't tracked, e.g. for older deposits.
+        self.internal_settle_pending_refund(&receiver_id, amount.0);
+        env::log_str(
+            format!("Refunding stuck deposit of {} to {}", amount.0, receiver_id).as_str(),
+        );
+        Promise::new(receiver_id).transfer(UncToken::from_attounc(amount.0))
+    }
+
+    /// Called by a lockup created by this factory when it closes, e.g. after the vesting was
+    /// terminated and the account was deleted. Removes the lockup from the created lockups, so it
+    /// no longer counts as active and the owner can get a new lockup.
+    pub fn on_lockup_closed(&mut self, lockup_account_id: AccountId) {
+        let predecessor_account_id = env::predecessor_account_id();
+        let current_account_id = env::current_account_id();
+        assert!(
+            predecessor_account_id == lockup_account_id
+                && predecessor_account_id.get_parent_account_id()
+                    == Some(current_account_id.as_ref()),
+            "Can only be called by the lockup itself"
+        );
+        let owner_account_id = self
+            .lockups
+            .iter()
+            .find(|(_, account_id)| *account_id == &lockup_account_id)
+            .map(|(owner_account_id, _)| owner_account_id.clone())
+            .expect("The lockup account ID is not created by this factory");
+
+        self.lockups.remove(&owner_account_id);
+        env::log_str(
+            format!(
+                "The lockup contract {} of {} was closed",
+                lockup_account_id, owner_account_id
+            )
+            .as_str(),
+        );
+    }
+
+    /// Returns the lockup account ID created by this factory for the given owner, if any.
+    pub fn get_lockup_by_owner(&self, owner_account_id: AccountId) -> Option<AccountId> {
+        self.lockups.get(&owner_account_id).cloned()
+    }
+
+    /// Returns up to the limit of created lockups starting from the given offset, as pairs of the
+    /// owner account ID and the lockup account ID.
+    pub fn list_lockups(&self, from_index: u64, limit: u64) -> Vec<(AccountId, AccountId)> {
+        self.lockups
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|(owner_account_id, lockup_account_id)| {
+                (owner_account_id.clone(), lockup_account_id.clone())
+            })
+            .collect()
+    }
+
+    /// Validates the lockup creation arguments for the given amount without creating the lockup.
+    /// Doesn't panic on invalid arguments, but reports the problems instead.
+    pub fn validate_create(
+        &self,
+        owner_account_id: AccountId,
+        lockup_duration: WrappedDuration,
+        lockup_timestamp: Option<WrappedTimestamp>,
+        vesting_schedule: Option<VestingScheduleOrHash>,
+        release_duration: Option<WrappedDuration>,
+        amount: U128,
+    ) -> ValidationReport {
+        // The lockup durations and timestamp are accepted as is by the lockup contract.
+        let _ = (lockup_duration, lockup_timestamp, release_duration);
+        let vesting_schedule_errors = match &vesting_schedule {
+            Some(VestingScheduleOrHash::VestingSchedule(vesting_schedule)) => {
+                vesting_schedule.ordering_errors()
+            }
+            Some(VestingScheduleOrHash::VestingTranches(tranches)) => {
+                // The lockup receives the deposit without the creation fee.
+                vesting_tranches_errors(tranches, amount.0.saturating_sub(self.creation_fee.as_attounc()))
+            }
+            // The hashed vesting schedule is opaque and can't be checked.
+            Some(VestingScheduleOrHash::VestingHash(_)) | None => vec![],
+        };
+        ValidationReport {
+            lockup_account_id: Self::internal_lockup_account_id(&owner_account_id),
+            deposit_sufficient: UncToken::from_attounc(amount.0)
+                >= self.internal_required_deposit(
+                    vesting_schedule.is_some(),
+                    UncToken::from_attounc(0),
+                ),
+            vesting_schedule_errors,
+        }
+    }
+
+    /// Creates the lockup at the account ID derived from the owner account ID.
+    /// When `verify_whitelist` is `true`, the lockup is only created once the staking pool
+    /// whitelist responds to a view call, otherwise the deposit is refunded. It costs an extra
+    /// cross-contract call, so it defaults to `false`.
+    /// The foundation account is embedded into the lockup when the lockup has a vesting schedule,
+    /// or when `with_foundation` is `true`, e.g. to keep the administrative control over a lockup
+    /// without vesting. Defaults to `false`.
     #[payable]
     pub fn create(
         &mut self,
@@ -107,149 +536,2346 @@ impl LockupFactory {
         vesting_schedule: Option<VestingScheduleOrHash>,
         release_duration: Option<WrappedDuration>,
         whitelist_account_id: Option<AccountId>,
+        transfers_information: Option<TransfersInformation>,
+        verify_whitelist: Option<bool>,
+        with_foundation: Option<bool>,
     ) -> Promise {
-        assert!(env::attached_deposit() >= UncToken::from_attounc(MIN_ATTACHED_BALANCE), "Not enough attached deposit");
+        let lockup_account_id = Self::internal_lockup_account_id(&owner_account_id);
 
-        let byte_slice = env::sha256(owner_account_id.as_bytes());
-        let lockup_account_id: AccountId =
-            format!("{}.{}", hex::encode(&byte_slice[..20]), env::current_account_id()).parse().unwrap();
+        self.internal_create(
+            lockup_account_id,
+            owner_account_id,
+            lockup_duration,
+            lockup_timestamp,
+            vesting_schedule,
+            release_duration,
+            whitelist_account_id,
+            transfers_information,
+            with_foundation.unwrap_or(false),
+            None,
+            verify_whitelist.unwrap_or(false),
+        )
+    }
 
-        let mut foundation_account: Option<AccountId> = None;
-        if vesting_schedule.is_some() {
-            foundation_account = Some(self.foundation_account_id.clone());
-        };
+    /// Creates the lockup with transfers disabled until the given transfer poll contract reports
+    /// that transfers were voted in. Otherwise it's the same as `create`.
+    #[payable]
+    pub fn create_with_transfers_disabled(
+        &mut self,
+        owner_account_id: AccountId,
+        lockup_duration: WrappedDuration,
+        lockup_timestamp: Option<WrappedTimestamp>,
+        vesting_schedule: Option<VestingScheduleOrHash>,
+        release_duration: Option<WrappedDuration>,
+        whitelist_account_id: Option<AccountId>,
+        transfer_poll_account_id: AccountId,
+    ) -> Promise {
+        let lockup_account_id = Self::internal_lockup_account_id(&owner_account_id);
+        // The account ID type can't be empty, so only the lockup itself has to be rejected.
+        assert_ne!(
+            transfer_poll_account_id, lockup_account_id,
+            "The transfer poll account ID can't be the lockup account ID"
+        );
 
-        // Defaults to the whitelist account ID given on init call.
-        let staking_pool_whitelist_account_id = if let Some(account_id) = whitelist_account_id {
-            account_id.into()
-        } else {
-            self.whitelist_account_id.clone()
-        };
+        self.internal_create(
+            lockup_account_id,
+            owner_account_id,
+            lockup_duration,
+            lockup_timestamp,
+            vesting_schedule,
+            release_duration,
+            whitelist_account_id,
+            Some(TransfersInformation::TransfersDisabled { transfer_poll_account_id }),
+            false,
+            None,
+            false,
+        )
+    }
+
+    /// Creates the lockup at `{account_prefix}.{current_account_id}` instead of the account ID
+    /// derived from the owner account ID. Otherwise it's the same as `create`.
+    #[payable]
+    pub fn create_with_name(
+        &mut self,
+        account_prefix: String,
+        owner_account_id: AccountId,
+        lockup_duration: WrappedDuration,
+        lockup_timestamp: Option<WrappedTimestamp>,
+        vesting_schedule: Option<VestingScheduleOrHash>,
+        release_duration: Option<WrappedDuration>,
+        whitelist_account_id: Option<AccountId>,
+        transfers_information: Option<TransfersInformation>,
+    ) -> Promise {
+        assert!(
+            is_valid_account_id_label(&account_prefix),
+            "The account prefix is not a valid account ID label"
+        );
+        let lockup_account_id = format!("{}.{}", account_prefix, env::current_account_id());
+        assert!(
+            lockup_account_id.len() <= MAX_ACCOUNT_ID_LEN,
+            "The lockup account ID can't be more than {} characters",
+            MAX_ACCOUNT_ID_LEN
+        );
+        let lockup_account_id: AccountId = lockup_account_id.parse().unwrap();
+        // Creating an existing account fails anyway, but the lockups known to the factory can be
+        // rejected before the deposit is sent.
+        assert!(
+            !self.lockups.values().any(|account_id| account_id == &lockup_account_id),
+            "The lockup account ID already exists"
+        );
+
+        self.internal_create(
+            lockup_account_id,
+            owner_account_id,
+            lockup_duration,
+            lockup_timestamp,
+            vesting_schedule,
+            release_duration,
+            whitelist_account_id,
+            transfers_information,
+            false,
+            None,
+            false,
+        )
+    }
+
+    /// Creates the lockup that selects the given staking pool on init and, once the staking pool
+    /// passes the whitelist check, deposits and stakes the lockup balance to it. The creation
+    /// doesn't depend on the staking: if the whitelist check or the staking fails, the lockup is
+    /// still created without staking. Otherwise it's the same as `create`.
+    #[payable]
+    pub fn create_and_stake(
+        &mut self,
+        owner_account_id: AccountId,
+        lockup_duration: WrappedDuration,
+        lockup_timestamp: Option<WrappedTimestamp>,
+        vesting_schedule: Option<VestingScheduleOrHash>,
+        release_duration: Option<WrappedDuration>,
+        whitelist_account_id: Option<AccountId>,
+        transfers_information: Option<TransfersInformation>,
+        staking_pool_account_id: AccountId,
+    ) -> Promise {
+        let lockup_account_id = Self::internal_lockup_account_id(&owner_account_id);
+
+        self.internal_create(
+            lockup_account_id,
+            owner_account_id,
+            lockup_duration,
+            lockup_timestamp,
+            vesting_schedule,
+            release_duration,
+            whitelist_account_id,
+            transfers_information,
+            false,
+            Some(staking_pool_account_id),
+            false,
+        )
+    }
 
-        let transfers_enabled: WrappedTimestamp = TRANSFERS_STARTED.into();
+    /// Deploys the given contract code to the lockup created by this factory and migrates the
+    /// lockup state in the same transaction.
+    /// Can only be called by the foundation.
+    pub fn upgrade_lockup(&mut self, lockup_account_id: AccountId, code: Vec<u8>) -> Promise {
+        self.assert_called_by_foundation();
+        self.assert_created_lockup(&lockup_account_id);
+        self.assert_lockup_code_hash(&code);
+
+        env::log_str(format!("Upgrading the lockup contract {}", lockup_account_id).as_str());
         Promise::new(lockup_account_id.clone())
-            .create_account()
-            .deploy_contract(CODE.to_vec())
-            .transfer(env::attached_deposit())
             .function_call(
-                "new".to_string(),
-                unc_sdk::serde_json::to_vec(&LockupArgs {
-                    owner_account_id,
-                    lockup_duration,
-                    lockup_timestamp,
-                    transfers_information: TransfersInformation::TransfersEnabled {
-                        transfers_timestamp: transfers_enabled,
-                    },
-                    vesting_schedule,
-                    release_duration,
-                    staking_pool_whitelist_account_id,
-                    foundation_account_id: foundation_account,
-                })
+                "upgrade".to_string(),
+                unc_sdk::serde_json::to_vec(&unc_sdk::serde_json::json!({ "code": code }))
                     .unwrap(),
                 NO_DEPOSIT,
-                gas::LOCKUP_NEW,
+                gas::LOCKUP_UPGRADE,
             )
             .then(ext_self::ext(env::current_account_id())
-                .with_static_gas(gas::CALLBACK)
+                .with_static_gas(self.callback_gas)
                 .with_attached_deposit(NO_DEPOSIT)
-                .on_lockup_create(
+                .on_lockup_upgrade(lockup_account_id))
+    }
+
+    /// Callback after a lockup was upgraded.
+    /// Returns `true` if the lockup code was deployed and the state was migrated.
+    pub fn on_lockup_upgrade(&mut self, lockup_account_id: AccountId) -> bool {
+        assert_self();
+
+        if is_promise_success() {
+            env::log_str(
+                format!("The lockup contract {} was successfully upgraded.", lockup_account_id)
+                    .as_str(),
+            );
+            true
+        } else {
+            env::log_str(
+                format!("The lockup contract {} upgrade has failed.", lockup_account_id).as_str(),
+            );
+            false
+        }
+    }
+
+    /// Callback after the staking pool whitelist was checked before creating the lockup.
+    /// Creates the lockup if the whitelist responded. Otherwise refunds the attached deposit,
+    /// including the creation fee, and returns `false`.
+    pub fn on_whitelist_verified(
+        &mut self,
+        lockup_account_id: AccountId,
+        lockup_args: LockupArgs,
+        lockup_new_gas: U64,
+        attached_deposit: U128,
+        predecessor_account_id: AccountId,
+        creation_fee: U128,
+    ) -> PromiseOrValue<bool> {
+        assert_self();
+
+        if is_promise_success() {
+            return self
+                .internal_deploy_lockup(
                     lockup_account_id,
-                    env::attached_deposit().as_attounc().into(),
-                    env::predecessor_account_id(),
-            ))
+                    lockup_args,
+                    Gas::from_gas(lockup_new_gas.0),
+                    UncToken::from_attounc(attached_deposit.0),
+                    predecessor_account_id,
+                    UncToken::from_attounc(creation_fee.0),
+                )
+                .into();
+        }
+
+        self.pending_creations.remove(&lockup_account_id);
+        env::log_str(
+            format!(
+                "The whitelist @{} is unreachable. The lockup {} is not created",
+                lockup_args.staking_pool_whitelist_account_id, lockup_account_id
+            )
+            .as_str(),
+        );
+        // A replayed callback finds the deposit already settled and doesn't refund it again.
+        if self.internal_settle_pending_refund(&predecessor_account_id, attached_deposit.0) {
+            env::log_str(
+                format!(
+                    "Returning attached deposit of {} to {}",
+                    attached_deposit.0, predecessor_account_id
+                )
+                .as_str(),
+            );
+            Promise::new(predecessor_account_id)
+                .transfer(UncToken::from_attounc(attached_deposit.0));
+        }
+        PromiseOrValue::Value(false)
     }
 
     /// Callback after a lockup was created.
-    /// Returns the promise if the lockup creation succeeded.
-    /// Otherwise refunds the attached deposit and returns `false`.
+    /// Records the lockup for the owner, collects the creation fee and returns `true` if the
+    /// lockup creation succeeded. Otherwise refunds the attached deposit, including the creation
+    /// fee, and returns `false`.
     pub fn on_lockup_create(
         &mut self,
         lockup_account_id: AccountId,
         attached_deposit: U128,
         predecessor_account_id: AccountId,
+        owner_account_id: AccountId,
+        creation_fee: U128,
     ) -> bool {
         assert_self();
 
         let lockup_account_created = is_promise_success();
+        self.pending_creations.remove(&lockup_account_id);
+        // A replayed callback finds the deposit already settled and doesn't refund it again.
+        let deposit_pending =
+            self.internal_settle_pending_refund(&predecessor_account_id, attached_deposit.0);
+
+        if lockup_account_created {
+            self.lockups.insert(owner_account_id.clone(), lockup_account_id.clone());
+            if deposit_pending {
+                self.collected_fees =
+                    self.collected_fees.saturating_add(UncToken::from_attounc(creation_fee.0));
+                self.lockups_created += 1;
+                self.total_deployed_balance = self.total_deployed_balance.saturating_add(
+                    UncToken::from_attounc(attached_deposit.0.saturating_sub(creation_fee.0)),
+                );
+            }
+            env::log_str(
+                format!("The lockup contract {} was successfully created.", lockup_account_id)
+                    .as_str(),
+            );
+            // The version of the code uploaded when the callback runs, which is the deployed one
+            // unless the code was replaced while the lockup was being created.
+            LockupFactoryEvent::LockupCreated {
+                lockup_account_id,
+                owner_account_id,
+                lockup_code_version: self.lockup_code_version.clone(),
+            }
+            .emit();
+            true
+        } else if !deposit_pending {
+            env::log_str(
+                format!(
+                    "The lockup {} creation has failed, but the attached deposit of {} is not pending for {}",
+                    lockup_account_id, attached_deposit.0, predecessor_account_id
+                )
+                    .as_str(),
+            );
+            false
+        } else {
+            env::log_str(
+                format!(
+                    "The lockup {} creation has failed. Returning attached deposit of {} to {}",
+                    lockup_account_id, attached_deposit.0, predecessor_account_id
+                )
+                    .as_str(),
+            );
+            Promise::new(predecessor_account_id).transfer(UncToken::from_attounc(attached_deposit.0));
+            false
+        }
+    }
+}
+
+impl LockupFactory {
+    /// Asserts that the lockup account ID was created by this factory.
+    fn assert_created_lockup(&self, lockup_account_id: &AccountId) {
+        assert!(
+            self.lockups.values().any(|account_id| account_id == lockup_account_id),
+            "The lockup account ID is not created by this factory"
+        );
+    }
+
+    /// Asserts that the given lockup code matches the expected hash, if it's set.
+    fn assert_lockup_code_hash(&self, code: &[u8]) {
+        if let Some(expected_lockup_code_hash) = &self.expected_lockup_code_hash {
+            assert!(
+                &env::sha256_array(code) == expected_lockup_code_hash,
+                "The lockup code doesn't match the expected hash"
+            );
+        }
+    }
+
+    /// Returns the lockup code to deploy: the uploaded code, or the code built into the factory.
+    fn internal_lockup_code(&self) -> Vec<u8> {
+        env::storage_read(LOCKUP_CODE_KEY).unwrap_or_else(|| CODE.to_vec())
+    }
+
+    /// Returns the lockup account ID derived from the owner account ID.
+    fn internal_lockup_account_id(owner_account_id: &AccountId) -> AccountId {
+        let byte_slice = env::sha256(owner_account_id.as_bytes());
+        format!("{}.{}", hex::encode(&byte_slice[..20]), env::current_account_id()).parse().unwrap()
+    }
+
+    /// Creates the lockup account with the given ID, deploys the lockup contract and initializes
+    /// it with the given arguments. The lockup stakes to the given staking pool on init, if any.
+    fn internal_create(
+        &mut self,
+        lockup_account_id: AccountId,
+        owner_account_id: AccountId,
+        lockup_duration: WrappedDuration,
+        lockup_timestamp: Option<WrappedTimestamp>,
+        vesting_schedule: Option<VestingScheduleOrHash>,
+        release_duration: Option<WrappedDuration>,
+        whitelist_account_id: Option<AccountId>,
+        transfers_information: Option<TransfersInformation>,
+        with_foundation: bool,
+        staking_pool_account_id: Option<AccountId>,
+        verify_whitelist: bool,
+    ) -> Promise {
+        self.assert_lockup_code_hash(&self.internal_lockup_code());
+        // The lockup needs extra gas to check the whitelist and stake on init.
+        let lockup_new_gas = if staking_pool_account_id.is_some() {
+            self.lockup_new_gas.saturating_add(gas::LOCKUP_STAKE)
+        } else {
+            self.lockup_new_gas
+        };
+        // The whitelist check delays the creation to the callback, which needs the gas of `create`.
+        let whitelist_check_gas = if verify_whitelist {
+            gas::WHITELIST_CHECK.saturating_add(gas::CREATE)
+        } else {
+            Gas::from_gas(0)
+        };
+        self.assert_gas_within_transaction_limit(lockup_new_gas.saturating_add(whitelist_check_gas));
+        let creation_fee = self.creation_fee;
+        assert!(
+            env::attached_deposit()
+                >= self.internal_required_deposit(
+                    vesting_schedule.is_some(),
+                    UncToken::from_attounc(0),
+                ),
+            "Not enough attached deposit"
+        );
+        // The lockup receives the deposit without the creation fee.
+        let lockup_amount = env::attached_deposit().saturating_sub(creation_fee);
+        if let Some(cap) = self.owner_caps.get(&owner_account_id) {
+            assert!(
+                lockup_amount.as_attounc() <= cap.0,
+                "The lockup amount exceeds the cap of the owner"
+            );
+        }
+        assert!(
+            !self.require_attestation || self.attested_owners.contains(&owner_account_id),
+            "The owner account ID is not attested"
+        );
+        // The hashed vesting schedule is opaque, so only the explicit one can be checked.
+        match &vesting_schedule {
+            Some(VestingScheduleOrHash::VestingSchedule(vesting_schedule)) => {
+                vesting_schedule.assert_valid();
+            }
+            Some(VestingScheduleOrHash::VestingTranches(tranches)) => {
+                if let Some(error) =
+                    vesting_tranches_errors(tranches, lockup_amount.as_attounc()).first()
+                {
+                    panic!("{}", error);
+                }
+            }
+            Some(VestingScheduleOrHash::VestingHash(_)) | None => {}
+        }
+        // The factory tracks a single lockup per owner, and the lockup account ID derived from the
+        // owner account ID would collide with the existing one.
+        assert!(
+            !self.lockups.contains_key(&owner_account_id),
+            "The lockup for the owner account ID already exists"
+        );
+
+        // Tracks the deposit until the callback settles it, so it can be recovered manually.
+        self.internal_add_pending_refund(&env::predecessor_account_id(), env::attached_deposit().as_attounc());
+        self.pending_creations.insert(lockup_account_id.clone(), owner_account_id.clone());
+
+        let mut foundation_account: Option<AccountId> = None;
+        if vesting_schedule.is_some() || with_foundation {
+            foundation_account = Some(self.foundation_account_id.clone());
+        };
+
+        // Defaults to the whitelist account ID given on init call.
+        let staking_pool_whitelist_account_id = if let Some(account_id) = whitelist_account_id {
+            assert_not_self_whitelist(&account_id);
+            account_id
+        } else {
+            self.whitelist_account_id.clone()
+        };
+
+        // Defaults to the release duration configured on the factory, if any.
+        let release_duration = release_duration.or(self.default_release_duration);
+
+        // Defaults to transfers enabled at the moment transfers were started on the network.
+        let transfers_information =
+            transfers_information.unwrap_or(TransfersInformation::TransfersEnabled {
+                transfers_timestamp: TRANSFERS_STARTED.into(),
+            });
+
+        let lockup_args = LockupArgs {
+            owner_account_id,
+            lockup_duration,
+            lockup_timestamp,
+            transfers_information,
+            vesting_schedule,
+            release_duration,
+            staking_pool_whitelist_account_id,
+            foundation_account_id: foundation_account,
+            staking_pool_account_id,
+        };
+        if verify_whitelist {
+            env::log_str(
+                format!(
+                    "Checking the whitelist @{} before creating the lockup",
+                    lockup_args.staking_pool_whitelist_account_id
+                )
+                .as_str(),
+            );
+            ext_whitelist::ext(lockup_args.staking_pool_whitelist_account_id.clone())
+                .with_static_gas(gas::WHITELIST_CHECK)
+                .with_attached_deposit(NO_DEPOSIT)
+                .is_factory_whitelisted(env::current_account_id())
+                .then(ext_self::ext(env::current_account_id())
+                    .with_static_gas(
+                        gas::CREATE
+                            .saturating_add(lockup_new_gas)
+                            .saturating_add(self.callback_gas),
+                    )
+                    .with_attached_deposit(NO_DEPOSIT)
+                    .on_whitelist_verified(
+                        lockup_account_id,
+                        lockup_args,
+                        lockup_new_gas.as_gas().into(),
+                        env::attached_deposit().as_attounc().into(),
+                        env::predecessor_account_id(),
+                        creation_fee.as_attounc().into(),
+                ))
+        } else {
+            self.internal_deploy_lockup(
+                lockup_account_id,
+                lockup_args,
+                lockup_new_gas,
+                env::attached_deposit(),
+                env::predecessor_account_id(),
+                creation_fee,
+            )
+        }
+    }
+
+    /// Creates the lockup account, deploys the current lockup code and initializes it with the
+    /// given arguments. The given deposit without the creation fee is transferred to the lockup.
+    fn internal_deploy_lockup(
+        &self,
+        lockup_account_id: AccountId,
+        lockup_args: LockupArgs,
+        lockup_new_gas: Gas,
+        attached_deposit: UncToken,
+        predecessor_account_id: AccountId,
+        creation_fee: UncToken,
+    ) -> Promise {
+        let owner_account_id = lockup_args.owner_account_id.clone();
+        Promise::new(lockup_account_id.clone())
+            .create_account()
+            .deploy_contract(self.internal_lockup_code())
+            // The creation fee stays on the factory.
+            .transfer(attached_deposit.saturating_sub(creation_fee))
+            .function_call(
+                "new".to_string(),
+                unc_sdk::serde_json::to_vec(&lockup_args).unwrap(),
+                NO_DEPOSIT,
+                lockup_new_gas,
+            )
+            .then(ext_self::ext(env::current_account_id())
+                .with_static_gas(self.callback_gas)
+                .with_attached_deposit(NO_DEPOSIT)
+                .on_lockup_create(
+                    lockup_account_id,
+                    attached_deposit.as_attounc().into(),
+                    predecessor_account_id,
+                    owner_account_id,
+                    creation_fee.as_attounc().into(),
+            ))
+    }
+
+    /// Panics if the given gas attached to the lockup `new` call and the gas of the callback
+    /// together with the gas for `create` itself doesn't fit into a single transaction.
+    fn assert_gas_within_transaction_limit(&self, lockup_new_gas: Gas) {
+        let total_gas = lockup_new_gas
+            .as_gas()
+            .saturating_add(self.callback_gas.as_gas())
+            .saturating_add(gas::CREATE.as_gas());
+        assert!(
+            total_gas <= gas::MAX_TRANSACTION.as_gas(),
+            "The configured gas exceeds the transaction gas limit"
+        );
+    }
+
+    /// Returns the minimum attached balance plus the given lockup amount, the storage of the
+    /// vesting if needed and the creation fee.
+    fn internal_required_deposit(&self, with_vesting: bool, lockup_amount: UncToken) -> UncToken {
+        let vesting_storage = if with_vesting {
+            env::storage_byte_cost().saturating_mul(VESTING_STORAGE_BYTES)
+        } else {
+            UncToken::from_attounc(0)
+        };
+        UncToken::from_attounc(MIN_ATTACHED_BALANCE)
+            .saturating_add(lockup_amount)
+            .saturating_add(vesting_storage)
+            .saturating_add(self.creation_fee)
+    }
+
+    /// Asserts that the method was called by the foundation.
+    fn assert_called_by_foundation(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.foundation_account_id,
+            "Can only be called by the foundation"
+        );
+    }
+
+    fn internal_add_pending_refund(&mut self, account_id: &AccountId, amount: u128) {
+        let pending = self.pending_refunds.get(account_id).map(|amount| amount.0).unwrap_or(0);
+        self.pending_refunds.insert(account_id.clone(), (pending + amount).into());
+    }
+
+    /// Removes the given amount from the pending deposits of the account.
+    /// Returns `true` if the whole amount was pending.
+    fn internal_settle_pending_refund(&mut self, account_id: &AccountId, amount: u128) -> bool {
+        let pending = self.pending_refunds.get(account_id).map(|amount| amount.0).unwrap_or(0);
+        let remaining = pending.saturating_sub(amount);
+        if remaining > 0 {
+            self.pending_refunds.insert(account_id.clone(), remaining.into());
+        } else {
+            self.pending_refunds.remove(account_id);
+        }
+        pending >= amount
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    mod test_utils;
+
+    use super::*;
+    use unc_sdk::{testing_env, test_vm_config, RuntimeFeesConfig, PromiseResult};
+    use unc_sdk::mock::MockAction;
+    use unc_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder};
+    use test_utils::*;
+
+    fn new_vesting_schedule(offset_in_days: u64) -> VestingSchedule {
+        VestingSchedule {
+            start_timestamp: to_ts(GENESIS_TIME_IN_DAYS - YEAR + offset_in_days).into(),
+            cliff_timestamp: to_ts(GENESIS_TIME_IN_DAYS + offset_in_days).into(),
+            end_timestamp: to_ts(GENESIS_TIME_IN_DAYS + YEAR * 3 + offset_in_days).into(),
+        }
+    }
+
+    #[test]
+    fn test_get_factory_vars() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let contract = LockupFactory::new(
+            whitelist_account_id(),
+            foundation_account_id(),
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .is_view(true)
+            .build());
+
+        assert_eq!(contract.get_min_attached_balance().0, MIN_ATTACHED_BALANCE);
+        assert_eq!(
+            contract.get_foundation_account_id(),
+            foundation_account_id()
+        );
+        println!("{}", contract.get_lockup_master_account_id());
+        assert_eq!(
+            contract.get_lockup_master_account_id(),
+            lockup_master_account_id()
+        );
+    }
+
+    #[test]
+    fn test_get_config() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let contract = LockupFactory::new(
+            whitelist_account_id(),
+            foundation_account_id(),
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .is_view(true)
+            .build());
+
+        assert_eq!(
+            contract.get_config(),
+            FactoryConfig {
+                foundation_account_id: foundation_account_id(),
+                whitelist_account_id: whitelist_account_id(),
+                lockup_master_account_id: lockup_master_account_id(),
+                min_attached_balance: MIN_ATTACHED_BALANCE.into(),
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The account ID of this contract can't be more than 23 characters")]
+    fn test_new_with_too_long_account_id() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id("lockup-factory-too-long1".parse().unwrap())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        LockupFactory::new(whitelist_account_id(), foundation_account_id());
+    }
+
+    #[test]
+    #[should_panic(expected = "The whitelist account ID can't be the account ID of this contract")]
+    fn test_new_with_self_whitelist() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        LockupFactory::new(account_factory(), foundation_account_id());
+    }
+
+    #[test]
+    fn test_new_with_distinct_whitelist() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        assert_eq!(contract.get_config().whitelist_account_id, whitelist_account_id());
+    }
+
+    #[test]
+    #[should_panic(expected = "The whitelist account ID can't be the account ID of this contract")]
+    fn test_create_lockup_with_self_whitelist() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(
+            account_tokens_owner(),
+            0.into(),
+            None,
+            None,
+            None,
+            Some(account_factory()),
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_new_with_longest_account_id() {
+        let factory_account_id: AccountId = "lockup-factory-23-chars".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(factory_account_id.clone())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        let lockup_account_id = LockupFactory::internal_lockup_account_id(&account_tokens_owner());
+        assert_eq!(lockup_account_id.len(), MAX_ACCOUNT_ID_LEN);
+    }
+
+    #[test]
+    fn test_create_lockup_success() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(
+            whitelist_account_id(),
+            foundation_account_id(),
+        );
+
+        const LOCKUP_DURATION: u64 = 63036000000000000; /* 24 months */
+        let lockup_duration: WrappedTimestamp = LOCKUP_DURATION.into();
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .is_view(false)
+            .build());
+
+        contract.create(account_tokens_owner(), lockup_duration, None, None, None, None, None, None, None);
+
+        let context = VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_factory())
+            .attached_deposit(UncToken::from_attounc(ntoy(0)))
+            .is_view(false)
+            .build();
+
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        println!("{}", lockup_account());
+        contract.on_lockup_create(
+            lockup_account(),
+            ntoy(30).into(),
+            account_tokens_owner(),
+            account_tokens_owner(),
+            0.into(),
+        );
+    }
+
+    fn create_lockup_for_tokens_owner(contract: &mut LockupFactory) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(account_factory())
+                .predecessor_account_id(account_factory())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.on_lockup_create(
+            lockup_account(),
+            ntoy(35).into(),
+            account_tokens_owner(),
+            account_tokens_owner(),
+            0.into(),
+        ));
+    }
+
+    #[test]
+    fn test_create_lockup_records_lockup_by_owner() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        assert_eq!(contract.get_lockup_by_owner(account_tokens_owner()), None);
+
+        create_lockup_for_tokens_owner(&mut contract);
+
+        assert_eq!(
+            contract.get_lockup_by_owner(account_tokens_owner()),
+            Some(lockup_account())
+        );
+        assert_eq!(
+            contract.list_lockups(0, 10),
+            vec![(account_tokens_owner(), lockup_account())]
+        );
+        assert!(contract.list_lockups(1, 10).is_empty());
+    }
+
+    fn close_lockup_by(
+        contract: &mut LockupFactory,
+        predecessor_account_id: AccountId,
+        lockup_account_id: AccountId,
+    ) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(predecessor_account_id)
+            .build());
+        contract.on_lockup_closed(lockup_account_id);
+    }
+
+    #[test]
+    fn test_on_lockup_closed() {
+        let mut contract = new_factory_with_creation_fee(0);
+        create_lockup_for_tokens_owner(&mut contract);
+        assert_eq!(contract.get_stats().active_lockups, 1);
+
+        close_lockup_by(&mut contract, lockup_account(), lockup_account());
+        assert_eq!(contract.get_lockup_by_owner(account_tokens_owner()), None);
+        let stats = contract.get_stats();
+        assert_eq!(stats.active_lockups, 0);
+        assert_eq!(stats.lockups_created, 1);
+
+        // The owner can get a new lockup once the closed one is removed.
+        create_lockup_for_tokens_owner(&mut contract);
+        assert_eq!(contract.get_lockup_by_owner(account_tokens_owner()), Some(lockup_account()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the lockup itself")]
+    fn test_on_lockup_closed_by_unrelated_account() {
+        let mut contract = new_factory_with_creation_fee(0);
+        create_lockup_for_tokens_owner(&mut contract);
+        close_lockup_by(&mut contract, account_tokens_owner(), lockup_account());
+    }
+
+    #[test]
+    #[should_panic(expected = "The lockup account ID is not created by this factory")]
+    fn test_on_lockup_closed_by_unknown_sub_account() {
+        let mut contract = new_factory_with_creation_fee(0);
+        create_lockup_for_tokens_owner(&mut contract);
+        let sub_account_id: AccountId = format!("other.{}", account_factory()).parse().unwrap();
+        close_lockup_by(&mut contract, sub_account_id.clone(), sub_account_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "The lockup for the owner account ID already exists")]
+    fn test_create_lockup_twice_for_same_owner() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        create_lockup_for_tokens_owner(&mut contract);
+        create_lockup_for_tokens_owner(&mut contract);
+    }
+
+    #[test]
+    fn test_create_lockup_with_name() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        let lockup_account_id: AccountId = "tokens_owner-lockup.lockup".parse().unwrap();
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create_with_name(
+            "tokens_owner-lockup".to_string(),
+            account_tokens_owner(),
+            0.into(),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, lockup_account_id);
+
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(account_factory())
+                .predecessor_account_id(account_factory())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.on_lockup_create(
+            lockup_account_id.clone(),
+            ntoy(35).into(),
+            account_tokens_owner(),
+            account_tokens_owner(),
+            0.into(),
+        ));
+        assert_eq!(
+            contract.get_lockup_by_owner(account_tokens_owner()),
+            Some(lockup_account_id)
+        );
+    }
+
+    fn create_lockup_with_name(account_prefix: &str) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create_with_name(
+            account_prefix.to_string(),
+            account_tokens_owner(),
+            0.into(),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The account prefix is not a valid account ID label")]
+    fn test_create_lockup_with_uppercase_name() {
+        create_lockup_with_name("Owner");
+    }
+
+    #[test]
+    #[should_panic(expected = "The account prefix is not a valid account ID label")]
+    fn test_create_lockup_with_dotted_name() {
+        create_lockup_with_name("owner.lockup");
+    }
+
+    #[test]
+    #[should_panic(expected = "The lockup account ID can't be more than 64 characters")]
+    fn test_create_lockup_with_too_long_name() {
+        create_lockup_with_name(&"a".repeat(MAX_ACCOUNT_ID_LEN - account_factory().len()));
+    }
+
+    fn upgrade_lockup_by(
+        contract: &mut LockupFactory,
+        predecessor_account_id: AccountId,
+        lockup_account_id: AccountId,
+    ) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(predecessor_account_id)
+            .build());
+        contract.upgrade_lockup(lockup_account_id, vec![0, 97, 115, 109]);
+    }
+
+    #[test]
+    fn test_upgrade_lockup() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        create_lockup_for_tokens_owner(&mut contract);
+
+        upgrade_lockup_by(&mut contract, foundation_account_id(), lockup_account());
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, lockup_account());
+        match &receipts[0].actions[..] {
+            [MockAction::FunctionCallWeight { method_name, args, prepaid_gas, .. }] => {
+                assert_eq!(method_name, b"upgrade");
+                let args: unc_sdk::serde_json::Value =
+                    unc_sdk::serde_json::from_slice(args).unwrap();
+                assert_eq!(args["code"], unc_sdk::serde_json::json!([0, 97, 115, 109]));
+                assert_eq!(*prepaid_gas, gas::LOCKUP_UPGRADE);
+            }
+            actions => panic!("Unexpected actions {:?}", actions),
+        }
+        assert_eq!(receipts[1].receiver_id, account_factory());
+
+        for (promise_result, upgraded) in
+            [(PromiseResult::Successful(vec![]), true), (PromiseResult::Failed, false)]
+        {
+            testing_env!(
+                VMContextBuilder::new()
+                    .current_account_id(account_factory())
+                    .predecessor_account_id(account_factory())
+                    .build(),
+                test_vm_config(),
+                RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![promise_result],
+            );
+            assert_eq!(contract.on_lockup_upgrade(lockup_account()), upgraded);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the foundation")]
+    fn test_upgrade_lockup_by_non_foundation() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        create_lockup_for_tokens_owner(&mut contract);
+
+        upgrade_lockup_by(&mut contract, account_tokens_owner(), lockup_account());
+    }
+
+    #[test]
+    #[should_panic(expected = "The lockup account ID is not created by this factory")]
+    fn test_upgrade_unknown_lockup() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        upgrade_lockup_by(&mut contract, foundation_account_id(), lockup_account());
+    }
+
+    #[test]
+    fn test_create_lockup_twice_keeps_deposit() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        create_lockup_for_tokens_owner(&mut contract);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+        }));
+        // The second attempt fails in `create` itself, so the attached deposit is returned with
+        // the failed transaction instead of being sent along with the lockup creation.
+        assert!(result.is_err());
+        assert!(get_created_receipts().is_empty());
+        assert!(contract.get_pending_refunds().is_empty());
+        assert_eq!(
+            contract.get_lockup_by_owner(account_tokens_owner()),
+            Some(lockup_account())
+        );
+    }
+
+    #[test]
+    fn test_create_lockup_with_vesting_success() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(
+            whitelist_account_id(),
+            foundation_account_id(),
+        );
+
+        const LOCKUP_DURATION: u64 = 63036000000000000; /* 24 months */
+        const LOCKUP_TIMESTAMP: u64 = 1661990400000000000; /* 1 September 2022 00:00:00 */
+        let lockup_duration: WrappedTimestamp = LOCKUP_DURATION.into();
+        let lockup_timestamp: WrappedTimestamp = LOCKUP_TIMESTAMP.into();
+
+        let vesting_schedule = Some(new_vesting_schedule(10));
+
+        let vesting_schedule = vesting_schedule.map(|vesting_schedule| {
+            VestingScheduleOrHash::VestingHash(
+                VestingScheduleWithSalt { vesting_schedule, salt: SALT.to_vec().into() }
+                    .hash()
+                    .into(),
+            )
+        });
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .is_view(false)
+            .build());
+
+        contract.create(
+            account_tokens_owner(),
+            lockup_duration,
+            Some(lockup_timestamp),
+            vesting_schedule,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let context = VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_factory())
+            .attached_deposit(UncToken::from_attounc(ntoy(0)))
+            .build();
+
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_lockup_create(
+            lockup_account(),
+            ntoy(30).into(),
+            account_tokens_owner(),
+            account_tokens_owner(),
+            0.into(),
+        );
+    }
+
+    #[test]
+    fn test_create_lockup_with_custom_transfers_information() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        const LOCKUP_DURATION: u64 = 63036000000000000; /* 24 months */
+        const TRANSFERS_TIMESTAMP: u64 = 1661990400000000000; /* 1 September 2022 00:00:00 */
+        let lockup_duration: WrappedTimestamp = LOCKUP_DURATION.into();
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .is_view(false)
+            .build());
+
+        contract.create(
+            account_tokens_owner(),
+            lockup_duration,
+            None,
+            None,
+            None,
+            None,
+            Some(TransfersInformation::TransfersEnabled {
+                transfers_timestamp: TRANSFERS_TIMESTAMP.into(),
+            }),
+            None,
+            None,
+        );
+
+        let lockup_args = get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .find_map(|action| match action {
+                MockAction::FunctionCallWeight { method_name, args, .. }
+                    if method_name == b"new" =>
+                {
+                    Some(unc_sdk::serde_json::from_slice::<unc_sdk::serde_json::Value>(&args).unwrap())
+                }
+                _ => None,
+            })
+            .expect("Expected the lockup `new` call");
+        assert_eq!(
+            lockup_args["transfers_information"]["TransfersEnabled"]["transfers_timestamp"],
+            TRANSFERS_TIMESTAMP.to_string()
+        );
+    }
+
+    #[test]
+    fn test_create_lockup_with_transfers_disabled() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create_with_transfers_disabled(
+            account_tokens_owner(),
+            0.into(),
+            None,
+            None,
+            None,
+            None,
+            "transfer-poll".parse().unwrap(),
+        );
+
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, lockup_account());
+        let lockup_args = receipts[0]
+            .actions
+            .iter()
+            .find_map(|action| match action {
+                MockAction::FunctionCallWeight { method_name, args, .. }
+                    if method_name == b"new" =>
+                {
+                    Some(unc_sdk::serde_json::from_slice::<unc_sdk::serde_json::Value>(args).unwrap())
+                }
+                _ => None,
+            })
+            .expect("Expected the lockup `new` call");
+        assert_eq!(
+            lockup_args["transfers_information"]["TransfersDisabled"]["transfer_poll_account_id"],
+            "transfer-poll"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The transfer poll account ID can't be the lockup account ID")]
+    fn test_create_lockup_with_lockup_as_transfer_poll() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create_with_transfers_disabled(
+            account_tokens_owner(),
+            0.into(),
+            None,
+            None,
+            None,
+            None,
+            lockup_account(),
+        );
+    }
+
+    #[test]
+    fn test_get_required_deposit() {
+        let mut contract = new_factory_with_creation_fee(0);
+
+        let without_vesting = contract.get_required_deposit(false, ntoy(100).into()).0;
+        assert_eq!(without_vesting, MIN_ATTACHED_BALANCE + ntoy(100));
+        let with_vesting = contract.get_required_deposit(true, ntoy(100).into()).0;
+        assert_eq!(
+            with_vesting,
+            without_vesting + env::storage_byte_cost().as_attounc() * VESTING_STORAGE_BYTES
+        );
+
+        contract.set_creation_fee(ntoy(1).into());
+        assert_eq!(
+            contract.get_required_deposit(true, ntoy(100).into()).0,
+            with_vesting + ntoy(1)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough attached deposit")]
+    fn test_create_lockup_with_vesting_without_storage_deposit() {
+        let mut contract = new_factory_with_creation_fee(0);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(MIN_ATTACHED_BALANCE))
+            .build());
+        contract.create(
+            account_tokens_owner(),
+            0.into(),
+            None,
+            Some(VestingScheduleOrHash::VestingSchedule(new_vesting_schedule(10))),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_create_lockup_with_vesting_and_required_deposit() {
+        let mut contract = new_factory_with_creation_fee(0);
+        let required_deposit = contract.get_required_deposit(true, 0.into()).0;
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(required_deposit))
+            .build());
+        contract.create(
+            account_tokens_owner(),
+            0.into(),
+            None,
+            Some(VestingScheduleOrHash::VestingSchedule(new_vesting_schedule(10))),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(!get_created_receipts().is_empty());
+    }
+
+    fn created_lockup_args() -> unc_sdk::serde_json::Value {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .find_map(|action| match action {
+                MockAction::FunctionCallWeight { method_name, args, .. }
+                    if method_name == b"new" =>
+                {
+                    Some(unc_sdk::serde_json::from_slice::<unc_sdk::serde_json::Value>(&args).unwrap())
+                }
+                _ => None,
+            })
+            .expect("Expected the lockup `new` call")
+    }
+
+    /// Initializes the lockup contract with the arguments of the created lockup `new` call.
+    fn init_created_lockup(
+        args: unc_sdk::serde_json::Value,
+        lockup_balance: u128,
+    ) -> lockup_contract::LockupContract {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(lockup_account())
+            .predecessor_account_id(account_factory())
+            .account_balance(UncToken::from_attounc(lockup_balance))
+            .build());
+        let arg = |name: &str| args[name].clone();
+        lockup_contract::LockupContract::new(
+            unc_sdk::serde_json::from_value(arg("owner_account_id")).unwrap(),
+            unc_sdk::serde_json::from_value(arg("lockup_duration")).unwrap(),
+            unc_sdk::serde_json::from_value(arg("lockup_timestamp")).unwrap(),
+            unc_sdk::serde_json::from_value(arg("transfers_information")).unwrap(),
+            unc_sdk::serde_json::from_value(arg("vesting_schedule")).unwrap(),
+            unc_sdk::serde_json::from_value(arg("release_duration")).unwrap(),
+            unc_sdk::serde_json::from_value(arg("staking_pool_whitelist_account_id")).unwrap(),
+            unc_sdk::serde_json::from_value(arg("foundation_account_id")).unwrap(),
+            None,
+            unc_sdk::serde_json::from_value(arg("staking_pool_account_id")).unwrap(),
+        )
+    }
+
+    fn created_release_duration() -> Option<u64> {
+        created_lockup_args()["release_duration"].as_str().map(|duration| duration.parse().unwrap())
+    }
+
+    fn yearly_vesting_tranches(total_amount: u128) -> Vec<VestingTranche> {
+        (1..=4u64)
+            .map(|year| ((year * 31_536_000_000_000_000).into(), (total_amount / 4).into()))
+            .collect()
+    }
+
+    #[test]
+    fn test_create_lockup_with_foundation_without_vesting() {
+        let mut contract = new_factory_with_creation_fee(0);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, Some(true));
+        let args = created_lockup_args();
+        assert_eq!(args["foundation_account_id"], foundation_account_id().to_string());
+        assert!(args["vesting_schedule"].is_null());
+
+        // The lockup accepts the foundation account without vesting.
+        let lockup = init_created_lockup(args, ntoy(35));
+        assert_eq!(lockup.foundation_account_id, Some(foundation_account_id()));
+        assert_eq!(lockup.vesting_information, lockup_contract::VestingInformation::None);
+    }
+
+    #[test]
+    fn test_create_lockup_without_foundation_by_default() {
+        let mut contract = new_factory_with_creation_fee(0);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, Some(false));
+        assert!(created_lockup_args()["foundation_account_id"].is_null());
+    }
+
+    #[test]
+    fn test_create_and_stake() {
+        let mut contract = new_factory_with_creation_fee(0);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create_and_stake(
+            account_tokens_owner(),
+            0.into(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            "staking_pool".parse().unwrap(),
+        );
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, lockup_account());
+        let prepaid_gas = receipts[0]
+            .actions
+            .iter()
+            .find_map(|action| match action {
+                MockAction::FunctionCallWeight { method_name, prepaid_gas, .. }
+                    if method_name == b"new" =>
+                {
+                    Some(*prepaid_gas)
+                }
+                _ => None,
+            })
+            .expect("Expected the lockup `new` call");
+        assert_eq!(prepaid_gas.as_gas(), gas::LOCKUP_NEW.as_gas() + gas::LOCKUP_STAKE.as_gas());
+        assert_eq!(created_lockup_args()["staking_pool_account_id"], "staking_pool");
+
+        // The staking happens on the lockup, so the creation callback records the lockup as usual.
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(account_factory())
+                .predecessor_account_id(account_factory())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.on_lockup_create(
+            lockup_account(),
+            ntoy(35).into(),
+            account_tokens_owner(),
+            account_tokens_owner(),
+            0.into(),
+        ));
+        assert_eq!(contract.get_lockup_by_owner(account_tokens_owner()), Some(lockup_account()));
+    }
+
+    #[test]
+    fn test_create_lockup_without_staking_pool_by_default() {
+        let mut contract = new_factory_with_creation_fee(0);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+        assert!(created_lockup_args()["staking_pool_account_id"].is_null());
+    }
+
+    fn new_factory_with_expected_lockup_code_hash(code: &[u8]) -> LockupFactory {
+        let mut contract = new_factory_with_creation_fee(0);
+        assert_eq!(contract.get_expected_lockup_code_hash(), None);
+        let expected_lockup_code_hash = Base58CryptoHash::from(env::sha256_array(code));
+        contract.set_expected_lockup_code_hash(Some(expected_lockup_code_hash));
+        assert_eq!(contract.get_expected_lockup_code_hash(), Some(expected_lockup_code_hash));
+        contract
+    }
+
+    #[test]
+    fn test_create_lockup_with_expected_code_hash() {
+        let mut contract = new_factory_with_expected_lockup_code_hash(CODE);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+        assert_eq!(created_lockup_args()["owner_account_id"], account_tokens_owner().to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "The lockup code doesn't match the expected hash")]
+    fn test_create_lockup_with_wrong_expected_code_hash() {
+        let mut contract = new_factory_with_expected_lockup_code_hash(b"wrong code");
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "The lockup code doesn't match the expected hash")]
+    fn test_upgrade_lockup_with_wrong_expected_code_hash() {
+        let mut contract = new_factory_with_creation_fee(0);
+        create_lockup_for_tokens_owner(&mut contract);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(foundation_account_id())
+            .build());
+        contract.set_expected_lockup_code_hash(Some(env::sha256_array(b"wrong code").into()));
+        upgrade_lockup_by(&mut contract, foundation_account_id(), lockup_account());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the foundation")]
+    fn test_set_expected_lockup_code_hash_by_non_foundation() {
+        let mut contract = new_factory_with_creation_fee(0);
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .build());
+        contract.set_expected_lockup_code_hash(None);
+    }
+
+    fn deployed_lockup_code() -> Vec<u8> {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .find_map(|action| match action {
+                MockAction::DeployContract { code, .. } => Some(code),
+                _ => None,
+            })
+            .expect("Expected the lockup code deployment")
+    }
+
+    #[test]
+    fn test_create_lockup_with_uploaded_code() {
+        let mut contract = new_factory_with_creation_fee(0);
+        assert_eq!(contract.get_lockup_code_version(), None);
+        contract.set_lockup_code(b"lockup code".to_vec(), "2.0.0".to_string());
+        assert_eq!(contract.get_lockup_code_version(), Some("2.0.0".to_string()));
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+        assert_eq!(deployed_lockup_code(), b"lockup code".to_vec());
+
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(account_factory())
+                .predecessor_account_id(account_factory())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.on_lockup_create(
+            lockup_account(),
+            ntoy(35).into(),
+            account_tokens_owner(),
+            account_tokens_owner(),
+            0.into(),
+        ));
+        let event = format!(
+            "EVENT_JSON:{{\"standard\":\"lockup_factory\",\"version\":\"1.0.0\",\"event\":\"lockup_created\",\"data\":{{\"lockup_account_id\":\"{}\",\"owner_account_id\":\"{}\",\"lockup_code_version\":\"2.0.0\"}}}}",
+            lockup_account(),
+            account_tokens_owner()
+        );
+        assert!(get_logs().contains(&event));
+    }
+
+    #[test]
+    fn test_create_lockup_with_built_in_code() {
+        let mut contract = new_factory_with_creation_fee(0);
+        create_lockup_for_tokens_owner(&mut contract);
+        assert!(get_logs().iter().any(|log| log.starts_with("EVENT_JSON:")
+            && log.contains("\"lockup_created\"")
+            && log.contains("\"lockup_code_version\":null")));
+    }
+
+    #[test]
+    #[should_panic(expected = "The lockup code doesn't match the expected hash")]
+    fn test_set_lockup_code_with_wrong_expected_code_hash() {
+        let mut contract = new_factory_with_expected_lockup_code_hash(CODE);
+        contract.set_lockup_code(b"wrong code".to_vec(), "2.0.0".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the foundation")]
+    fn test_set_lockup_code_by_non_foundation() {
+        let mut contract = new_factory_with_creation_fee(0);
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .build());
+        contract.set_lockup_code(b"lockup code".to_vec(), "2.0.0".to_string());
+    }
+
+    #[test]
+    fn test_create_lockup_with_vesting_tranches() {
+        let mut contract = new_factory_with_creation_fee(0);
+        let tranches = yearly_vesting_tranches(ntoy(30));
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(
+            account_tokens_owner(),
+            0.into(),
+            None,
+            Some(VestingScheduleOrHash::VestingTranches(tranches.clone())),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let lockup_args = created_lockup_args();
+        assert_eq!(
+            lockup_args["vesting_schedule"]["VestingTranches"],
+            unc_sdk::serde_json::to_value(&tranches).unwrap()
+        );
+        assert_eq!(lockup_args["foundation_account_id"], foundation_account_id().to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "The vesting tranche timestamps should be increasing")]
+    fn test_create_lockup_with_unordered_vesting_tranches() {
+        let mut contract = new_factory_with_creation_fee(0);
+        let mut tranches = yearly_vesting_tranches(ntoy(30));
+        tranches.reverse();
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(
+            account_tokens_owner(),
+            0.into(),
+            None,
+            Some(VestingScheduleOrHash::VestingTranches(tranches)),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_validate_create_vesting_tranches_exceed_deposit() {
+        let contract = new_factory_with_creation_fee(ntoy(1));
+        let report = contract.validate_create(
+            account_tokens_owner(),
+            0.into(),
+            None,
+            Some(VestingScheduleOrHash::VestingTranches(yearly_vesting_tranches(ntoy(35)))),
+            None,
+            ntoy(35).into(),
+        );
+        assert_eq!(
+            report.vesting_schedule_errors,
+            vec!["The vesting tranches can't exceed the lockup amount".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_create_lockup_with_default_release_duration() {
+        const RELEASE_DURATION: u64 = 31536000000000000; /* 12 months */
+        let mut contract = new_factory_with_creation_fee(0);
+        assert_eq!(contract.get_default_release_duration(), None);
+        contract.set_default_release_duration(Some(RELEASE_DURATION.into()));
+        assert_eq!(contract.get_default_release_duration(), Some(RELEASE_DURATION.into()));
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+        assert_eq!(created_release_duration(), Some(RELEASE_DURATION));
+
+        // The explicit release duration overrides the default.
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(
+            "owner2".parse().unwrap(),
+            0.into(),
+            None,
+            None,
+            Some((RELEASE_DURATION * 2).into()),
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(created_release_duration(), Some(RELEASE_DURATION * 2));
+    }
+
+    #[test]
+    fn test_create_lockup_without_default_release_duration() {
+        let mut contract = new_factory_with_creation_fee(0);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+        assert_eq!(created_release_duration(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the foundation")]
+    fn test_set_default_release_duration_by_non_foundation() {
+        let mut contract = new_factory_with_creation_fee(0);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .build());
+        contract.set_default_release_duration(None);
+    }
+
+    #[test]
+    fn test_validate_create() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        const LOCKUP_DURATION: u64 = 63036000000000000; /* 24 months */
+        let lockup_duration: WrappedTimestamp = LOCKUP_DURATION.into();
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .is_view(true)
+            .build());
+
+        let report = contract.validate_create(
+            account_tokens_owner(),
+            lockup_duration,
+            None,
+            Some(VestingScheduleOrHash::VestingSchedule(new_vesting_schedule(10))),
+            None,
+            ntoy(35).into(),
+        );
+        assert!(report.is_valid());
+        assert_eq!(report.lockup_account_id, lockup_account());
+
+        let report = contract.validate_create(
+            account_tokens_owner(),
+            lockup_duration,
+            None,
+            None,
+            None,
+            ntoy(1).into(),
+        );
+        assert!(!report.deposit_sufficient);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn test_validate_create_end_before_cliff() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        const LOCKUP_DURATION: u64 = 63036000000000000; /* 24 months */
+        let lockup_duration: WrappedTimestamp = LOCKUP_DURATION.into();
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .is_view(true)
+            .build());
+
+        let vesting_schedule = VestingSchedule {
+            start_timestamp: to_ts(GENESIS_TIME_IN_DAYS - YEAR).into(),
+            cliff_timestamp: to_ts(GENESIS_TIME_IN_DAYS + YEAR).into(),
+            end_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+        };
+        let report = contract.validate_create(
+            account_tokens_owner(),
+            lockup_duration,
+            None,
+            Some(VestingScheduleOrHash::VestingSchedule(vesting_schedule)),
+            None,
+            ntoy(35).into(),
+        );
+        assert!(report.deposit_sufficient);
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.vesting_schedule_errors,
+            vec!["Cliff timestamp can't be later than vesting end timestamp".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_create_lockup_with_configured_gas() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        assert_eq!(contract.get_lockup_new_gas().0, gas::LOCKUP_NEW.as_gas());
+        assert_eq!(contract.get_callback_gas().0, gas::CALLBACK.as_gas());
+
+        let lockup_new_gas = gas::LOCKUP_NEW.as_gas() * 2;
+        contract.set_lockup_new_gas(lockup_new_gas.into());
+        assert_eq!(contract.get_lockup_new_gas().0, lockup_new_gas);
+
+        const LOCKUP_DURATION: u64 = 63036000000000000; /* 24 months */
+        let lockup_duration: WrappedTimestamp = LOCKUP_DURATION.into();
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .is_view(false)
+            .build());
+
+        contract.create(account_tokens_owner(), lockup_duration, None, None, None, None, None, None, None);
+
+        let prepaid_gas = get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .find_map(|action| match action {
+                MockAction::FunctionCallWeight { method_name, prepaid_gas, .. }
+                    if method_name == b"new" =>
+                {
+                    Some(prepaid_gas)
+                }
+                _ => None,
+            })
+            .expect("Expected the lockup `new` call");
+        assert_eq!(prepaid_gas.as_gas(), lockup_new_gas);
+    }
+
+    #[test]
+    fn test_default_gas_split() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        assert!(contract.get_lockup_new_gas().0 > contract.get_callback_gas().0);
+        assert!(
+            contract.get_lockup_new_gas().0 + contract.get_callback_gas().0 + gas::CREATE.as_gas()
+                <= gas::MAX_TRANSACTION.as_gas()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The configured gas exceeds the transaction gas limit")]
+    fn test_create_lockup_with_gas_over_transaction_limit() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        // Leaves no room for the execution of `create` itself.
+        let lockup_new_gas = gas::MAX_TRANSACTION.as_gas() - gas::CALLBACK.as_gas();
+        contract.set_lockup_new_gas(lockup_new_gas.into());
+
+        const LOCKUP_DURATION: u64 = 63036000000000000; /* 24 months */
+        let lockup_duration: WrappedTimestamp = LOCKUP_DURATION.into();
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .is_view(false)
+            .build());
+
+        contract.create(account_tokens_owner(), lockup_duration, None, None, None, None, None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the foundation")]
+    fn test_set_lockup_new_gas_by_non_foundation() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .is_view(false)
+            .build());
+
+        contract.set_lockup_new_gas(gas::LOCKUP_NEW.as_gas().into());
+    }
+
+    #[test]
+    fn test_create_lockup_refund_stuck_deposit() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        const LOCKUP_DURATION: u64 = 63036000000000000; /* 24 months */
+        let lockup_duration: WrappedTimestamp = LOCKUP_DURATION.into();
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .is_view(false)
+            .build());
+
+        contract.create(account_tokens_owner(), lockup_duration, None, None, None, None, None, None, None);
+
+        // The callback never ran, so the deposit is still pending.
+        assert_eq!(
+            contract.get_pending_refunds(),
+            vec![(account_tokens_owner(), U128(ntoy(35)))]
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(foundation_account_id())
+            .account_balance(UncToken::from_attounc(ntoy(50)))
+            .is_view(false)
+            .build());
+
+        contract.refund_stuck_deposit(account_tokens_owner(), ntoy(35).into());
+        assert!(contract.get_pending_refunds().is_empty());
+    }
+
+    #[test]
+    fn test_create_lockups_with_failed_middle_refunds_only_its_deposit() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        let funder: AccountId = "funder".parse().unwrap();
+        let lockups: Vec<(AccountId, u128)> = vec![
+            ("owner1".parse().unwrap(), ntoy(10)),
+            ("owner2".parse().unwrap(), ntoy(20)),
+            ("owner3".parse().unwrap(), ntoy(30)),
+        ];
+        for (owner_account_id, deposit) in &lockups {
+            testing_env!(VMContextBuilder::new()
+                .current_account_id(account_factory())
+                .predecessor_account_id(funder.clone())
+                .attached_deposit(UncToken::from_attounc(*deposit))
+                .build());
+            contract.create(owner_account_id.clone(), 0.into(), None, None, None, None, None, None, None);
+        }
+        assert_eq!(contract.get_pending_refunds(), vec![(funder.clone(), U128(ntoy(60)))]);
+
+        // Only the middle lockup fails to be created.
+        for (i, (owner_account_id, deposit)) in lockups.iter().enumerate() {
+            let promise_result = if i == 1 {
+                PromiseResult::Failed
+            } else {
+                PromiseResult::Successful(vec![])
+            };
+            testing_env!(
+                VMContextBuilder::new()
+                    .current_account_id(account_factory())
+                    .predecessor_account_id(account_factory())
+                    .build(),
+                test_vm_config(),
+                RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![promise_result],
+            );
+            let created = contract.on_lockup_create(
+                LockupFactory::internal_lockup_account_id(owner_account_id),
+                (*deposit).into(),
+                funder.clone(),
+                owner_account_id.clone(),
+                0.into(),
+            );
+            assert_eq!(created, i != 1);
+
+            let receipts = get_created_receipts();
+            if created {
+                assert!(receipts.is_empty());
+            } else {
+                assert_eq!(receipts.len(), 1);
+                assert_eq!(receipts[0].receiver_id, funder);
+                match &receipts[0].actions[0] {
+                    MockAction::Transfer { deposit: refund, .. } => {
+                        assert_eq!(refund.as_attounc(), ntoy(20))
+                    }
+                    action => panic!("Unexpected action {:?}", action),
+                }
+                // The deposits of the remaining lockup are still pending.
+                assert_eq!(contract.get_pending_refunds(), vec![(funder.clone(), U128(ntoy(30)))]);
+            }
+        }
+
+        assert!(contract.get_pending_refunds().is_empty());
+        assert!(contract.get_lockup_by_owner(lockups[0].0.clone()).is_some());
+        assert_eq!(contract.get_lockup_by_owner(lockups[1].0.clone()), None);
+        assert!(contract.get_lockup_by_owner(lockups[2].0.clone()).is_some());
+    }
+
+    fn new_factory_with_creation_fee(creation_fee: u128) -> LockupFactory {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        assert_eq!(contract.get_creation_fee().0, 0);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(foundation_account_id())
+            .build());
+        contract.set_creation_fee(creation_fee.into());
+        assert_eq!(contract.get_creation_fee().0, creation_fee);
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough attached deposit")]
+    fn test_create_lockup_without_creation_fee() {
+        let mut contract = new_factory_with_creation_fee(ntoy(1));
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(MIN_ATTACHED_BALANCE))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+    }
+
+    fn call_create_for_tokens_owner(contract: &mut LockupFactory, deposit: u128) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(deposit))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+    }
+
+    #[test]
+    fn test_create_lockup_within_owner_cap() {
+        let mut contract = new_factory_with_creation_fee(ntoy(1));
+        assert_eq!(contract.get_owner_cap(account_tokens_owner()), None);
+        contract.set_owner_cap(account_tokens_owner(), Some(ntoy(34).into()));
+        assert_eq!(contract.get_owner_cap(account_tokens_owner()), Some(ntoy(34).into()));
+
+        // The creation fee doesn't count towards the cap.
+        call_create_for_tokens_owner(&mut contract, ntoy(35));
+        assert!(!get_created_receipts().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "The lockup amount exceeds the cap of the owner")]
+    fn test_create_lockup_over_owner_cap() {
+        let mut contract = new_factory_with_creation_fee(ntoy(1));
+        contract.set_owner_cap(account_tokens_owner(), Some(ntoy(33).into()));
+        call_create_for_tokens_owner(&mut contract, ntoy(35));
+    }
+
+    #[test]
+    fn test_remove_owner_cap() {
+        let mut contract = new_factory_with_creation_fee(0);
+        contract.set_owner_cap(account_tokens_owner(), Some(ntoy(1).into()));
+        contract.set_owner_cap(account_tokens_owner(), None);
+        assert_eq!(contract.get_owner_cap(account_tokens_owner()), None);
+
+        call_create_for_tokens_owner(&mut contract, ntoy(35));
+        assert!(!get_created_receipts().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the foundation")]
+    fn test_set_owner_cap_by_non_foundation() {
+        let mut contract = new_factory_with_creation_fee(0);
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .build());
+        contract.set_owner_cap(account_tokens_owner(), None);
+    }
+
+    #[test]
+    fn test_create_lockup_attested_owner() {
+        let mut contract = new_factory_with_creation_fee(0);
+        assert!(!contract.is_attestation_required());
+        contract.set_require_attestation(true);
+        assert!(contract.is_attestation_required());
+        assert!(contract.add_attested_owner(account_tokens_owner()));
+        assert!(!contract.add_attested_owner(account_tokens_owner()));
+        assert!(contract.is_owner_attested(account_tokens_owner()));
+
+        call_create_for_tokens_owner(&mut contract, ntoy(35));
+        assert!(!get_created_receipts().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "The owner account ID is not attested")]
+    fn test_create_lockup_not_attested_owner() {
+        let mut contract = new_factory_with_creation_fee(0);
+        contract.set_require_attestation(true);
+        contract.add_attested_owner(account_tokens_owner());
+        assert!(contract.remove_attested_owner(account_tokens_owner()));
+        assert!(!contract.is_owner_attested(account_tokens_owner()));
+
+        call_create_for_tokens_owner(&mut contract, ntoy(35));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the foundation")]
+    fn test_add_attested_owner_by_non_foundation() {
+        let mut contract = new_factory_with_creation_fee(0);
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .build());
+        contract.add_attested_owner(account_tokens_owner());
+    }
+
+    #[test]
+    fn test_create_lockup_collects_creation_fee() {
+        let mut contract = new_factory_with_creation_fee(ntoy(1));
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+
+        // Only the lockup funding is sent to the lockup account.
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, lockup_account());
+        let lockup_funding = receipts[0]
+            .actions
+            .iter()
+            .find_map(|action| match action {
+                MockAction::Transfer { deposit, .. } => Some(deposit.as_attounc()),
+                _ => None,
+            })
+            .expect("Expected the lockup funding transfer");
+        assert_eq!(lockup_funding, ntoy(34));
+
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(account_factory())
+                .predecessor_account_id(account_factory())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.on_lockup_create(
+            lockup_account(),
+            ntoy(35).into(),
+            account_tokens_owner(),
+            account_tokens_owner(),
+            ntoy(1).into(),
+        ));
+        assert_eq!(contract.get_collected_fees().0, ntoy(1));
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(foundation_account_id())
+            .build());
+        contract.withdraw_collected_fees(foundation_account_id());
+        assert_eq!(contract.get_collected_fees().0, 0);
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, foundation_account_id());
+        match &receipts[0].actions[0] {
+            MockAction::Transfer { deposit, .. } => assert_eq!(deposit.as_attounc(), ntoy(1)),
+            action => panic!("Unexpected action {:?}", action),
+        }
+    }
 
-        if lockup_account_created {
-            env::log_str(
-                format!("The lockup contract {} was successfully created.", lockup_account_id)
-                    .as_str(),
-            );
-            true
-        } else {
-            env::log_str(
-                format!(
-                    "The lockup {} creation has failed. Returning attached deposit of {} to {}",
-                    lockup_account_id, attached_deposit.0, predecessor_account_id
-                )
-                    .as_str(),
+    #[test]
+    fn test_get_stats() {
+        let mut contract = new_factory_with_creation_fee(ntoy(1));
+        let stats = contract.get_stats();
+        assert_eq!(stats.lockups_created, 0);
+        assert_eq!(stats.active_lockups, 0);
+        assert_eq!(stats.total_deployed_balance.0, 0);
+        assert_eq!(stats.pending_refunds_count, 0);
+
+        let second_owner: AccountId = "owner2".parse().unwrap();
+        for (owner_account_id, amount) in
+            [(account_tokens_owner(), ntoy(35)), (second_owner, ntoy(50))]
+        {
+            testing_env!(VMContextBuilder::new()
+                .current_account_id(account_factory())
+                .predecessor_account_id(owner_account_id.clone())
+                .attached_deposit(UncToken::from_attounc(amount))
+                .build());
+            contract.create(owner_account_id.clone(), 0.into(), None, None, None, None, None, None, None);
+            assert_eq!(contract.get_stats().pending_refunds_count, 1);
+
+            testing_env!(
+                VMContextBuilder::new()
+                    .current_account_id(account_factory())
+                    .predecessor_account_id(account_factory())
+                    .build(),
+                test_vm_config(),
+                RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![PromiseResult::Successful(vec![])],
             );
-            Promise::new(predecessor_account_id).transfer(UncToken::from_attounc(attached_deposit.0));
-            false
+            assert!(contract.on_lockup_create(
+                LockupFactory::internal_lockup_account_id(&owner_account_id),
+                amount.into(),
+                owner_account_id.clone(),
+                owner_account_id,
+                ntoy(1).into(),
+            ));
         }
+
+        let stats = contract.get_stats();
+        assert_eq!(stats.lockups_created, 2);
+        assert_eq!(stats.active_lockups, 2);
+        // The creation fees stay on the factory.
+        assert_eq!(stats.total_deployed_balance.0, ntoy(35) + ntoy(50) - 2 * ntoy(1));
+        assert_eq!(stats.pending_refunds_count, 0);
+
+        // The config hash changes with the configuration.
+        let config_hash = stats.config_hash;
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(foundation_account_id())
+            .build());
+        contract.set_creation_fee(ntoy(2).into());
+        assert_ne!(contract.get_stats().config_hash, config_hash);
     }
-}
 
-#[cfg(all(test, not(target_arch = "wasm32")))]
-mod tests {
-    mod test_utils;
+    #[test]
+    fn test_create_lockup_failure_refunds_creation_fee() {
+        let mut contract = new_factory_with_creation_fee(ntoy(1));
 
-    use super::*;
-    use unc_sdk::{testing_env, test_vm_config, RuntimeFeesConfig, PromiseResult};
-    use unc_sdk::test_utils::VMContextBuilder;
-    use test_utils::*;
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
 
-    fn new_vesting_schedule(offset_in_days: u64) -> VestingSchedule {
-        VestingSchedule {
-            start_timestamp: to_ts(GENESIS_TIME_IN_DAYS - YEAR + offset_in_days).into(),
-            cliff_timestamp: to_ts(GENESIS_TIME_IN_DAYS + offset_in_days).into(),
-            end_timestamp: to_ts(GENESIS_TIME_IN_DAYS + YEAR * 3 + offset_in_days).into(),
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(account_factory())
+                .predecessor_account_id(account_factory())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        assert!(!contract.on_lockup_create(
+            lockup_account(),
+            ntoy(35).into(),
+            account_tokens_owner(),
+            account_tokens_owner(),
+            ntoy(1).into(),
+        ));
+        assert_eq!(contract.get_collected_fees().0, 0);
+        match &get_created_receipts()[0].actions[0] {
+            MockAction::Transfer { deposit, .. } => assert_eq!(deposit.as_attounc(), ntoy(35)),
+            action => panic!("Unexpected action {:?}", action),
         }
     }
 
     #[test]
-    fn test_get_factory_vars() {
+    #[should_panic(expected = "There are no collected fees to withdraw")]
+    fn test_withdraw_collected_fees_without_fees() {
+        let mut contract = new_factory_with_creation_fee(ntoy(1));
+        contract.withdraw_collected_fees(foundation_account_id());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the foundation")]
+    fn test_refund_stuck_deposit_by_non_foundation() {
         testing_env!(VMContextBuilder::new()
             .current_account_id(account_factory())
             .predecessor_account_id(account_unc())
             .build());
 
-        let contract = LockupFactory::new(
-            whitelist_account_id(),
-            foundation_account_id(),
-        );
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
 
         testing_env!(VMContextBuilder::new()
             .current_account_id(account_factory())
-            .predecessor_account_id(account_unc())
-            .is_view(true)
+            .predecessor_account_id(account_tokens_owner())
+            .is_view(false)
             .build());
 
-        assert_eq!(contract.get_min_attached_balance().0, MIN_ATTACHED_BALANCE);
-        assert_eq!(
-            contract.get_foundation_account_id(),
-            foundation_account_id()
-        );
-        println!("{}", contract.get_lockup_master_account_id());
-        assert_eq!(
-            contract.get_lockup_master_account_id(),
-            lockup_master_account_id()
-        );
+        contract.refund_stuck_deposit(account_tokens_owner(), ntoy(35).into());
     }
 
     #[test]
-    fn test_create_lockup_success() {
+    fn test_create_lockup_with_explicit_vesting_success() {
         testing_env!(VMContextBuilder::new()
             .current_account_id(account_factory())
             .predecessor_account_id(account_unc())
             .build());
 
-        let mut contract = LockupFactory::new(
-            whitelist_account_id(),
-            foundation_account_id(),
-        );
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
 
         const LOCKUP_DURATION: u64 = 63036000000000000; /* 24 months */
         let lockup_duration: WrappedTimestamp = LOCKUP_DURATION.into();
@@ -261,56 +2887,32 @@ mod tests {
             .is_view(false)
             .build());
 
-        contract.create(account_tokens_owner(), lockup_duration, None, None, None, None);
-
-        let context = VMContextBuilder::new()
-            .current_account_id(account_factory())
-            .predecessor_account_id(account_factory())
-            .attached_deposit(UncToken::from_attounc(ntoy(0)))
-            .is_view(false)
-            .build();
-
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(vec![])],
-        );
-        println!("{}", lockup_account());
-        contract.on_lockup_create(
-            lockup_account(),
-            ntoy(30).into(),
+        contract.create(
             account_tokens_owner(),
+            lockup_duration,
+            None,
+            Some(VestingScheduleOrHash::VestingSchedule(new_vesting_schedule(10))),
+            None,
+            None,
+            None,
+            None,
+            None,
         );
+        assert!(!get_created_receipts().is_empty());
     }
 
     #[test]
-    fn test_create_lockup_with_vesting_success() {
+    #[should_panic(expected = "Cliff timestamp can't be earlier than vesting start timestamp")]
+    fn test_create_lockup_with_vesting_out_of_order() {
         testing_env!(VMContextBuilder::new()
             .current_account_id(account_factory())
             .predecessor_account_id(account_unc())
             .build());
 
-        let mut contract = LockupFactory::new(
-            whitelist_account_id(),
-            foundation_account_id(),
-        );
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
 
         const LOCKUP_DURATION: u64 = 63036000000000000; /* 24 months */
-        const LOCKUP_TIMESTAMP: u64 = 1661990400000000000; /* 1 September 2022 00:00:00 */
         let lockup_duration: WrappedTimestamp = LOCKUP_DURATION.into();
-        let lockup_timestamp: WrappedTimestamp = LOCKUP_TIMESTAMP.into();
-
-        let vesting_schedule = Some(new_vesting_schedule(10));
-
-        let vesting_schedule = vesting_schedule.map(|vesting_schedule| {
-            VestingScheduleOrHash::VestingHash(
-                VestingScheduleWithSalt { vesting_schedule, salt: SALT.to_vec().into() }
-                    .hash()
-                    .into(),
-            )
-        });
 
         testing_env!(VMContextBuilder::new()
             .current_account_id(account_factory())
@@ -319,32 +2921,21 @@ mod tests {
             .is_view(false)
             .build());
 
+        let vesting_schedule = VestingSchedule {
+            start_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            cliff_timestamp: to_ts(GENESIS_TIME_IN_DAYS - YEAR).into(),
+            end_timestamp: to_ts(GENESIS_TIME_IN_DAYS + YEAR).into(),
+        };
         contract.create(
             account_tokens_owner(),
             lockup_duration,
-            Some(lockup_timestamp),
-            vesting_schedule,
+            None,
+            Some(VestingScheduleOrHash::VestingSchedule(vesting_schedule)),
+            None,
+            None,
+            None,
             None,
             None,
-        );
-
-        let context = VMContextBuilder::new()
-            .current_account_id(account_factory())
-            .predecessor_account_id(account_factory())
-            .attached_deposit(UncToken::from_attounc(ntoy(0)))
-            .build();
-
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(vec![])],
-        );
-        contract.on_lockup_create(
-            lockup_account(),
-            ntoy(30).into(),
-            account_tokens_owner(),
         );
     }
 
@@ -371,7 +2962,7 @@ mod tests {
             .is_view(false)
             .build());
 
-        contract.create(account_tokens_owner(), lockup_duration, None, None, None, None);
+        contract.create(account_tokens_owner(), lockup_duration, None, None, None, None, None, None, None);
     }
 
     #[test]
@@ -397,7 +2988,7 @@ mod tests {
             .build();
         testing_env!(context.clone());
 
-        contract.create(account_tokens_owner(), lockup_duration, None, None, None, None);
+        contract.create(account_tokens_owner(), lockup_duration, None, None, None, None, None, None, None);
 
         let context = VMContextBuilder::new()
             .current_account_id(account_factory())
@@ -419,12 +3010,125 @@ mod tests {
             lockup_account(),
             ntoy(35).into(),
             account_tokens_owner(),
+            account_tokens_owner(),
+            0.into(),
         );
 
         match res {
             true => panic!("Unexpected result, should return false"),
             false => assert!(true),
         };
+        assert_eq!(contract.get_lockup_by_owner(account_tokens_owner()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can be called only as a callback")]
+    fn test_on_lockup_create_called_externally() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(account_factory())
+                .predecessor_account_id(account_tokens_owner())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_lockup_create(
+            lockup_account(),
+            ntoy(35).into(),
+            account_tokens_owner(),
+            account_tokens_owner(),
+            0.into(),
+        );
+    }
+
+    #[test]
+    fn test_on_lockup_create_replay_does_not_refund_twice() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, None, None);
+
+        for expected_refunds in [1, 0] {
+            testing_env!(
+                VMContextBuilder::new()
+                    .current_account_id(account_factory())
+                    .predecessor_account_id(account_factory())
+                    .build(),
+                test_vm_config(),
+                RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![PromiseResult::Failed],
+            );
+            assert!(!contract.on_lockup_create(
+                lockup_account(),
+                ntoy(35).into(),
+                account_tokens_owner(),
+                account_tokens_owner(),
+                0.into(),
+            ));
+            assert_eq!(get_created_receipts().len(), expected_refunds);
+            assert!(contract.get_pending_refunds().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_pending_creations() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_unc())
+            .build());
+
+        let mut contract = LockupFactory::new(whitelist_account_id(), foundation_account_id());
+        assert!(contract.get_pending_creations().is_empty());
+
+        for (promise_result, lockup_created) in
+            [(PromiseResult::Successful(vec![]), true), (PromiseResult::Failed, false)]
+        {
+            call_create_for_tokens_owner(&mut contract, ntoy(35));
+            assert_eq!(contract.get_pending_creations(), vec![lockup_account()]);
+
+            testing_env!(
+                VMContextBuilder::new()
+                    .current_account_id(account_factory())
+                    .predecessor_account_id(account_factory())
+                    .build(),
+                test_vm_config(),
+                RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![promise_result],
+            );
+            assert_eq!(
+                contract.on_lockup_create(
+                    lockup_account(),
+                    ntoy(35).into(),
+                    account_tokens_owner(),
+                    account_tokens_owner(),
+                    0.into(),
+                ),
+                lockup_created
+            );
+            assert!(contract.get_pending_creations().is_empty());
+            // Lets the same owner create the lockup again after the successful creation.
+            contract.lockups.remove(&account_tokens_owner());
+        }
     }
 
     #[test]
@@ -453,6 +3157,9 @@ mod tests {
             None,
             None,
             Some(custom_whitelist_account_id()),
+            None,
+            None,
+            None,
         );
 
         testing_env!(
@@ -473,6 +3180,114 @@ mod tests {
             lockup_account(),
             ntoy(30).into(),
             account_tokens_owner(),
+            account_tokens_owner(),
+            0.into(),
+        );
+    }
+
+    fn whitelist_verification_args() -> unc_sdk::serde_json::Value {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .find_map(|action| match action {
+                MockAction::FunctionCallWeight { method_name, args, .. }
+                    if method_name == b"on_whitelist_verified" =>
+                {
+                    Some(unc_sdk::serde_json::from_slice::<unc_sdk::serde_json::Value>(&args).unwrap())
+                }
+                _ => None,
+            })
+            .expect("Expected the `on_whitelist_verified` callback")
+    }
+
+    fn create_with_whitelist_verification(
+        contract: &mut LockupFactory,
+        whitelist_result: PromiseResult,
+    ) -> PromiseOrValue<bool> {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, Some(true), None);
+        let args = whitelist_verification_args();
+
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(account_factory())
+                .predecessor_account_id(account_factory())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![whitelist_result],
+        );
+        contract.on_whitelist_verified(
+            lockup_account(),
+            unc_sdk::serde_json::from_value(args["lockup_args"].clone()).unwrap(),
+            unc_sdk::serde_json::from_value(args["lockup_new_gas"].clone()).unwrap(),
+            ntoy(35).into(),
+            account_tokens_owner(),
+            ntoy(1).into(),
+        )
+    }
+
+    #[test]
+    fn test_create_lockup_verifies_whitelist() {
+        let mut contract = new_factory_with_creation_fee(0);
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_factory())
+            .predecessor_account_id(account_tokens_owner())
+            .attached_deposit(UncToken::from_attounc(ntoy(35)))
+            .build());
+        contract.create(account_tokens_owner(), 0.into(), None, None, None, None, None, Some(true), None);
+
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, whitelist_account_id());
+        assert!(matches!(
+            &receipts[0].actions[0],
+            MockAction::FunctionCallWeight { method_name, .. } if method_name == b"is_factory_whitelisted"
+        ));
+        // The lockup account is only created once the whitelist responds.
+        assert!(receipts.iter().all(|receipt| receipt.receiver_id != lockup_account()));
+        assert_eq!(contract.get_pending_creations(), vec![lockup_account()]);
+        assert_eq!(whitelist_verification_args()["attached_deposit"], ntoy(35).to_string());
+    }
+
+    #[test]
+    fn test_create_lockup_with_reachable_whitelist() {
+        let mut contract = new_factory_with_creation_fee(ntoy(1));
+
+        let result = create_with_whitelist_verification(
+            &mut contract,
+            PromiseResult::Successful(b"true".to_vec()),
         );
+        assert!(matches!(result, PromiseOrValue::Promise(_)));
+        // The promise is only scheduled once it's dropped.
+        drop(result);
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, lockup_account());
+        assert!(matches!(&receipts[0].actions[0], MockAction::CreateAccount { .. }));
+        assert_eq!(created_lockup_args()["owner_account_id"], account_tokens_owner().to_string());
+        assert_eq!(contract.get_pending_creations(), vec![lockup_account()]);
+    }
+
+    #[test]
+    fn test_create_lockup_with_unreachable_whitelist() {
+        let mut contract = new_factory_with_creation_fee(ntoy(1));
+
+        let result = create_with_whitelist_verification(&mut contract, PromiseResult::Failed);
+        assert!(matches!(result, PromiseOrValue::Value(false)));
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, account_tokens_owner());
+        match &receipts[0].actions[0] {
+            MockAction::Transfer { deposit, .. } => assert_eq!(deposit.as_attounc(), ntoy(35)),
+            action => panic!("Unexpected action {:?}", action),
+        }
+        assert!(contract.get_pending_creations().is_empty());
+        assert!(contract.get_pending_refunds().is_empty());
+        assert_eq!(contract.get_collected_fees().0, 0);
     }
 }
@@ -17,6 +17,13 @@ pub type WrappedBalance = U128;
 /// Hash of Vesting schedule.
 pub type Hash = Vec<u8>;
 
+/// A vesting tranche: the timestamp in nanosecond and the amount of tokens that becomes vested
+/// at this timestamp.
+pub type VestingTranche = (WrappedTimestamp, WrappedBalance);
+
+/// The maximum number of vesting tranches accepted by the lockup contract.
+pub const MAX_VESTING_TRANCHES: usize = 100;
+
 /// Contains information about token lockups.
 #[unc]
 pub struct LockupInformation {
@@ -94,18 +101,24 @@ pub struct VestingSchedule {
 
 impl VestingSchedule {
     pub fn assert_valid(&self) {
-        assert!(
-            self.start_timestamp.0 <= self.cliff_timestamp.0,
-            "Cliff timestamp can't be earlier than vesting start timestamp"
-        );
-        assert!(
-            self.cliff_timestamp.0 <= self.end_timestamp.0,
-            "Cliff timestamp can't be later than vesting end timestamp"
-        );
-        assert!(
-            self.start_timestamp.0 < self.end_timestamp.0,
-            "The total vesting time should be positive"
-        );
+        if let Some(error) = self.ordering_errors().first() {
+            panic!("{}", error);
+        }
+    }
+
+    /// Returns the problems with the timestamps ordering. Empty if the schedule is valid.
+    pub fn ordering_errors(&self) -> Vec<String> {
+        let mut errors = vec![];
+        if self.start_timestamp.0 > self.cliff_timestamp.0 {
+            errors.push("Cliff timestamp can't be earlier than vesting start timestamp".to_string());
+        }
+        if self.cliff_timestamp.0 > self.end_timestamp.0 {
+            errors.push("Cliff timestamp can't be later than vesting end timestamp".to_string());
+        }
+        if self.start_timestamp.0 >= self.end_timestamp.0 {
+            errors.push("The total vesting time should be positive".to_string());
+        }
+        errors
     }
 }
 
@@ -118,6 +131,32 @@ pub enum VestingScheduleOrHash {
     VestingHash(Base64VecU8),
     /// The vesting schedule (public)
     VestingSchedule(VestingSchedule),
+    /// The vesting in discrete tranches (public), e.g. 25% at the end of every year.
+    VestingTranches(Vec<VestingTranche>),
+}
+
+/// Returns the problems with the vesting tranches that the lockup contract would reject for the
+/// given lockup amount. Empty if the tranches are valid.
+pub fn vesting_tranches_errors(tranches: &[VestingTranche], lockup_amount: u128) -> Vec<String> {
+    let mut errors = vec![];
+    if tranches.is_empty() {
+        errors.push("The vesting tranches can't be empty".to_string());
+    }
+    if tranches.len() > MAX_VESTING_TRANCHES {
+        errors.push("Too many vesting tranches".to_string());
+    }
+    if tranches.windows(2).any(|pair| pair[0].0 .0 >= pair[1].0 .0) {
+        errors.push("The vesting tranche timestamps should be increasing".to_string());
+    }
+    if tranches.iter().any(|(_, amount)| amount.0 == 0) {
+        errors.push("The vesting tranche amount should be positive".to_string());
+    }
+    let total_amount =
+        tranches.iter().fold(0u128, |total, (_, amount)| total.saturating_add(amount.0));
+    if total_amount > lockup_amount {
+        errors.push("The vesting tranches can't exceed the lockup amount".to_string());
+    }
+    errors
 }
 
 /// Contains information about vesting that contains vesting schedule and termination information.
@@ -136,6 +175,8 @@ pub enum VestingInformation {
     /// It means the termination of the vesting is currently in progress.
     /// Once the unvested amount is transferred out, `VestingInformation` is removed.
     Terminating(TerminationInformation),
+    /// Explicit vesting in discrete tranches.
+    VestingTranches(Vec<VestingTranche>),
 }
 
 /// Describes the status of transactions with the staking pool contract or terminated unvesting
@@ -189,3 +230,68 @@ impl VestingScheduleWithSalt {
         env::sha256(&borsh::to_vec(&self).expect("Failed to serialize"))
     }
 }
+
+/// The result of validating the lockup creation arguments without creating the lockup.
+#[derive(Debug)]
+#[unc(serializers=[json])]
+pub struct ValidationReport {
+    /// The account ID of the lockup that would be created for the owner.
+    pub lockup_account_id: AccountId,
+    /// Whether the given amount is enough to create the lockup.
+    pub deposit_sufficient: bool,
+    /// The problems found with the vesting schedule. Empty if there are none.
+    pub vesting_schedule_errors: Vec<String>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if the lockup can be created with the validated arguments.
+    pub fn is_valid(&self) -> bool {
+        self.deposit_sufficient && self.vesting_schedule_errors.is_empty()
+    }
+}
+
+/// The configuration of the lockup factory.
+#[derive(Debug, PartialEq)]
+#[unc(serializers=[json])]
+pub struct FactoryConfig {
+    /// The account ID of the foundation.
+    pub foundation_account_id: AccountId,
+    /// The default staking pool whitelist account ID for the created lockups.
+    pub whitelist_account_id: AccountId,
+    /// The account ID under which the lockups are created.
+    pub lockup_master_account_id: AccountId,
+    /// The minimum deposit that has to be attached to create a lockup.
+    pub min_attached_balance: U128,
+}
+
+/// The operational statistics of the lockup factory for monitoring.
+#[derive(Debug, PartialEq)]
+#[unc(serializers=[json])]
+pub struct FactoryStats {
+    /// The number of lockups successfully created by this factory.
+    pub lockups_created: u64,
+    /// The number of created lockups that didn't report closing.
+    pub active_lockups: u64,
+    /// The total amount transferred to the successfully created lockups, excluding the creation
+    /// fees.
+    pub total_deployed_balance: U128,
+    /// The number of accounts with a deposit not settled by the creation callback yet.
+    pub pending_refunds_count: u64,
+    /// The hex-encoded SHA-256 hash of the configuration returned by `get_config` together with
+    /// the gas, the creation fee and the default release duration, to detect configuration
+    /// changes.
+    pub config_hash: String,
+}
+
+/// Events emitted by the lockup factory.
+#[unc(event_json(standard = "lockup_factory"))]
+pub enum LockupFactoryEvent {
+    /// The factory created a lockup with the lockup code of the given version. The version is
+    /// `None` for the lockup code built into the factory.
+    #[event_version("1.0.0")]
+    LockupCreated {
+        lockup_account_id: AccountId,
+        owner_account_id: AccountId,
+        lockup_code_version: Option<String>,
+    },
+}
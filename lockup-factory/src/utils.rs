@@ -1,7 +1,11 @@
-use unc_sdk::{env, PromiseResult};
+use unc_sdk::{env, AccountId, PromiseResult};
 
 pub fn assert_self() {
-    assert_eq!(env::predecessor_account_id(), env::current_account_id());
+    assert_eq!(
+        env::predecessor_account_id(),
+        env::current_account_id(),
+        "Can be called only as a callback"
+    );
 }
 
 pub fn is_promise_success() -> bool {
@@ -11,3 +15,27 @@ pub fn is_promise_success() -> bool {
         _ => false,
     }
 }
+
+/// Returns `true` if the given string is a valid single label of an account ID, i.e. it consists
+/// of lowercase alphanumeric characters separated by single `-` or `_`, and has no dots.
+pub fn is_valid_account_id_label(label: &str) -> bool {
+    let mut last_char_is_separator = true;
+    for c in label.chars() {
+        match c {
+            'a'..='z' | '0'..='9' => last_char_is_separator = false,
+            '-' | '_' if !last_char_is_separator => last_char_is_separator = true,
+            _ => return false,
+        }
+    }
+    !last_char_is_separator
+}
+
+/// Asserts that the given staking pool whitelist account ID is not the account ID of this
+/// contract.
+pub fn assert_not_self_whitelist(whitelist_account_id: &AccountId) {
+    assert_ne!(
+        whitelist_account_id,
+        &env::current_account_id(),
+        "The whitelist account ID can't be the account ID of this contract"
+    );
+}
@@ -1,3 +1,4 @@
+use unc_sdk::json_types::U64;
 use unc_sdk::{unc, AccountId, Promise, Gas};
 
 use crate::*;
@@ -17,8 +18,26 @@ impl LockupContract {
         vesting_schedule_with_salt: Option<VestingScheduleWithSalt>,
     ) {
         self.assert_called_by_foundation();
-        let vesting_schedule = self.assert_vesting(vesting_schedule_with_salt);
-        let unvested_amount = self.get_unvested_amount(vesting_schedule);
+        let block_timestamp = env::block_timestamp();
+        let unvested_amount =
+            if let VestingInformation::VestingTranches(tranches) = &self.vesting_information {
+                assert!(
+                    vesting_schedule_with_salt.is_none(),
+                    "Explicit vesting schedule exists"
+                );
+                self.internal_get_unvested_tranches_amount(tranches, block_timestamp)
+            } else if self.vesting_information == VestingInformation::None {
+                // Without the vesting schedule, only the vesting grants can be terminated.
+                assert!(vesting_schedule_with_salt.is_none(), "Vesting is None");
+                0.into()
+            } else {
+                let vesting_schedule = self.assert_vesting(vesting_schedule_with_salt);
+                self.internal_get_unvested_amount_at(&vesting_schedule, block_timestamp)
+            };
+        // The vesting grants are terminated together with the vesting schedule.
+        let unvested_amount: WrappedBalance = (unvested_amount.0
+            + self.internal_get_unvested_grants_amount_at(block_timestamp))
+        .into();
         assert!(unvested_amount.0 > 0, "The account is fully vested");
 
         env::log_str(
@@ -40,12 +59,88 @@ impl LockupContract {
             TerminationStatus::ReadyToWithdraw
         };
 
+        self.terminated_vesting_information = Some(self.vesting_information.clone());
         self.vesting_information = VestingInformation::Terminating(TerminationInformation {
             unvested_amount,
             status,
         });
     }
 
+    /// FOUNDATION'S METHOD
+    ///
+    /// Requires 25 TGas (1 * BASE_GAS)
+    ///
+    /// Adds a vesting grant of the given amount to the owner, which vests on the given schedule.
+    /// The granted tokens have to be attached as the deposit. They become part of the lockup
+    /// amount, so they are locked and released together with the rest of the tokens, and the
+    /// vesting termination terminates the grant as well.
+    #[payable]
+    pub fn add_vesting_grant(&mut self, vesting_schedule: VestingSchedule, amount: WrappedBalance) {
+        self.assert_called_by_foundation();
+        self.assert_no_termination();
+        vesting_schedule.assert_valid();
+        assert!(amount.0 > 0, "The grant amount should be positive");
+        assert_eq!(
+            env::attached_deposit().as_attounc(),
+            amount.0,
+            "The attached deposit should be equal to the grant amount"
+        );
+        assert!(
+            self.vesting_grants.len() < MAX_VESTING_GRANTS,
+            "Too many vesting grants"
+        );
+
+        env::log_str(
+            format!(
+                "Adding a vesting grant of {} vesting until {}",
+                amount.0, vesting_schedule.end_timestamp.0
+            )
+            .as_str(),
+        );
+        self.lockup_information.lockup_amount += amount.0;
+        self.vesting_grants.push(VestingGrant {
+            vesting_schedule,
+            amount,
+        });
+    }
+
+    /// FOUNDATION'S METHOD
+    ///
+    /// Requires 25 TGas (1 * BASE_GAS)
+    ///
+    /// Cancels the termination initiated in error and restores the vesting schedule or its hash.
+    /// Only possible until the unvested balance starts being withdrawn out of the account, and
+    /// while no termination transaction is in progress.
+    /// NOTE: The tokens unstaked or withdrawn from the staking pool during the termination are not
+    /// staked back. The owner can stake them again.
+    pub fn cancel_termination(&mut self) {
+        self.assert_called_by_foundation();
+        let status = self.get_termination_status();
+        assert!(status.is_some(), "There is no termination in progress");
+        assert!(
+            matches!(
+                status,
+                Some(TerminationStatus::VestingTerminatedWithDeficit)
+                    | Some(TerminationStatus::EverythingUnstaked)
+                    | Some(TerminationStatus::ReadyToWithdraw)
+            ),
+            "The termination can't be cancelled while a transaction is in progress"
+        );
+        // The vesting can be terminated only once, so any withdrawn tokens were withdrawn by this
+        // termination.
+        assert_eq!(
+            self.lockup_information.termination_withdrawn_tokens, 0,
+            "The terminated unvested balance is already withdrawn"
+        );
+        let vesting_information = self
+            .terminated_vesting_information
+            .take()
+            .expect("The vesting information before the termination is unknown");
+
+        env::log_str("Cancelling the termination of the vesting");
+        self.vesting_information = vesting_information;
+    }
+
     /// FOUNDATION'S METHOD
     ///
     /// Requires 175 TGas (7 * BASE_GAS)
@@ -79,19 +174,18 @@ impl LockupContract {
                 self.set_staking_pool_status(TransactionStatus::Busy);
                 env::log_str("Termination Step: Going to unstake everything from the staking pool");
 
-                ext_staking_pool::ext(env::current_account_id())
+                ext_staking_pool::ext(self
+                        .staking_information
+                        .as_ref()
+                        .unwrap()
+                        .staking_pool_account_id
+                        .clone())
                     .with_static_gas(Gas::from_gas(gas::staking_pool::GET_ACCOUNT_STAKED_BALANCE))
                     .with_attached_deposit(NO_DEPOSIT)
-                    .get_account_staked_balance(
-                        self
-                            .staking_information
-                            .as_ref()
-                            .unwrap()
-                            .staking_pool_account_id
-                            .clone())
+                    .get_account_staked_balance(env::current_account_id())
                 .then(
                     ext_self_foundation::ext(env::current_account_id())
-                        .with_static_gas(Gas::from_gas(gas::foundation_callbacks::ON_GET_ACCOUNT_STAKED_BALANCE_TO_UNSTAKE))
+                        .with_static_gas(self.on_get_account_staked_balance_to_unstake_gas())
                         .with_attached_deposit(NO_DEPOSIT) 
                         .on_get_account_staked_balance_to_unstake(),
                 )
@@ -104,19 +198,18 @@ impl LockupContract {
                 self.set_staking_pool_status(TransactionStatus::Busy);
                 env::log_str("Termination Step: Going to withdraw everything from the staking pool");
 
-                ext_staking_pool::ext(env::current_account_id())
+                ext_staking_pool::ext(self
+                        .staking_information
+                        .as_ref()
+                        .unwrap()
+                        .staking_pool_account_id
+                        .clone())
                     .with_static_gas(Gas::from_gas(gas::staking_pool::GET_ACCOUNT_UNSTAKED_BALANCE))
                     .with_attached_deposit(NO_DEPOSIT)
-                    .get_account_unstaked_balance(
-                        self
-                            .staking_information
-                            .as_ref()
-                            .unwrap()
-                            .staking_pool_account_id
-                            .clone())
+                    .get_account_unstaked_balance(env::current_account_id())
                 .then(
                     ext_self_foundation::ext(env::current_account_id())
-                        .with_static_gas(Gas::from_gas(gas::foundation_callbacks::ON_GET_ACCOUNT_UNSTAKED_BALANCE_TO_WITHDRAW))
+                        .with_static_gas(self.on_get_account_unstaked_balance_to_withdraw_gas())
                         .with_attached_deposit(NO_DEPOSIT)
                         .on_get_account_unstaked_balance_to_withdraw(),
                 )
@@ -131,6 +224,8 @@ impl LockupContract {
     /// Withdraws the unvested amount from the early termination of the vesting schedule.
     pub fn termination_withdraw(&mut self, receiver_id: AccountId) -> Promise {
         self.assert_called_by_foundation();
+        // The balance can't be relied on while a staking pool call is in flight.
+        self.assert_no_staking_or_idle();
         assert!(
             env::is_valid_account_id(receiver_id.as_bytes()),
             "The receiver account ID is invalid"
@@ -170,4 +265,113 @@ impl LockupContract {
             ),
         )
     }
+
+    /// FOUNDATION'S METHOD
+    ///
+    /// Requires 25 TGas (1 * BASE_GAS)
+    ///
+    /// Reassigns the foundation account, e.g. when the foundation migrates to a new account.
+    /// Only possible while the foundation account is set, i.e. the vesting schedule is active.
+    /// Can only be called by the foundation account itself, not by the signers.
+    /// The signers approved by the previous foundation account are revoked, so the new foundation
+    /// account has to approve its own signers.
+    pub fn set_foundation_account_id(&mut self, new_foundation_account_id: AccountId) {
+        assert!(
+            self.foundation_account_id.is_some(),
+            "No UNC Foundation account is specified in the contract"
+        );
+        self.assert_called_by_foundation_account();
+        assert!(
+            env::is_valid_account_id(new_foundation_account_id.as_bytes()),
+            "The foundation account ID is invalid"
+        );
+
+        env::log_str(
+            format!(
+                "Reassigning the foundation account to @{}",
+                new_foundation_account_id
+            )
+            .as_str(),
+        );
+        self.foundation_account_id = Some(new_foundation_account_id);
+        self.foundation_signers.clear();
+    }
+
+    /// FOUNDATION'S METHOD
+    ///
+    /// Requires 25 TGas (1 * BASE_GAS)
+    ///
+    /// Approves the ownership change to the given account proposed by the owner with
+    /// `propose_owner_change` and reassigns the lockup to it. The given account has to match the
+    /// current proposal, so a proposal replaced in the meantime isn't approved by mistake.
+    pub fn approve_owner_change(&mut self, new_owner_account_id: AccountId) {
+        self.assert_called_by_foundation();
+        let proposed_owner_account_id = self
+            .proposed_owner_account_id
+            .as_ref()
+            .expect("There is no proposed owner change");
+        assert_eq!(
+            proposed_owner_account_id, &new_owner_account_id,
+            "The new owner account ID doesn't match the proposed one"
+        );
+        self.proposed_owner_account_id = None;
+
+        env::log_str(
+            format!(
+                "Reassigning the ownership from @{} to @{}",
+                self.owner_account_id, new_owner_account_id
+            )
+            .as_str(),
+        );
+        self.owner_account_id = new_owner_account_id;
+    }
+
+    /// FOUNDATION'S METHOD
+    ///
+    /// Approves the given account to call foundation's methods, e.g. a multisig or an ops team
+    /// account that can initiate termination on behalf of the UNC Foundation.
+    /// Can only be called by the foundation account itself, not by the signers.
+    /// Returns `true` if the account wasn't approved before.
+    pub fn add_foundation_signer(&mut self, account_id: AccountId) -> bool {
+        self.assert_called_by_foundation_account();
+        assert!(
+            env::is_valid_account_id(account_id.as_bytes()),
+            "The foundation signer account ID is invalid"
+        );
+
+        env::log_str(format!("Adding @{} as a foundation signer", account_id).as_str());
+        self.foundation_signers.insert(account_id)
+    }
+
+    /// FOUNDATION'S METHOD
+    ///
+    /// Revokes the approval of the given account to call foundation's methods.
+    /// Can only be called by the foundation account itself, not by the signers.
+    /// Returns `true` if the account was approved before.
+    pub fn remove_foundation_signer(&mut self, account_id: AccountId) -> bool {
+        self.assert_called_by_foundation_account();
+
+        env::log_str(format!("Removing @{} from the foundation signers", account_id).as_str());
+        self.foundation_signers.remove(&account_id)
+    }
+
+    /// FOUNDATION'S METHOD
+    ///
+    /// Updates the amount of gas attached to the staking pool `unstake` call during the
+    /// termination, e.g. if the staking pool method becomes heavier.
+    pub fn set_termination_unstake_gas(&mut self, termination_unstake_gas: U64) {
+        self.assert_called_by_foundation();
+        assert!(termination_unstake_gas.0 > 0, "Gas should be positive");
+        self.termination_unstake_gas = Gas::from_gas(termination_unstake_gas.0);
+    }
+
+    /// FOUNDATION'S METHOD
+    ///
+    /// Updates the amount of gas attached to the staking pool `withdraw` call during the
+    /// termination, e.g. if the staking pool method becomes heavier.
+    pub fn set_termination_withdraw_gas(&mut self, termination_withdraw_gas: U64) {
+        self.assert_called_by_foundation();
+        assert!(termination_withdraw_gas.0 > 0, "Gas should be positive");
+        self.termination_withdraw_gas = Gas::from_gas(termination_withdraw_gas.0);
+    }
 }
@@ -31,7 +31,7 @@ impl LockupContract {
                     .unwrap()
                     .staking_pool_account_id
                     .clone())
-                .with_static_gas(Gas::from_gas(gas::staking_pool::UNSTAKE))
+                .with_static_gas(self.termination_unstake_gas)
                 .with_attached_deposit(NO_DEPOSIT)
                 .unstake(
                     staked_balance,
@@ -118,7 +118,7 @@ impl LockupContract {
                     .unwrap()
                     .staking_pool_account_id
                     .clone())
-                .with_static_gas(Gas::from_gas(gas::staking_pool::WITHDRAW))
+                .with_static_gas(self.termination_withdraw_gas)
                 .with_attached_deposit(NO_DEPOSIT)
                 .withdraw(
                     unstaked_balance,
@@ -227,6 +227,8 @@ impl LockupContract {
             } else {
                 self.foundation_account_id = None;
                 self.vesting_information = VestingInformation::None;
+                self.terminated_vesting_information = None;
+                self.vesting_grants.clear();
                 env::log_str("Vesting schedule termination and withdrawal are completed");
             }
         } else {
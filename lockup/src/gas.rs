@@ -55,11 +55,24 @@ pub mod transfer_poll {
     pub const GET_RESULT: u64 = super::BASE_GAS;
 }
 
+pub mod upgrade {
+    /// Gas attached to the `migrate` call after the new contract code is deployed.
+    /// Requires BASE for reading and writing the state.
+    pub const MIGRATE: u64 = super::BASE_GAS;
+}
+
 pub mod owner_callbacks {
     /// Gas attached to the inner callback for processing whitelist check results.
     /// Requires BASE for local execution.
     pub const ON_WHITELIST_IS_WHITELISTED: u64 = super::BASE_GAS;
 
+    /// Gas attached to the inner callback for processing whitelist check results of the staking
+    /// pool selected on init, which deposits and stakes the account balance.
+    /// Requires BASE for local execution + DEPOSIT_AND_STAKE + ON_STAKING_POOL_DEPOSIT_AND_STAKE.
+    pub const ON_WHITELIST_IS_WHITELISTED_TO_STAKE: u64 = super::BASE_GAS
+        + super::staking_pool::DEPOSIT_AND_STAKE
+        + ON_STAKING_POOL_DEPOSIT_AND_STAKE;
+
     /// Gas attached to the inner callback for processing result of the deposit call to the
     /// staking pool.
     /// Requires BASE for local updates.
@@ -99,12 +112,27 @@ pub mod owner_callbacks {
     /// Requires BASE for local updates.
     pub const ON_GET_ACCOUNT_TOTAL_BALANCE: u64 = super::BASE_GAS;
 
+    /// Gas attached to the inner callback for processing result of the call to get the current
+    /// total balance from the staking pool before unselecting it.
+    /// Requires BASE for local updates.
+    pub const ON_GET_ACCOUNT_TOTAL_BALANCE_TO_UNSELECT: u64 = super::BASE_GAS;
+
     /// Gas attached to the inner callback for processing result of the call to get the current
     /// unstaked balance from the staking pool.
     /// The callback might proceed with withdrawing this amount.
     /// Requires BASE for local updates + gas for withdraw + gas for another callback.
     pub const ON_GET_ACCOUNT_UNSTAKED_BALANCE_TO_WITHDRAW_BY_OWNER: u64 =
         super::BASE_GAS + super::staking_pool::WITHDRAW + ON_STAKING_POOL_WITHDRAW;
+
+    /// Gas attached to the inner callback for processing result of the call to get the current
+    /// staked balance from the staking pool to exit it.
+    /// The callback either unstakes everything or proceeds with querying the unstaked balance to
+    /// withdraw it, which requires more gas.
+    /// Requires BASE for local updates + gas for the unstaked balance query + gas for the callback
+    /// that withdraws it.
+    pub const ON_GET_ACCOUNT_STAKED_BALANCE_TO_EXIT: u64 = super::BASE_GAS
+        + super::staking_pool::GET_ACCOUNT_UNSTAKED_BALANCE
+        + ON_GET_ACCOUNT_UNSTAKED_BALANCE_TO_WITHDRAW_BY_OWNER;
 }
 
 pub mod foundation_callbacks {
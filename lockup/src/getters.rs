@@ -1,4 +1,4 @@
-use unc_sdk::json_types::U128;
+use unc_sdk::json_types::U64;
 use unc_sdk::unc;
 
 use crate::*;
@@ -41,6 +41,8 @@ impl LockupContract {
 
     /// Returns the amount of tokens that are not going to be vested, because the vesting schedule
     /// was terminated earlier.
+    /// The amount is decreased by every withdrawal of the foundation, so it's the amount remaining
+    /// to be withdrawn and `termination_withdrawn_tokens` should not be subtracted from it again.
     pub fn get_terminated_unvested_balance(&self) -> WrappedBalance {
         if let VestingInformation::Terminating(TerminationInformation {
             unvested_amount, ..
@@ -63,53 +65,54 @@ impl LockupContract {
 
     /// Returns the amount of tokens that are locked in the account due to lockup or vesting.
     pub fn get_locked_amount(&self) -> WrappedBalance {
-        let lockup_amount = self.lockup_information.lockup_amount;
-        if let TransfersInformation::TransfersEnabled {
-            transfers_timestamp,
-        } = &self.lockup_information.transfers_information
-        {
-            let lockup_timestamp = std::cmp::max(
-                transfers_timestamp
-                    .0
-                    .saturating_add(self.lockup_information.lockup_duration),
-                self.lockup_information.lockup_timestamp.unwrap_or(0),
-            );
-            let block_timestamp = env::block_timestamp();
-            if lockup_timestamp <= block_timestamp {
-                let unreleased_amount =
-                    if let &Some(release_duration) = &self.lockup_information.release_duration {
-                        let end_timestamp = lockup_timestamp.saturating_add(release_duration);
-                        if block_timestamp >= end_timestamp {
-                            // Everything is released
-                            0
-                        } else {
-                            let time_left = U256::from(end_timestamp - block_timestamp);
-                            let unreleased_amount = U256::from(lockup_amount) * time_left
-                                / U256::from(release_duration);
-                            // The unreleased amount can't be larger than lockup_amount because the
-                            // time_left is smaller than total_time.
-                            unreleased_amount.as_u128()
-                        }
-                    } else {
-                        0
-                    };
-
-                let unvested_amount = match &self.vesting_information {
-                    VestingInformation::VestingSchedule(vs) => self.get_unvested_amount(vs.clone()),
-                    VestingInformation::Terminating(terminating) => terminating.unvested_amount,
-                    // Vesting is private, so we can assume the vesting started before lockup date.
-                    _ => U128(0),
-                };
-                return std::cmp::max(
-                    unreleased_amount
-                        .saturating_sub(self.lockup_information.termination_withdrawn_tokens),
-                    unvested_amount.0,
-                )
-                .into();
+        self.internal_get_locked_amount_at(env::block_timestamp()).into()
+    }
+
+    /// Returns the number of seconds, rounded up, until the locked amount decreases next time:
+    /// the end of the lockup, the end of the release, the vesting cliff or end, or the next vesting
+    /// tranche. While the tokens are released or vested linearly, it's the time until the linear
+    /// release or vesting ends.
+    /// Returns 0 if nothing is locked or no unlock is scheduled yet, e.g. while the transfers are
+    /// disabled.
+    pub fn get_seconds_until_next_unlock(&self) -> u64 {
+        let block_timestamp = env::block_timestamp();
+        let locked_amount = self.internal_get_locked_amount_at(block_timestamp);
+        let Some(lockup_timestamp) = self.internal_get_lockup_end_timestamp() else {
+            return 0;
+        };
+
+        let mut unlock_timestamps = vec![lockup_timestamp];
+        if let Some(release_duration) = self.lockup_information.release_duration {
+            unlock_timestamps.push(lockup_timestamp.saturating_add(release_duration));
+        }
+        match &self.vesting_information {
+            VestingInformation::VestingSchedule(vesting_schedule) => {
+                unlock_timestamps.push(vesting_schedule.cliff_timestamp.0);
+                unlock_timestamps.push(vesting_schedule.end_timestamp.0);
             }
+            VestingInformation::VestingTranches(tranches) => {
+                unlock_timestamps.extend(tranches.iter().map(|(timestamp, _)| timestamp.0));
+            }
+            _ => {}
+        }
+        for grant in &self.vesting_grants {
+            unlock_timestamps.push(grant.vesting_schedule.cliff_timestamp.0);
+            unlock_timestamps.push(grant.vesting_schedule.end_timestamp.0);
         }
-        // The entire balance is still locked before the lockup timestamp.
-        (lockup_amount - self.lockup_information.termination_withdrawn_tokens).into()
+
+        // Skips the boundaries that don't unlock anything, e.g. the end of the lockup before the
+        // vesting cliff. The linear release only starts decreasing the locked amount right after
+        // the end of the lockup, so the amount is checked a nanosecond after the boundary.
+        unlock_timestamps
+            .into_iter()
+            .filter(|&timestamp| {
+                timestamp > block_timestamp
+                    && self.internal_get_locked_amount_at(timestamp.saturating_add(1))
+                        < locked_amount
+            })
+            .min()
+            .map(|timestamp| (timestamp - block_timestamp).div_ceil(1_000_000_000))
+            .unwrap_or(0)
     }
 
     /// Returns the amount of tokens that are already vested, but still locked due to lockup.
@@ -118,37 +121,37 @@ impl LockupContract {
         (self.get_locked_amount().0 - self.get_unvested_amount(vesting_schedule).0).into()
     }
 
-    /// Returns the amount of tokens that are locked in this account due to vesting schedule.
+    /// Returns the amount of tokens that are locked in this account due to vesting schedule,
+    /// including the unvested amount of the additional vesting grants.
     /// Takes raw vesting schedule, in case the internal vesting schedule is private.
+    /// The given schedule is ignored for the vesting in tranches, which is always public.
     pub fn get_unvested_amount(&self, vesting_schedule: VestingSchedule) -> WrappedBalance {
         let block_timestamp = env::block_timestamp();
-        let lockup_amount = self.lockup_information.lockup_amount;
-        match &self.vesting_information {
-            VestingInformation::Terminating(termination_information) => {
-                termination_information.unvested_amount
-            }
-            VestingInformation::None => U128::from(0),
-            _ => {
-                if block_timestamp < vesting_schedule.cliff_timestamp.0 {
-                    // Before the cliff, nothing is vested
-                    lockup_amount.into()
-                } else if block_timestamp >= vesting_schedule.end_timestamp.0 {
-                    // After the end, everything is vested
-                    0.into()
-                } else {
-                    // cannot overflow since block_timestamp < vesting_schedule.end_timestamp
-                    let time_left = U256::from(vesting_schedule.end_timestamp.0 - block_timestamp);
-                    // The total time is positive. Checked at the contract initialization.
-                    let total_time = U256::from(
-                        vesting_schedule.end_timestamp.0 - vesting_schedule.start_timestamp.0,
-                    );
-                    let unvested_amount = U256::from(lockup_amount) * time_left / total_time;
-                    // The unvested amount can't be larger than lockup_amount because the
-                    // time_left is smaller than total_time.
-                    unvested_amount.as_u128().into()
-                }
-            }
-        }
+        (self
+            .internal_get_unvested_amount_at(&vesting_schedule, block_timestamp)
+            .0
+            + self.internal_get_unvested_grants_amount_at(block_timestamp))
+        .into()
+    }
+
+    /// Returns the amount of tokens that are vested according to the internal vesting schedule,
+    /// including the vested amount of the additional vesting grants. Without vesting, the whole
+    /// lockup amount is vested. It includes the tokens that are still locked due to lockup, see
+    /// `get_vested_and_unlocked`.
+    /// The hashed vesting schedule is private, so it's assumed to be vested.
+    pub fn get_vested_amount(&self) -> WrappedBalance {
+        self.internal_get_lockup_amount_left()
+            .saturating_sub(self.internal_get_stored_unvested_amount_at(env::block_timestamp()))
+            .into()
+    }
+
+    /// Returns the amount of tokens that are vested and no longer locked due to lockup, i.e. the
+    /// part of `get_vested_amount` that is available now. The rest of the vested amount is
+    /// returned by `get_locked_vested_amount`.
+    pub fn get_vested_and_unlocked(&self) -> WrappedBalance {
+        self.internal_get_lockup_amount_left()
+            .saturating_sub(self.get_locked_amount().0)
+            .into()
     }
 
     /// Returns the internal vesting information.
@@ -156,6 +159,11 @@ impl LockupContract {
         self.vesting_information.clone()
     }
 
+    /// Returns the additional vesting grants added by the UNC Foundation.
+    pub fn get_vesting_grants(&self) -> Vec<VestingGrant> {
+        self.vesting_grants.clone()
+    }
+
     /// Returns the balance of the account owner. It includes vested and extra tokens that
     /// may have been deposited to this account, but excludes locked tokens.
     /// NOTE: Some of this tokens may be deposited to the staking pool.
@@ -180,10 +188,118 @@ impl LockupContract {
         std::cmp::min(self.get_owners_balance().0, self.get_account_balance().0).into()
     }
 
+    /// Returns the amount of tokens above the lockup amount that can be withdrawn right away, e.g.
+    /// tokens that were sent to this account by mistake.
+    /// NOTE: It never includes locked or unvested tokens, nor the storage staking balance.
+    pub fn get_unaccounted_balance(&self) -> WrappedBalance {
+        let accounted_amount = self
+            .lockup_information
+            .lockup_amount
+            .saturating_sub(self.lockup_information.termination_withdrawn_tokens);
+        let surplus = self.get_balance().0.saturating_sub(accounted_amount);
+        std::cmp::min(surplus, self.get_liquid_owners_balance().0).into()
+    }
+
+    /// Returns the amount of tokens kept in the account to cover the storage.
+    pub fn get_storage_reserve(&self) -> WrappedBalance {
+        self.storage_reserve.into()
+    }
+
+    /// Returns the list of additional accounts approved to call foundation's methods.
+    pub fn get_foundation_signers(&self) -> Vec<AccountId> {
+        self.foundation_signers.iter().cloned().collect()
+    }
+
+    /// Returns the amount of gas attached to the staking pool `unstake` call during the termination.
+    pub fn get_termination_unstake_gas(&self) -> U64 {
+        self.termination_unstake_gas.as_gas().into()
+    }
+
+    /// Returns the amount of gas attached to the staking pool `withdraw` call during the
+    /// termination.
+    pub fn get_termination_withdraw_gas(&self) -> U64 {
+        self.termination_withdraw_gas.as_gas().into()
+    }
+
+    /// Returns the summary of the lockup state in a single call.
+    pub fn get_lockup_info(&self) -> LockupInfo {
+        LockupInfo {
+            owner_account_id: self.get_owner_account_id(),
+            lockup_timestamp: self.lockup_information.lockup_timestamp.map(|t| t.into()),
+            lockup_duration: self.lockup_information.lockup_duration.into(),
+            release_duration: self.lockup_information.release_duration.map(|d| d.into()),
+            transfers_information: self.lockup_information.transfers_information.clone(),
+            vesting_information: self.get_vesting_information(),
+            termination_status: self.get_termination_status(),
+            staking_pool_account_id: self.get_staking_pool_account_id(),
+            known_deposited_balance: self.get_known_deposited_balance(),
+            locked_amount: self.get_locked_amount(),
+            liquid_owners_balance: self.get_liquid_owners_balance(),
+            terminated_unvested_balance: self.get_terminated_unvested_balance(),
+        }
+    }
+
+    /// Returns the raw balance breakdown of the account in a single call. The locked amount
+    /// overlaps with the storage reserve and the staking deposit, so the fields don't add up to the
+    /// total balance. Instead, the total balance is the locked amount plus the owner's balance.
+    pub fn get_balance_breakdown(&self) -> BalanceBreakdown {
+        let unvested_amount = self
+            .internal_get_stored_unvested_amount_at(env::block_timestamp())
+            .into();
+        BalanceBreakdown {
+            account_balance: env::account_balance().as_attounc().into(),
+            total_balance: self.get_balance(),
+            locked_amount: self.get_locked_amount(),
+            unvested_amount,
+            terminated_unvested_balance: self.get_terminated_unvested_balance(),
+            known_deposited_balance: self.get_known_deposited_balance(),
+            termination_withdrawn_tokens: self
+                .lockup_information
+                .termination_withdrawn_tokens
+                .into(),
+            storage_reserve: self.get_storage_reserve(),
+            owners_balance: self.get_owners_balance(),
+            liquid_owners_balance: self.get_liquid_owners_balance(),
+        }
+    }
+
+    /// Returns `true` if the foundation can terminate the vesting now, i.e. the vesting isn't
+    /// terminated yet and some tokens are still unvested, including the vesting grants.
+    /// The hashed vesting schedule can't be checked, so it's assumed to be terminable.
+    pub fn is_terminable(&self) -> bool {
+        if self.foundation_account_id.is_none() {
+            return false;
+        }
+        if self.internal_get_unvested_grants_amount_at(env::block_timestamp()) > 0 {
+            return true;
+        }
+        match &self.vesting_information {
+            VestingInformation::VestingSchedule(vesting_schedule) => {
+                self.get_unvested_amount(vesting_schedule.clone()).0 > 0
+            }
+            VestingInformation::VestingTranches(tranches) => {
+                self.internal_get_unvested_tranches_amount(tranches, env::block_timestamp()).0 > 0
+            }
+            VestingInformation::VestingHash(_) => true,
+            VestingInformation::None | VestingInformation::Terminating(_) => false,
+        }
+    }
+
+    /// Returns the new owner account ID proposed by the owner and waiting for the approval of the
+    /// UNC Foundation.
+    pub fn get_proposed_owner_account_id(&self) -> Option<AccountId> {
+        self.proposed_owner_account_id.clone()
+    }
+
     /// Returns `true` if transfers are enabled, `false` otherwise.
+    /// Transfers are enabled once the block timestamp reaches the transfers timestamp. While
+    /// transfers are disabled, the transfer poll has to be checked with `check_transfers_vote`
+    /// first, which records the timestamp when the transfers were voted in.
     pub fn are_transfers_enabled(&self) -> bool {
         match &self.lockup_information.transfers_information {
-            TransfersInformation::TransfersEnabled { .. } => true,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp,
+            } => env::block_timestamp() >= transfers_timestamp.0,
             TransfersInformation::TransfersDisabled { .. } => false,
         }
     }
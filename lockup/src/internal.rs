@@ -1,3 +1,6 @@
+use unc_sdk::json_types::U128;
+use unc_sdk::Promise;
+
 use crate::*;
 
 /********************/
@@ -5,11 +8,11 @@ use crate::*;
 /********************/
 
 impl LockupContract {
-    /// The balance of the account excluding the storage staking balance.
+    /// The balance of the account excluding the storage reserve.
     /// NOTE: The storage staking balance can't be transferred out without deleting this contract.
     pub fn get_account_balance(&self) -> WrappedBalance {
         env::account_balance()
-            .saturating_sub(UncToken::from_attounc(MIN_BALANCE_FOR_STORAGE))
+            .saturating_sub(UncToken::from_attounc(self.storage_reserve))
             .as_attounc()
             .into()
     }
@@ -57,34 +60,197 @@ impl LockupContract {
             }
             VestingInformation::Terminating(_) => env::panic_str("Vesting was terminated"),
             VestingInformation::None => env::panic_str("Vesting is None"),
+            VestingInformation::VestingTranches(_) => {
+                env::panic_str("Vesting is in tranches and has no schedule")
+            }
         }
     }
 
-    pub fn assert_no_termination(&self) {
+    /// Returns the timestamp when the lockup ends and the release starts, or `None` while the
+    /// transfers are disabled.
+    pub fn internal_get_lockup_end_timestamp(&self) -> Option<Timestamp> {
+        if let TransfersInformation::TransfersEnabled {
+            transfers_timestamp,
+        } = &self.lockup_information.transfers_information
+        {
+            Some(std::cmp::max(
+                transfers_timestamp
+                    .0
+                    .saturating_add(self.lockup_information.lockup_duration),
+                self.lockup_information.lockup_timestamp.unwrap_or(0),
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the amount of tokens that are locked due to lockup or vesting at the given timestamp.
+    pub fn internal_get_locked_amount_at(&self, block_timestamp: Timestamp) -> u128 {
+        let lockup_amount = self.lockup_information.lockup_amount;
+        if let Some(lockup_timestamp) = self.internal_get_lockup_end_timestamp() {
+            if lockup_timestamp <= block_timestamp {
+                let unreleased_amount =
+                    if let &Some(release_duration) = &self.lockup_information.release_duration {
+                        let end_timestamp = lockup_timestamp.saturating_add(release_duration);
+                        if block_timestamp >= end_timestamp {
+                            // Everything is released
+                            0
+                        } else {
+                            let time_left = U256::from(end_timestamp - block_timestamp);
+                            let unreleased_amount = U256::from(lockup_amount) * time_left
+                                / U256::from(release_duration);
+                            // The unreleased amount can't be larger than lockup_amount because the
+                            // time_left is smaller than total_time.
+                            unreleased_amount.as_u128()
+                        }
+                    } else {
+                        0
+                    };
+
+                let unvested_amount = match &self.vesting_information {
+                    VestingInformation::VestingSchedule(vs) => {
+                        self.internal_get_unvested_amount_at(vs, block_timestamp)
+                    }
+                    VestingInformation::VestingTranches(tranches) => {
+                        self.internal_get_unvested_tranches_amount(tranches, block_timestamp)
+                    }
+                    VestingInformation::Terminating(terminating) => terminating.unvested_amount,
+                    // Vesting is private, so we can assume the vesting started before lockup date.
+                    _ => U128(0),
+                };
+                let unvested_amount = unvested_amount.0
+                    + self.internal_get_unvested_grants_amount_at(block_timestamp);
+                return std::cmp::max(
+                    unreleased_amount
+                        .saturating_sub(self.lockup_information.termination_withdrawn_tokens),
+                    unvested_amount,
+                );
+            }
+        }
+        // The entire balance is still locked before the lockup timestamp.
+        lockup_amount - self.lockup_information.termination_withdrawn_tokens
+    }
+
+    /// Returns the amount of tokens that are unvested at the given timestamp according to the
+    /// given vesting schedule, or according to the vesting tranches if the vesting is in tranches.
+    pub fn internal_get_unvested_amount_at(
+        &self,
+        vesting_schedule: &VestingSchedule,
+        block_timestamp: Timestamp,
+    ) -> WrappedBalance {
+        // The vesting grants are part of the lockup amount, but they vest on their own schedules.
+        let lockup_amount = self
+            .lockup_information
+            .lockup_amount
+            .saturating_sub(self.internal_get_vesting_grants_amount());
+        match &self.vesting_information {
+            VestingInformation::Terminating(termination_information) => {
+                termination_information.unvested_amount
+            }
+            VestingInformation::None => U128::from(0),
+            VestingInformation::VestingTranches(tranches) => {
+                self.internal_get_unvested_tranches_amount(tranches, block_timestamp)
+            }
+            _ => vesting_schedule.unvested_amount_at(lockup_amount, block_timestamp).into(),
+        }
+    }
+
+    /// Returns the amount of tokens that are unvested at the given timestamp according to the
+    /// internal vesting information, including the additional vesting grants.
+    /// The hashed vesting schedule is private, so it's assumed to be vested.
+    pub fn internal_get_stored_unvested_amount_at(&self, block_timestamp: Timestamp) -> u128 {
+        let unvested_amount = match &self.vesting_information {
+            VestingInformation::VestingSchedule(vesting_schedule) => {
+                self.internal_get_unvested_amount_at(vesting_schedule, block_timestamp)
+            }
+            VestingInformation::VestingTranches(tranches) => {
+                self.internal_get_unvested_tranches_amount(tranches, block_timestamp)
+            }
+            VestingInformation::Terminating(termination_information) => {
+                termination_information.unvested_amount
+            }
+            VestingInformation::VestingHash(_) | VestingInformation::None => 0.into(),
+        };
+        unvested_amount.0 + self.internal_get_unvested_grants_amount_at(block_timestamp)
+    }
+
+    /// Returns the lockup amount without the tokens withdrawn by the foundation after the
+    /// termination.
+    pub fn internal_get_lockup_amount_left(&self) -> u128 {
+        self.lockup_information
+            .lockup_amount
+            .saturating_sub(self.lockup_information.termination_withdrawn_tokens)
+    }
+
+    /// Returns the total amount of tokens granted by the additional vesting grants.
+    pub fn internal_get_vesting_grants_amount(&self) -> u128 {
+        self.vesting_grants
+            .iter()
+            .fold(0u128, |total, grant| total.saturating_add(grant.amount.0))
+    }
+
+    /// Returns the amount of tokens that are unvested at the given timestamp across the additional
+    /// vesting grants. Once the vesting is terminated, the unvested amount of the grants is part of
+    /// the terminated unvested balance instead.
+    pub fn internal_get_unvested_grants_amount_at(&self, block_timestamp: Timestamp) -> u128 {
         if let VestingInformation::Terminating(_) = &self.vesting_information {
-            env::panic_str("All operations are blocked until vesting termination is completed");
+            return 0;
         }
+        self.vesting_grants.iter().fold(0u128, |total, grant| {
+            total.saturating_add(
+                grant
+                    .vesting_schedule
+                    .unvested_amount_at(grant.amount.0, block_timestamp),
+            )
+        })
+    }
+
+    /// Returns the sum of the vesting tranches whose timestamps haven't passed yet at the given
+    /// timestamp.
+    pub fn internal_get_unvested_tranches_amount(
+        &self,
+        tranches: &[VestingTranche],
+        block_timestamp: Timestamp,
+    ) -> WrappedBalance {
+        tranches
+            .iter()
+            .filter(|(timestamp, _)| block_timestamp < timestamp.0)
+            .fold(0u128, |total, (_, amount)| total.saturating_add(amount.0))
+            .into()
+    }
+
+    /// Blocks owner's operations, including staking, while the foundation goes through the
+    /// termination steps, i.e. until the termination status is cleared with the withdrawal of the
+    /// unvested balance.
+    pub fn assert_no_termination(&self) {
+        assert!(
+            self.get_termination_status().is_none(),
+            "All operations are blocked until vesting termination is completed"
+        );
     }
 
     pub fn assert_transfers_enabled(&self) {
         assert!(self.are_transfers_enabled(), "Transfers are disabled");
     }
 
+    /// Asserts that the transfers are still waiting for the transfer poll, even if the transfers
+    /// timestamp is not reached yet.
     pub fn assert_transfers_disabled(&self) {
         assert!(
-            !self.are_transfers_enabled(),
+            matches!(
+                self.lockup_information.transfers_information,
+                TransfersInformation::TransfersDisabled { .. }
+            ),
             "Transfers are already enabled"
         );
     }
 
     pub fn assert_no_staking_or_idle(&self) {
         if let Some(staking_information) = &self.staking_information {
-            match staking_information.status {
-                TransactionStatus::Idle => (),
-                TransactionStatus::Busy => {
-                    env::panic_str("Contract is currently busy with another operation")
-                }
-            };
+            assert!(
+                staking_information.status == TransactionStatus::Idle,
+                "Contract is currently busy with another operation"
+            );
         }
     }
 
@@ -93,12 +259,46 @@ impl LockupContract {
             self.staking_information.is_some(),
             "Staking pool is not selected"
         );
-        match self.staking_information.as_ref().unwrap().status {
-            TransactionStatus::Idle => (),
-            TransactionStatus::Busy => {
-                env::panic_str("Contract is currently busy with another operation")
-            }
-        };
+        assert!(
+            self.staking_information.as_ref().unwrap().status == TransactionStatus::Idle,
+            "Contract is currently busy with another operation"
+        );
+    }
+
+    /// Deposits and stakes the given amount to the selected staking pool, which has to be idle.
+    pub fn internal_deposit_and_stake(&mut self, amount: WrappedBalance) -> Promise {
+        env::log_str(
+            format!(
+                "Depositing and staking {} to the staking pool @{}",
+                amount.0,
+                self.staking_information
+                    .as_ref()
+                    .unwrap()
+                    .staking_pool_account_id
+            )
+            .as_str(),
+        );
+
+        self.set_staking_pool_status(TransactionStatus::Busy);
+
+        ext_staking_pool::ext(self
+                .staking_information
+                .as_ref()
+                .unwrap()
+                .staking_pool_account_id
+                .clone()
+            )
+            .with_static_gas(Gas::from_gas(gas::staking_pool::DEPOSIT_AND_STAKE))
+            .with_attached_deposit(UncToken::from_attounc(amount.0))
+            .deposit_and_stake(
+                //amount.0,
+        )
+        .then(ext_self_owner::ext(env::current_account_id())
+            .with_static_gas(Gas::from_gas(gas::owner_callbacks::ON_STAKING_POOL_DEPOSIT_AND_STAKE))
+            .with_attached_deposit(NO_DEPOSIT)
+            .on_staking_pool_deposit_and_stake(
+                amount,
+        ))
     }
 
     pub fn assert_staking_pool_is_not_selected(&self) {
@@ -108,11 +308,14 @@ impl LockupContract {
         );
     }
 
+    /// Asserts the predecessor is the foundation account or one of the approved foundation
+    /// signers.
     pub fn assert_called_by_foundation(&self) {
         if let Some(foundation_account_id) = &self.foundation_account_id {
-            assert_eq!(
-                &env::predecessor_account_id(),
-                foundation_account_id,
+            let predecessor_account_id = env::predecessor_account_id();
+            assert!(
+                &predecessor_account_id == foundation_account_id
+                    || self.foundation_signers.contains(&predecessor_account_id),
                 "Can only be called by UNC Foundation"
             )
         } else {
@@ -120,6 +323,40 @@ impl LockupContract {
         }
     }
 
+    /// Asserts the predecessor is the foundation account itself. The approved foundation signers
+    /// can't manage the foundation role, i.e. change the signers or the foundation account.
+    pub fn assert_called_by_foundation_account(&self) {
+        let foundation_account_id = self
+            .foundation_account_id
+            .as_ref()
+            .expect("No UNC Foundation account is specified in the contract");
+        assert_eq!(
+            &env::predecessor_account_id(),
+            foundation_account_id,
+            "Can only be called by UNC Foundation account"
+        );
+    }
+
+    /// Returns the gas for the callback that proceeds with unstaking during the termination.
+    /// The callback has to cover the configured gas of the `unstake` call instead of the default.
+    pub fn on_get_account_staked_balance_to_unstake_gas(&self) -> Gas {
+        Gas::from_gas(
+            gas::foundation_callbacks::ON_GET_ACCOUNT_STAKED_BALANCE_TO_UNSTAKE
+                - gas::staking_pool::UNSTAKE,
+        )
+        .saturating_add(self.termination_unstake_gas)
+    }
+
+    /// Returns the gas for the callback that proceeds with withdrawing during the termination.
+    /// The callback has to cover the configured gas of the `withdraw` call instead of the default.
+    pub fn on_get_account_unstaked_balance_to_withdraw_gas(&self) -> Gas {
+        Gas::from_gas(
+            gas::foundation_callbacks::ON_GET_ACCOUNT_UNSTAKED_BALANCE_TO_WITHDRAW
+                - gas::staking_pool::WITHDRAW,
+        )
+        .saturating_add(self.termination_withdraw_gas)
+    }
+
     pub fn assert_owner(&self) {
         assert_eq!(
             &env::predecessor_account_id(),
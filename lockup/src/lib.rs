@@ -1,4 +1,5 @@
 //! A smart contract that allows tokens to be locked up.
+use unc_sdk::store::IterableSet;
 use unc_sdk::{env, ext_contract, unc, AccountId, UncToken, Gas};
 
 pub use crate::types::*;
@@ -11,13 +12,14 @@ pub mod types;
 
 pub mod getters;
 pub mod internal;
+pub mod migrate;
 pub mod owner;
 
 /// Indicates there are no deposit for a cross contract call for better readability.
 const NO_DEPOSIT: UncToken = UncToken::from_attounc(0);
 
-/// The contract keeps at least 3.5 UNC in the account to avoid being transferred out to cover
-/// contract code storage and some internal state.
+/// By default the contract keeps at least 3.5 UNC in the account to avoid being transferred out
+/// to cover contract code storage and some internal state.
 pub const MIN_BALANCE_FOR_STORAGE: u128 = 3_500_000_000_000_000_000_000_000;
 
 #[ext_contract(ext_staking_pool)]
@@ -59,6 +61,12 @@ pub trait ExtLockupContractOwner {
         staking_pool_account_id: AccountId,
     ) -> bool;
 
+    fn on_whitelist_is_whitelisted_to_stake(
+        &mut self,
+        #[callback] is_whitelisted: bool,
+        staking_pool_account_id: AccountId,
+    );
+
     fn on_staking_pool_deposit(&mut self, amount: WrappedBalance) -> bool;
 
     fn on_staking_pool_deposit_and_stake(&mut self, amount: WrappedBalance) -> bool;
@@ -75,10 +83,14 @@ pub trait ExtLockupContractOwner {
 
     fn on_get_account_total_balance(&mut self, #[callback] total_balance: WrappedBalance);
 
+    fn on_get_account_total_balance_to_unselect(&mut self) -> bool;
+
     fn on_get_account_unstaked_balance_to_withdraw_by_owner(
         &mut self,
         #[callback] unstaked_balance: WrappedBalance,
     );
+
+    fn on_get_account_staked_balance_to_exit(&mut self) -> bool;
 }
 
 #[ext_contract(ext_self_foundation)]
@@ -125,6 +137,32 @@ pub struct LockupContract {
 
     /// The account ID that the UNC Foundation, that has the ability to terminate vesting.
     pub foundation_account_id: Option<AccountId>,
+
+    /// The amount of tokens kept in the account to cover contract code storage and internal state.
+    /// These tokens are excluded from the liquid balance.
+    pub storage_reserve: u128,
+
+    /// Additional accounts approved by the UNC Foundation to call foundation's methods.
+    pub foundation_signers: IterableSet<AccountId>,
+
+    /// The amount of gas attached to the staking pool `unstake` call during the termination.
+    /// Defaults to `gas::staking_pool::UNSTAKE`.
+    pub termination_unstake_gas: Gas,
+
+    /// The amount of gas attached to the staking pool `withdraw` call during the termination.
+    /// Defaults to `gas::staking_pool::WITHDRAW`.
+    pub termination_withdraw_gas: Gas,
+
+    /// The new owner account ID proposed by the current owner.
+    /// The change takes effect once approved by the UNC Foundation.
+    pub proposed_owner_account_id: Option<AccountId>,
+
+    /// The vesting information before the termination in progress, kept to cancel the termination.
+    pub terminated_vesting_information: Option<VestingInformation>,
+
+    /// The additional vesting grants to the owner added by the UNC Foundation. The granted tokens
+    /// are part of the lockup amount, but each grant vests on its own schedule.
+    pub vesting_grants: Vec<VestingGrant>,
 }
 
 impl Default for LockupContract {
@@ -163,7 +201,13 @@ impl LockupContract {
     ///    transfer is subject to the minimum between vested tokens and released tokens.
     /// - `staking_pool_whitelist_account_id` - the Account ID of the staking pool whitelist contract.
     /// - `foundation_account_id` - the account ID of the UNC Foundation, that has the ability to
-    ///    terminate vesting schedule.
+    ///    terminate vesting schedule. It's required for the vesting schedule, but can also be
+    ///    added without it, e.g. to add the vesting grants later.
+    /// - `storage_reserve` - the optional amount of tokens kept in the account to cover the
+    ///    storage. Defaults to `MIN_BALANCE_FOR_STORAGE`.
+    /// - `staking_pool_account_id` - the optional account ID of the staking pool to select right
+    ///    away. Once the staking pool passes the whitelist check, the account balance is deposited
+    ///    and staked to it. If the check or the staking fails, the lockup stays without staking.
     #[init]
     pub fn new(
         owner_account_id: AccountId,
@@ -174,6 +218,8 @@ impl LockupContract {
         release_duration: Option<WrappedDuration>,
         staking_pool_whitelist_account_id: AccountId,
         foundation_account_id: Option<AccountId>,
+        storage_reserve: Option<WrappedBalance>,
+        staking_pool_account_id: Option<AccountId>,
     ) -> Self {
         assert!(
             env::is_valid_account_id(owner_account_id.as_bytes()),
@@ -201,17 +247,22 @@ impl LockupContract {
             transfers_information,
         };
         let vesting_information = match vesting_schedule {
-            None => {
-                assert!(
-                    foundation_account_id.is_none(),
-                    "Foundation account can't be added without vesting schedule"
-                );
-                VestingInformation::None
-            }
+            None => VestingInformation::None,
             Some(VestingScheduleOrHash::VestingHash(hash)) => VestingInformation::VestingHash(hash),
             Some(VestingScheduleOrHash::VestingSchedule(vs)) => {
                 VestingInformation::VestingSchedule(vs)
             }
+            Some(VestingScheduleOrHash::VestingTranches(tranches)) => {
+                assert_valid_vesting_tranches(&tranches);
+                let total_amount = tranches
+                    .iter()
+                    .fold(0u128, |total, (_, amount)| total.saturating_add(amount.0));
+                assert!(
+                    total_amount <= lockup_information.lockup_amount,
+                    "The vesting tranches can't exceed the lockup amount"
+                );
+                VestingInformation::VestingTranches(tranches)
+            }
         };
         assert!(
             vesting_information == VestingInformation::None ||
@@ -219,6 +270,28 @@ impl LockupContract {
             "Foundation account should be added for vesting schedule"
         );
 
+        if let Some(staking_pool_account_id) = staking_pool_account_id {
+            env::log_str(
+                format!(
+                    "Selecting staking pool @{} to stake on init. Going to check whitelist first.",
+                    staking_pool_account_id
+                )
+                .as_str(),
+            );
+            ext_whitelist::ext(staking_pool_whitelist_account_id.clone())
+                .with_static_gas(Gas::from_gas(gas::whitelist::IS_WHITELISTED))
+                .with_attached_deposit(NO_DEPOSIT)
+                .is_whitelisted(staking_pool_account_id.clone())
+                .then(
+                    ext_self_owner::ext(env::current_account_id())
+                        .with_static_gas(Gas::from_gas(
+                            gas::owner_callbacks::ON_WHITELIST_IS_WHITELISTED_TO_STAKE,
+                        ))
+                        .with_attached_deposit(NO_DEPOSIT)
+                        .on_whitelist_is_whitelisted_to_stake(staking_pool_account_id),
+                );
+        }
+
         Self {
             owner_account_id,
             lockup_information,
@@ -226,6 +299,13 @@ impl LockupContract {
             staking_information: None,
             staking_pool_whitelist_account_id,
             foundation_account_id,
+            storage_reserve: storage_reserve.map(|b| b.0).unwrap_or(MIN_BALANCE_FOR_STORAGE),
+            foundation_signers: IterableSet::new(b"s".to_vec()),
+            termination_unstake_gas: Gas::from_gas(gas::staking_pool::UNSTAKE),
+            termination_withdraw_gas: Gas::from_gas(gas::staking_pool::WITHDRAW),
+            proposed_owner_account_id: None,
+            terminated_vesting_information: None,
+            vesting_grants: Vec::new(),
         }
     }
 }
@@ -236,7 +316,8 @@ mod tests {
     use std::convert::TryInto;
 
     use unc_sdk::{test_vm_config, testing_env, PromiseResult, RuntimeFeesConfig, VMContext};
-    use unc_sdk::test_utils::VMContextBuilder;
+    use unc_sdk::mock::MockAction;
+    use unc_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder};
 
     use test_utils::*;
 
@@ -314,6 +395,8 @@ mod tests {
             release_duration,
            "whitelist".parse().unwrap(),
             foundation_account_id,
+            None,
+            None,
         )
     }
 
@@ -364,6 +447,308 @@ mod tests {
         assert_almost_eq(contract.get_owners_balance().0, to_atto(LOCKUP_UNC));
     }
 
+    #[test]
+    fn test_staking_information_getters() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+
+        // No staking pool is selected.
+        assert_eq!(contract.get_staking_pool_account_id(), None);
+        assert_eq!(contract.get_known_deposited_balance().0, 0);
+
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        contract.staking_information = Some(StakingInformation {
+            staking_pool_account_id: staking_pool.clone(),
+            status: TransactionStatus::Idle,
+            deposit_amount: to_atto(100).into(),
+        });
+        assert_eq!(contract.get_staking_pool_account_id(), Some(staking_pool));
+        assert_eq!(contract.get_known_deposited_balance().0, to_atto(100));
+    }
+
+    #[test]
+    fn test_refresh_staking_pool_balance_recognizes_rewards() {
+        let (mut context, mut contract) = lockup_only_setup();
+        contract.staking_information = Some(StakingInformation {
+            staking_pool_account_id: "staking_pool".parse().unwrap(),
+            status: TransactionStatus::Idle,
+            deposit_amount: to_atto(100).into(),
+        });
+
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.refresh_staking_pool_balance();
+        assert!(
+            contract.staking_information.as_ref().unwrap().status == TransactionStatus::Busy
+        );
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_get_account_total_balance(to_atto(103).into());
+        assert_eq!(contract.get_known_deposited_balance().0, to_atto(103));
+        assert!(
+            contract.staking_information.as_ref().unwrap().status == TransactionStatus::Idle
+        );
+        assert!(get_logs().contains(&format!("Recognized staking rewards of {}", to_atto(3))));
+    }
+
+    #[test]
+    #[should_panic(expected = "Staking pool is not selected")]
+    fn test_refresh_staking_pool_balance_without_staking_pool() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.refresh_staking_pool_balance();
+    }
+
+    #[test]
+    fn test_get_lockup_info() {
+        let mut context = basic_context();
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
+
+        let info = contract.get_lockup_info();
+        assert_eq!(info.owner_account_id, account_owner());
+        assert_eq!(info.lockup_timestamp, None);
+        assert_eq!(info.lockup_duration.0, to_nanos(YEAR));
+        assert_eq!(info.release_duration, None);
+        assert_eq!(
+            info.transfers_information,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            }
+        );
+        assert_eq!(
+            info.vesting_information,
+            VestingInformation::VestingHash(
+                VestingScheduleWithSalt { vesting_schedule, salt: SALT.to_vec().into() }
+                    .hash()
+                    .into()
+            )
+        );
+        assert_eq!(info.termination_status, None);
+        assert_eq!(info.staking_pool_account_id, None);
+        assert_eq!(info.known_deposited_balance.0, 0);
+        assert_eq!(info.locked_amount.0, to_atto(LOCKUP_UNC));
+        assert_eq!(info.liquid_owners_balance.0, 0);
+        assert_eq!(info.terminated_unvested_balance.0, 0);
+    }
+
+    #[test]
+    fn test_get_balance_breakdown() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+
+        // Everything is locked at genesis.
+        let breakdown = contract.get_balance_breakdown();
+        assert_eq!(breakdown.account_balance.0, to_atto(LOCKUP_UNC));
+        assert_eq!(breakdown.total_balance.0, to_atto(LOCKUP_UNC));
+        assert_eq!(breakdown.locked_amount.0, to_atto(LOCKUP_UNC));
+        assert_eq!(breakdown.unvested_amount.0, 0);
+        assert_eq!(breakdown.owners_balance.0, 0);
+        assert_eq!(breakdown.liquid_owners_balance.0, 0);
+        assert_eq!(breakdown.storage_reserve.0, MIN_BALANCE_FOR_STORAGE);
+
+        // After the lockup with some tokens deposited to the staking pool.
+        contract.staking_information = Some(StakingInformation {
+            staking_pool_account_id: "staking_pool".parse().unwrap(),
+            status: TransactionStatus::Idle,
+            deposit_amount: to_atto(100).into(),
+        });
+        context.account_balance = UncToken::from_attounc(to_atto(LOCKUP_UNC - 100));
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
+        testing_env!(context.clone());
+
+        let breakdown = contract.get_balance_breakdown();
+        assert_eq!(breakdown.account_balance.0, to_atto(LOCKUP_UNC - 100));
+        assert_eq!(breakdown.total_balance.0, to_atto(LOCKUP_UNC));
+        assert_eq!(breakdown.locked_amount.0, 0);
+        assert_eq!(breakdown.known_deposited_balance.0, to_atto(100));
+        assert_eq!(
+            breakdown.locked_amount.0
+                + breakdown.liquid_owners_balance.0
+                + breakdown.known_deposited_balance.0
+                + breakdown.storage_reserve.0,
+            breakdown.total_balance.0
+        );
+    }
+
+    #[test]
+    fn test_get_balance_breakdown_during_termination() {
+        let mut context = basic_context();
+        let contract = terminating_contract_with_staking_pool(&mut context);
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+
+        let breakdown = contract.get_balance_breakdown();
+        assert_eq!(breakdown.unvested_amount.0, to_atto(250));
+        assert_eq!(breakdown.terminated_unvested_balance.0, to_atto(250));
+        assert_eq!(breakdown.termination_withdrawn_tokens.0, 0);
+        assert_eq!(
+            breakdown.locked_amount.0 + breakdown.owners_balance.0,
+            breakdown.total_balance.0
+        );
+    }
+
+    #[test]
+    fn test_migrate_terminating() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = new_contract(true, Some(new_vesting_schedule(0)), None, true);
+        let termination_information = TerminationInformation {
+            unvested_amount: to_atto(750).into(),
+            status: TerminationStatus::EverythingUnstaked,
+        };
+        contract.vesting_information =
+            VestingInformation::Terminating(termination_information.clone());
+        contract.staking_information = Some(StakingInformation {
+            staking_pool_account_id: "staking_pool".parse().unwrap(),
+            status: TransactionStatus::Idle,
+            deposit_amount: to_atto(100).into(),
+        });
+
+        env::state_write(&migrate::OldLockupContract {
+            owner_account_id: contract.owner_account_id,
+            lockup_information: contract.lockup_information,
+            vesting_information: contract.vesting_information,
+            staking_pool_whitelist_account_id: contract.staking_pool_whitelist_account_id,
+            staking_information: contract.staking_information,
+            foundation_account_id: contract.foundation_account_id,
+        });
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        let migrated = LockupContract::migrate();
+        assert_eq!(migrated.get_owner_account_id(), account_owner());
+        assert_eq!(migrated.lockup_information.lockup_amount, to_atto(LOCKUP_UNC));
+        assert_eq!(
+            migrated.vesting_information,
+            VestingInformation::Terminating(termination_information)
+        );
+        assert_eq!(migrated.get_terminated_unvested_balance().0, to_atto(750));
+        assert_eq!(
+            migrated.get_termination_status(),
+            Some(TerminationStatus::EverythingUnstaked)
+        );
+        assert_eq!(
+            migrated.get_staking_pool_account_id(),
+            Some("staking_pool".parse().unwrap())
+        );
+        assert_eq!(migrated.get_known_deposited_balance().0, to_atto(100));
+        assert_eq!(migrated.foundation_account_id, Some(account_foundation()));
+        assert_eq!(migrated.get_storage_reserve().0, MIN_BALANCE_FOR_STORAGE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Method is private")]
+    fn test_migrate_not_self() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        new_contract(true, None, None, false);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+
+        LockupContract::migrate();
+    }
+
+    fn assert_upgrade_receipts(code: &[u8]) {
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, env::current_account_id());
+        match &receipts[0].actions[..] {
+            [MockAction::DeployContract { code: deployed_code, .. }, MockAction::FunctionCallWeight { method_name, .. }] =>
+            {
+                assert_eq!(deployed_code, code);
+                assert_eq!(method_name, b"migrate");
+            }
+            actions => panic!("Unexpected actions {:?}", actions),
+        }
+    }
+
+    #[test]
+    fn test_upgrade_by_factory() {
+        let mut context = basic_context();
+        context.current_account_id = "lockup1.factory".parse().unwrap();
+        testing_env!(context.clone());
+        let mut contract = new_contract(true, None, None, false);
+
+        let code = vec![0, 97, 115, 109];
+        context.predecessor_account_id = "factory".parse().unwrap();
+        testing_env!(context.clone());
+        contract.upgrade(code.clone());
+        assert_upgrade_receipts(&code);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the lockup factory")]
+    fn test_upgrade_by_owner() {
+        let mut context = basic_context();
+        context.current_account_id = "lockup1.factory".parse().unwrap();
+        testing_env!(context.clone());
+        let mut contract = new_contract(true, None, None, false);
+
+        // The owner alone can't replace the code holding the locked tokens.
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.upgrade(vec![0, 97, 115, 109]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the lockup factory")]
+    fn test_upgrade_by_foundation() {
+        let mut context = basic_context();
+        context.current_account_id = "lockup1.factory".parse().unwrap();
+        testing_env!(context.clone());
+        let mut contract = new_contract(true, None, None, false);
+
+        context.predecessor_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.upgrade(vec![0, 97, 115, 109]);
+    }
+
+    #[test]
+    fn test_storage_reserve() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let default_contract = new_contract(true, None, None, false);
+        let storage_reserve = MIN_BALANCE_FOR_STORAGE + to_atto(10);
+        let contract = LockupContract::new(
+            account_owner(),
+            to_nanos(YEAR).into(),
+            None,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            },
+            None,
+            None,
+            "whitelist".parse().unwrap(),
+            None,
+            Some(storage_reserve.into()),
+            None,
+        );
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(default_contract.get_storage_reserve().0, MIN_BALANCE_FOR_STORAGE);
+        assert_eq!(contract.get_storage_reserve().0, storage_reserve);
+        // Raising the reserve by 10 tokens reduces the liquid balance by the same amount.
+        assert_eq!(
+            contract.get_liquid_owners_balance().0,
+            default_contract.get_liquid_owners_balance().0 - to_atto(10)
+        );
+    }
+
     #[test]
     fn test_add_full_access_key() {
         let (mut context, mut contract) = lockup_only_setup();
@@ -441,396 +826,2207 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Expected vesting schedule and salt, but it was not provided")]
-    fn test_vesting_schedule_and_salt_not_provided() {
+    fn test_terminate_vesting_with_hash_reveal() {
         let mut context = basic_context();
         testing_env!(context.clone());
-        let vesting_schedule = new_vesting_schedule(5);
-        let mut contract = new_contract(true, Some(vesting_schedule), None, true);
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
         context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
         context.predecessor_account_id = account_foundation();
-        context.signer_account_id = non_owner();
+        context.signer_account_id = account_foundation();
         testing_env!(context.clone());
 
-        contract.terminate_vesting(None);
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: SALT.to_vec().into(),
+        }));
+        assert_eq!(
+            contract.get_termination_status(),
+            Some(TerminationStatus::ReadyToWithdraw)
+        );
+        // Half of the vesting period has passed, so half of the tokens are unvested.
+        assert_almost_eq(
+            contract.get_terminated_unvested_balance().0,
+            to_atto(LOCKUP_UNC / 2),
+        );
     }
 
     #[test]
-    #[should_panic(expected = "Explicit vesting schedule exists")]
-    fn test_explicit_vesting() {
+    fn test_is_terminable() {
         let mut context = basic_context();
         testing_env!(context.clone());
-        let vesting_schedule = new_vesting_schedule(5);
-        let mut contract = LockupContract::new(
-            account_owner(),
-            to_nanos(YEAR).into(),
-            None,
-            TransfersInformation::TransfersEnabled {
-                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
-            },
-            Some(VestingScheduleOrHash::VestingSchedule(
-                vesting_schedule.clone(),
-            )),
-            None,
-           "whitelist".parse().unwrap(),
-            Some(account_foundation()),
-        );
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        // The hashed vesting schedule can't be checked.
+        assert!(contract.is_terminable());
+
+        contract.vesting_information = VestingInformation::VestingSchedule(vesting_schedule);
+        assert!(contract.is_terminable());
+
+        // Everything is vested after the end of the vesting schedule.
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR * 3);
+        testing_env!(context.clone());
+        assert!(!contract.is_terminable());
+    }
+
+    #[test]
+    fn test_is_terminable_when_terminating() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
         context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
         context.predecessor_account_id = account_foundation();
-        context.signer_account_id = non_owner();
         testing_env!(context.clone());
 
         contract.terminate_vesting(Some(VestingScheduleWithSalt {
             vesting_schedule,
             salt: SALT.to_vec().into(),
         }));
+        assert!(!contract.is_terminable());
     }
 
     #[test]
-    #[should_panic(expected = "Foundation account can't be added without vesting schedule")]
-    fn test_init_foundation_key_no_vesting() {
+    fn test_is_terminable_without_vesting() {
         let context = basic_context();
         testing_env!(context.clone());
-        new_contract(true, None, None, true);
-    }
+        let contract = new_contract(true, None, None, false);
+        assert!(!contract.is_terminable());
 
-    #[test]
-    #[should_panic(expected = "Foundation account can't be added without vesting schedule")]
-    fn test_init_foundation_key_no_vesting_with_release() {
-        let context = basic_context();
-        testing_env!(context.clone());
-        new_contract(true, None, Some(to_nanos(YEAR).into()), true);
+        // Without the foundation account the vesting can't be terminated at all.
+        let mut contract = new_contract(true, Some(new_vesting_schedule(0)), None, true);
+        contract.foundation_account_id = None;
+        assert!(!contract.is_terminable());
     }
 
     #[test]
-    #[should_panic(expected = "Can only be called by UNC Foundation")]
-    fn test_call_by_non_foundation() {
+    #[should_panic(expected = "Presented vesting schedule and salt don't match the hash")]
+    fn test_terminate_vesting_with_wrong_salt() {
         let mut context = basic_context();
         testing_env!(context.clone());
         let vesting_schedule = new_vesting_schedule(0);
         let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
         context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
-        context.predecessor_account_id = non_owner();
-        context.signer_account_id = non_owner();
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
         testing_env!(context.clone());
 
-        contract.terminate_vesting(None);
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: b"wrong_salt".to_vec().into(),
+        }));
     }
 
     #[test]
-    #[should_panic(expected = "Transfers are disabled")]
-    fn test_transfers_not_enabled() {
+    #[should_panic(expected = "Expected vesting schedule and salt, but it was not provided")]
+    fn test_vesting_schedule_and_salt_not_provided() {
         let mut context = basic_context();
         testing_env!(context.clone());
-        let mut contract = new_contract(false, None, None, false);
-        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(1).try_into().unwrap();
-        context.view_config = None;
+        let vesting_schedule = new_vesting_schedule(5);
+        let mut contract = new_contract(true, Some(vesting_schedule), None, true);
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = non_owner();
         testing_env!(context.clone());
 
-        contract.transfer(to_atto(100).into(), non_owner());
+        contract.terminate_vesting(None);
     }
 
     #[test]
-    fn test_enable_transfers() {
+    #[should_panic(expected = "Explicit vesting schedule exists")]
+    fn test_explicit_vesting() {
         let mut context = basic_context();
         testing_env!(context.clone());
-        let mut contract = new_contract(false, None, None, false);
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
-        testing_env!(context.clone());
-        assert!(!contract.are_transfers_enabled());
-
-        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(1).try_into().unwrap();
-        context.view_config = None;
+        let vesting_schedule = new_vesting_schedule(5);
+        let mut contract = LockupContract::new(
+            account_owner(),
+            to_nanos(YEAR).into(),
+            None,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            },
+            Some(VestingScheduleOrHash::VestingSchedule(
+                vesting_schedule.clone(),
+            )),
+            None,
+           "whitelist".parse().unwrap(),
+            Some(account_foundation()),
+            None,
+            None,
+        );
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = non_owner();
         testing_env!(context.clone());
 
-        contract.check_transfers_vote();
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: SALT.to_vec().into(),
+        }));
+    }
 
-        let poll_result = Some(to_ts(GENESIS_TIME_IN_DAYS + 10).into());
-        context.predecessor_account_id = lockup_account();
-        // NOTE: Unit tests don't need to read the content of the promise result. So here we don't
-        // have to pass serialized result from the transfer poll.
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(vec![])],
-        );
-        assert!(contract.on_get_result_from_transfer_poll(poll_result));
+    fn new_contract_with_vesting_tranches(tranches: Vec<VestingTranche>) -> LockupContract {
+        LockupContract::new(
+            account_owner(),
+            0.into(),
+            None,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            },
+            Some(VestingScheduleOrHash::VestingTranches(tranches)),
+            None,
+            "whitelist".parse().unwrap(),
+            Some(account_foundation()),
+            None,
+            None,
+        )
+    }
 
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
-        testing_env!(context.clone());
-        // Not unlocked yet
-        assert_eq!(contract.get_owners_balance().0, 0);
-        assert!(contract.are_transfers_enabled());
-        assert_eq!(contract.get_vesting_information(), VestingInformation::None);
+    /// 25% of the lockup amount vests at the end of each of the 4 years after genesis.
+    fn yearly_vesting_tranches() -> Vec<VestingTranche> {
+        (1..=4)
+            .map(|year| (to_ts(GENESIS_TIME_IN_DAYS + YEAR * year).into(), to_atto(LOCKUP_UNC / 4).into()))
+            .collect()
+    }
 
-        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 10);
+    #[test]
+    fn test_vesting_tranches() {
+        let mut context = basic_context();
         testing_env!(context.clone());
-        // Unlocked yet
-        assert_eq!(
-            contract.get_owners_balance().0,
-            to_atto(LOCKUP_UNC).into()
-        );
+        let contract = new_contract_with_vesting_tranches(yearly_vesting_tranches());
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
 
-        context.view_config = None;
-        context.predecessor_account_id = account_owner();
-        testing_env!(context.clone());
-        contract.transfer(to_atto(100).into(), non_owner());
+        for (day, unvested_unc) in [
+            (GENESIS_TIME_IN_DAYS, 1000),
+            (GENESIS_TIME_IN_DAYS + YEAR - 1, 1000),
+            (GENESIS_TIME_IN_DAYS + YEAR, 750),
+            (GENESIS_TIME_IN_DAYS + YEAR * 2 + 1, 500),
+            (GENESIS_TIME_IN_DAYS + YEAR * 4 - 1, 250),
+            (GENESIS_TIME_IN_DAYS + YEAR * 4, 0),
+        ] {
+            context.block_timestamp = to_ts(day);
+            testing_env!(context.clone());
+            assert_eq!(
+                contract.get_unvested_amount(no_vesting_schedule()).0,
+                to_atto(unvested_unc)
+            );
+            assert_eq!(contract.get_locked_amount().0, to_atto(unvested_unc));
+            assert_eq!(
+                contract.get_owners_balance().0,
+                to_atto(LOCKUP_UNC - unvested_unc)
+            );
+            assert_eq!(contract.is_terminable(), unvested_unc > 0);
+        }
     }
 
     #[test]
-    fn test_check_transfers_vote_false() {
+    fn test_seconds_until_next_unlock_linear_release() {
+        const SECONDS_IN_DAY: u64 = 86400;
         let mut context = basic_context();
         testing_env!(context.clone());
-        let mut contract = new_contract(false, None, None, false);
+        let contract = new_contract(true, None, Some(to_nanos(YEAR * 4).into()), false);
         context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+
+        for (day, seconds_until_next_unlock) in [
+            // Until the end of the lockup, when the release starts.
+            (GENESIS_TIME_IN_DAYS, YEAR * SECONDS_IN_DAY),
+            (GENESIS_TIME_IN_DAYS + YEAR / 2, (YEAR - YEAR / 2) * SECONDS_IN_DAY),
+            // Until the end of the linear release.
+            (GENESIS_TIME_IN_DAYS + YEAR, YEAR * 4 * SECONDS_IN_DAY),
+            (GENESIS_TIME_IN_DAYS + YEAR * 3, YEAR * 2 * SECONDS_IN_DAY),
+            (GENESIS_TIME_IN_DAYS + YEAR * 5 - 1, SECONDS_IN_DAY),
+            // Fully unlocked.
+            (GENESIS_TIME_IN_DAYS + YEAR * 5, 0),
+        ] {
+            context.block_timestamp = to_ts(day);
+            testing_env!(context.clone());
+            assert_eq!(contract.get_seconds_until_next_unlock(), seconds_until_next_unlock);
+        }
+
+        // Rounded up to the whole second.
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR * 5) - 1;
         testing_env!(context.clone());
-        assert!(!contract.are_transfers_enabled());
+        assert_eq!(contract.get_seconds_until_next_unlock(), 1);
+    }
 
-        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(1).try_into().unwrap();
-        context.view_config = None;
+    #[test]
+    fn test_seconds_until_next_unlock_vesting() {
+        const SECONDS_IN_DAY: u64 = 86400;
+        let mut context = basic_context();
         testing_env!(context.clone());
+        let contract = new_contract_with_vesting_tranches(yearly_vesting_tranches());
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
 
-        contract.check_transfers_vote();
+        for (day, seconds_until_next_unlock) in [
+            (GENESIS_TIME_IN_DAYS, YEAR * SECONDS_IN_DAY),
+            (GENESIS_TIME_IN_DAYS + YEAR, YEAR * SECONDS_IN_DAY),
+            (GENESIS_TIME_IN_DAYS + YEAR * 4 - 1, SECONDS_IN_DAY),
+            (GENESIS_TIME_IN_DAYS + YEAR * 4, 0),
+        ] {
+            context.block_timestamp = to_ts(day);
+            testing_env!(context.clone());
+            assert_eq!(contract.get_seconds_until_next_unlock(), seconds_until_next_unlock);
+        }
 
-        let poll_result = None;
-        // NOTE: Unit tests don't need to read the content of the promise result. So here we don't
-        // have to pass serialized result from the transfer poll.
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(vec![])],
+        // The end of the lockup is skipped, because the tokens are still unvested until the cliff.
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS);
+        testing_env!(context.clone());
+        let contract = LockupContract::new(
+            account_owner(),
+            to_nanos(YEAR).into(),
+            None,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            },
+            Some(VestingScheduleOrHash::VestingSchedule(new_vesting_schedule(YEAR * 2))),
+            None,
+            "whitelist".parse().unwrap(),
+            Some(account_foundation()),
+            None,
+            None,
         );
-        assert!(!contract.on_get_result_from_transfer_poll(poll_result));
+        assert_eq!(contract.get_seconds_until_next_unlock(), YEAR * 2 * SECONDS_IN_DAY);
+    }
 
+    #[test]
+    fn test_seconds_until_next_unlock_transfers_disabled() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let contract = new_contract(false, None, None, false);
         context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
         testing_env!(context.clone());
-        // Not enabled
-        assert!(!contract.are_transfers_enabled());
+        assert_eq!(contract.get_seconds_until_next_unlock(), 0);
     }
 
     #[test]
-    fn test_lockup_only_transfer_call_by_owner() {
-        let (mut context, mut contract) = lockup_only_setup();
-        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+    fn test_terminate_vesting_tranches() {
+        let mut context = basic_context();
         testing_env!(context.clone());
-        assert_almost_eq(contract.get_owners_balance().0, to_atto(LOCKUP_UNC));
-
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(1).try_into().unwrap();
-        context.view_config = None;
+        let mut contract = new_contract_with_vesting_tranches(yearly_vesting_tranches());
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR * 2);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = non_owner();
         testing_env!(context.clone());
 
-        assert_eq!(env::account_balance(), UncToken::from_attounc(to_atto(LOCKUP_UNC)));
-        contract.transfer(to_atto(100).into(), non_owner());
-        assert_almost_eq(env::account_balance().as_attounc(), to_atto(LOCKUP_UNC - 100));
+        contract.terminate_vesting(None);
+        assert_eq!(contract.get_terminated_unvested_balance().0, to_atto(500));
+        assert_eq!(contract.get_locked_amount().0, to_atto(500));
     }
 
     #[test]
-    #[should_panic(expected = "Staking pool is not selected")]
-    fn test_staking_pool_is_not_selected() {
-        let (mut context, mut contract) = lockup_only_setup();
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(2).try_into().unwrap();
-
-        let amount = to_atto(LOCKUP_UNC - 100);
+    #[should_panic(expected = "The vesting tranche timestamps should be increasing")]
+    fn test_vesting_tranches_not_ordered() {
+        let context = basic_context();
         testing_env!(context.clone());
-        contract.deposit_to_staking_pool(amount.into());
+        let mut tranches = yearly_vesting_tranches();
+        tranches.swap(0, 1);
+        new_contract_with_vesting_tranches(tranches);
     }
 
     #[test]
-    fn test_staking_pool_success() {
-        let (mut context, mut contract) = lockup_only_setup();
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(2).try_into().unwrap();
+    #[should_panic(expected = "The vesting tranches can't exceed the lockup amount")]
+    fn test_vesting_tranches_exceed_lockup_amount() {
+        let context = basic_context();
+        testing_env!(context.clone());
+        let mut tranches = yearly_vesting_tranches();
+        tranches.push((to_ts(GENESIS_TIME_IN_DAYS + YEAR * 5).into(), 1.into()));
+        new_contract_with_vesting_tranches(tranches);
+    }
 
-        // Selecting staking pool
-        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+    #[test]
+    fn test_init_foundation_key_no_vesting() {
+        let mut context = basic_context();
         testing_env!(context.clone());
-        contract.select_staking_pool(staking_pool.clone());
+        let mut contract = new_contract(true, None, None, true);
+        assert_eq!(contract.foundation_account_id, Some(account_foundation()));
+        assert_eq!(contract.get_vesting_information(), VestingInformation::None);
+        assert!(!contract.is_terminable());
 
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(b"true".to_vec())],
+        context.predecessor_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.add_foundation_signer("foundation-ops".parse().unwrap());
+        assert_eq!(
+            contract.get_foundation_signers(),
+            vec!["foundation-ops".parse::<AccountId>().unwrap()]
         );
-        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+    }
 
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+    #[test]
+    #[should_panic(expected = "The account is fully vested")]
+    fn test_init_foundation_key_no_vesting_with_release() {
+        let mut context = basic_context();
         testing_env!(context.clone());
-        assert_eq!(contract.get_staking_pool_account_id(), Some(staking_pool));
-        assert_eq!(contract.get_known_deposited_balance().0, 0);
-        context.view_config = None;
+        let mut contract = new_contract(true, None, Some(to_nanos(YEAR).into()), true);
+        assert_eq!(contract.foundation_account_id, Some(account_foundation()));
 
-        // Deposit to the staking_pool
-        let amount = to_atto(LOCKUP_UNC - 100);
-        context.predecessor_account_id = account_owner();
+        context.predecessor_account_id = account_foundation();
         testing_env!(context.clone());
-        contract.deposit_to_staking_pool(amount.into());
-        context.account_balance = env::account_balance();
-        assert_eq!(context.account_balance, UncToken::from_attounc(to_atto(LOCKUP_UNC) - amount));
+        contract.terminate_vesting(None);
+    }
 
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(vec![])],
-        );
-        contract.on_staking_pool_deposit(amount.into());
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_call_by_non_foundation() {
+        let mut context = basic_context();
         testing_env!(context.clone());
-        assert_eq!(contract.get_known_deposited_balance().0, amount);
-        context.view_config = None;
-
-        // Staking on the staking pool
-        context.predecessor_account_id = account_owner();
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.predecessor_account_id = non_owner();
+        context.signer_account_id = non_owner();
         testing_env!(context.clone());
-        contract.stake(amount.into());
 
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
+        contract.terminate_vesting(None);
+    }
+
+    #[test]
+    fn test_set_foundation_account_id() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+
+        contract.set_foundation_account_id(non_owner());
+        assert_eq!(contract.foundation_account_id, Some(non_owner()));
+
+        // The new foundation account can terminate the vesting.
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.predecessor_account_id = non_owner();
+        context.signer_account_id = non_owner();
+        testing_env!(context.clone());
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: SALT.to_vec().into(),
+        }));
+        assert_eq!(
+            contract.get_termination_status(),
+            Some(TerminationStatus::ReadyToWithdraw)
+        );
+    }
+
+    #[test]
+    fn test_set_foundation_account_id_revokes_foundation_signers() {
+        let mut context = basic_context();
+        let mut contract = new_contract_called_by_foundation_signer(&mut context);
+        assert_eq!(contract.get_foundation_signers(), vec![non_owner()]);
+
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+        let new_foundation: AccountId = "new-foundation".parse().unwrap();
+        contract.set_foundation_account_id(new_foundation.clone());
+        assert_eq!(contract.foundation_account_id, Some(new_foundation));
+        assert!(contract.get_foundation_signers().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_previous_foundation_signer_cannot_terminate_vesting() {
+        let mut context = basic_context();
+        let mut contract = new_contract_called_by_foundation_signer(&mut context);
+
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.set_foundation_account_id("new-foundation".parse().unwrap());
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.predecessor_account_id = non_owner();
+        context.signer_account_id = non_owner();
+        testing_env!(context.clone());
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule: new_vesting_schedule(0),
+            salt: SALT.to_vec().into(),
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_set_foundation_account_id_by_non_foundation() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule), None, true);
+        context.predecessor_account_id = non_owner();
+        context.signer_account_id = non_owner();
+        testing_env!(context.clone());
+
+        contract.set_foundation_account_id(non_owner());
+    }
+
+    #[test]
+    #[should_panic(expected = "No UNC Foundation account is specified in the contract")]
+    fn test_set_foundation_account_id_without_foundation() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = new_contract(true, None, None, false);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+
+        contract.set_foundation_account_id(non_owner());
+    }
+
+    #[test]
+    fn test_owner_change() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule), None, true);
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        testing_env!(context.clone());
+
+        contract.propose_owner_change(non_owner());
+        assert_eq!(contract.get_proposed_owner_account_id(), Some(non_owner()));
+        // The proposal alone doesn't change the owner.
+        assert_eq!(contract.get_owner_account_id(), account_owner());
+
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.approve_owner_change(non_owner());
+        assert_eq!(contract.get_owner_account_id(), non_owner());
+        assert_eq!(contract.get_proposed_owner_account_id(), None);
+
+        // The previous owner can't call owner's methods anymore.
+        context.predecessor_account_id = non_owner();
+        context.signer_account_id = non_owner();
+        testing_env!(context.clone());
+        contract.propose_owner_change(account_owner());
+        assert_eq!(contract.get_proposed_owner_account_id(), Some(account_owner()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_propose_owner_change_by_non_owner() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule), None, true);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+
+        contract.propose_owner_change(account_foundation());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_approve_owner_change_by_non_foundation() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule), None, true);
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        testing_env!(context.clone());
+
+        contract.propose_owner_change(non_owner());
+        contract.approve_owner_change(non_owner());
+    }
+
+    #[test]
+    #[should_panic(expected = "There is no proposed owner change")]
+    fn test_approve_owner_change_without_proposal() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule), None, true);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+
+        contract.approve_owner_change(non_owner());
+    }
+
+    #[test]
+    #[should_panic(expected = "The new owner account ID doesn't match the proposed one")]
+    fn test_approve_owner_change_after_swapped_proposal() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule), None, true);
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.propose_owner_change(non_owner());
+
+        // The owner swaps the proposal after the foundation decided on the first one.
+        contract.propose_owner_change("owner2".parse().unwrap());
+
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.approve_owner_change(non_owner());
+    }
+
+    #[test]
+    fn test_foundation_signer_terminates_vesting() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+
+        assert!(contract.add_foundation_signer(non_owner()));
+        assert!(!contract.add_foundation_signer(non_owner()));
+        assert_eq!(contract.get_foundation_signers(), vec![non_owner()]);
+
+        // The approved signer can terminate the vesting.
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.predecessor_account_id = non_owner();
+        context.signer_account_id = non_owner();
+        testing_env!(context.clone());
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: SALT.to_vec().into(),
+        }));
+        assert_eq!(
+            contract.get_termination_status(),
+            Some(TerminationStatus::ReadyToWithdraw)
+        );
+        // The primary foundation account is kept.
+        assert_eq!(contract.foundation_account_id, Some(account_foundation()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_unapproved_account_cannot_terminate_vesting() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.add_foundation_signer("ops".parse().unwrap());
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.predecessor_account_id = non_owner();
+        context.signer_account_id = non_owner();
+        testing_env!(context.clone());
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: SALT.to_vec().into(),
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_removed_foundation_signer_cannot_terminate_vesting() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.add_foundation_signer(non_owner());
+        assert!(contract.remove_foundation_signer(non_owner()));
+        assert!(contract.get_foundation_signers().is_empty());
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.predecessor_account_id = non_owner();
+        context.signer_account_id = non_owner();
+        testing_env!(context.clone());
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: SALT.to_vec().into(),
+        }));
+    }
+
+    fn new_contract_called_by_foundation_signer(context: &mut VMContext) -> LockupContract {
+        testing_env!(context.clone());
+        let mut contract = new_contract(true, Some(new_vesting_schedule(0)), None, true);
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.add_foundation_signer(non_owner());
+
+        context.predecessor_account_id = non_owner();
+        context.signer_account_id = non_owner();
+        testing_env!(context.clone());
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation account")]
+    fn test_foundation_signer_cannot_add_foundation_signer() {
+        let mut context = basic_context();
+        let mut contract = new_contract_called_by_foundation_signer(&mut context);
+        contract.add_foundation_signer("ops".parse().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation account")]
+    fn test_foundation_signer_cannot_remove_foundation_signer() {
+        let mut context = basic_context();
+        let mut contract = new_contract_called_by_foundation_signer(&mut context);
+        contract.remove_foundation_signer(non_owner());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation account")]
+    fn test_foundation_signer_cannot_set_foundation_account_id() {
+        let mut context = basic_context();
+        let mut contract = new_contract_called_by_foundation_signer(&mut context);
+        contract.set_foundation_account_id(non_owner());
+    }
+
+    fn function_call_gas(receipt_index: usize, method: &str) -> u64 {
+        get_created_receipts()[receipt_index]
+            .actions
+            .iter()
+            .find_map(|action| match action {
+                MockAction::FunctionCallWeight { method_name, prepaid_gas, .. }
+                    if method_name == method.as_bytes() =>
+                {
+                    Some(prepaid_gas.as_gas())
+                }
+                _ => None,
+            })
+            .expect("Expected the function call")
+    }
+
+    #[test]
+    fn test_termination_with_configured_unstake_gas() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule), None, true);
+        assert_eq!(contract.get_termination_unstake_gas().0, gas::staking_pool::UNSTAKE);
+
+        let unstake_gas = gas::staking_pool::UNSTAKE * 2;
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.set_termination_unstake_gas(unstake_gas.into());
+        assert_eq!(contract.get_termination_unstake_gas().0, unstake_gas);
+
+        contract.staking_information = Some(StakingInformation {
+            staking_pool_account_id: "staking_pool".parse().unwrap(),
+            status: TransactionStatus::Idle,
+            deposit_amount: to_atto(100).into(),
+        });
+        contract.vesting_information = VestingInformation::Terminating(TerminationInformation {
+            unvested_amount: to_atto(500).into(),
+            status: TerminationStatus::VestingTerminatedWithDeficit,
+        });
+        contract.termination_prepare_to_withdraw();
+        // The callback covers the configured gas of the unstake call.
+        assert_eq!(
+            function_call_gas(1, "on_get_account_staked_balance_to_unstake"),
+            gas::foundation_callbacks::ON_GET_ACCOUNT_STAKED_BALANCE_TO_UNSTAKE
+                + gas::staking_pool::UNSTAKE
+        );
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(format!("\"{}\"", to_atto(100)).into_bytes())],
+        );
+        contract.on_get_account_staked_balance_to_unstake(to_atto(100).into());
+        assert_eq!(function_call_gas(0, "unstake"), unstake_gas);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_set_termination_unstake_gas_by_non_foundation() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = new_contract(true, Some(new_vesting_schedule(0)), None, true);
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        testing_env!(context.clone());
+
+        contract.set_termination_unstake_gas((gas::staking_pool::UNSTAKE * 2).into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_add_foundation_signer_by_non_foundation() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = new_contract(true, Some(new_vesting_schedule(0)), None, true);
+        context.predecessor_account_id = non_owner();
+        context.signer_account_id = non_owner();
+        testing_env!(context.clone());
+
+        contract.add_foundation_signer(non_owner());
+    }
+
+    #[test]
+    #[should_panic(expected = "Transfers are disabled")]
+    fn test_transfers_not_enabled() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = new_contract(false, None, None, false);
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(1).try_into().unwrap();
+        context.view_config = None;
+        testing_env!(context.clone());
+
+        contract.transfer(to_atto(100).into(), non_owner());
+    }
+
+    fn new_contract_with_transfers_timestamp(transfers_timestamp: Timestamp) -> LockupContract {
+        LockupContract::new(
+            account_owner(),
+            0.into(),
+            None,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: transfers_timestamp.into(),
+            },
+            None,
+            None,
+            "whitelist".parse().unwrap(),
+            None,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    #[should_panic(expected = "Transfers are disabled")]
+    fn test_transfer_before_transfers_timestamp() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = new_contract_with_transfers_timestamp(to_ts(GENESIS_TIME_IN_DAYS + YEAR));
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR) - 1;
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        assert!(!contract.are_transfers_enabled());
+
+        contract.transfer(to_atto(100).into(), non_owner());
+    }
+
+    #[test]
+    fn test_transfer_after_transfers_timestamp() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = new_contract_with_transfers_timestamp(to_ts(GENESIS_TIME_IN_DAYS + YEAR));
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        assert!(contract.are_transfers_enabled());
+
+        contract.transfer(to_atto(100).into(), non_owner());
+        assert_eq!(get_created_receipts().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Transfers are disabled")]
+    fn test_withdraw_unaccounted_before_transfers_timestamp() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = new_contract_with_transfers_timestamp(to_ts(GENESIS_TIME_IN_DAYS + YEAR));
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+
+        contract.withdraw_unaccounted(non_owner());
+    }
+
+    #[test]
+    fn test_enable_transfers() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = new_contract(false, None, None, false);
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert!(!contract.are_transfers_enabled());
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(1).try_into().unwrap();
+        context.view_config = None;
+        testing_env!(context.clone());
+
+        contract.check_transfers_vote();
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, "transfers".parse::<AccountId>().unwrap());
+        assert_eq!(receipts[1].receiver_id, lockup_account());
+
+        let poll_result = Some(to_ts(GENESIS_TIME_IN_DAYS + 10).into());
+        context.predecessor_account_id = lockup_account();
+        // NOTE: Unit tests don't need to read the content of the promise result. So here we don't
+        // have to pass serialized result from the transfer poll.
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.on_get_result_from_transfer_poll(poll_result));
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        // Not unlocked yet
+        assert_eq!(contract.get_owners_balance().0, 0);
+        assert!(contract.are_transfers_enabled());
+        assert_eq!(contract.get_vesting_information(), VestingInformation::None);
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 10);
+        testing_env!(context.clone());
+        // Unlocked yet
+        assert_eq!(
+            contract.get_owners_balance().0,
+            to_atto(LOCKUP_UNC).into()
+        );
+
+        context.view_config = None;
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.transfer(to_atto(100).into(), non_owner());
+    }
+
+    #[test]
+    fn test_check_transfers_vote_false() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = new_contract(false, None, None, false);
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert!(!contract.are_transfers_enabled());
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(1).try_into().unwrap();
+        context.view_config = None;
+        testing_env!(context.clone());
+
+        contract.check_transfers_vote();
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, "transfers".parse::<AccountId>().unwrap());
+        assert_eq!(receipts[1].receiver_id, lockup_account());
+
+        let poll_result = None;
+        // NOTE: Unit tests don't need to read the content of the promise result. So here we don't
+        // have to pass serialized result from the transfer poll.
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(!contract.on_get_result_from_transfer_poll(poll_result));
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        // Not enabled
+        assert!(!contract.are_transfers_enabled());
+    }
+
+    #[test]
+    fn test_lockup_only_transfer_call_by_owner() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_almost_eq(contract.get_owners_balance().0, to_atto(LOCKUP_UNC));
+
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(1).try_into().unwrap();
+        context.view_config = None;
+        testing_env!(context.clone());
+
+        assert_eq!(env::account_balance(), UncToken::from_attounc(to_atto(LOCKUP_UNC)));
+        contract.transfer(to_atto(100).into(), non_owner());
+        assert_almost_eq(env::account_balance().as_attounc(), to_atto(LOCKUP_UNC - 100));
+    }
+
+    #[test]
+    #[should_panic(expected = "is smaller than the requested transfer amount")]
+    fn test_lockup_only_transfer_over_liquid_balance() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(1).try_into().unwrap();
+        testing_env!(context.clone());
+
+        contract.transfer(to_atto(LOCKUP_UNC + 1).into(), non_owner());
+    }
+
+    #[test]
+    fn test_withdraw_unaccounted() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_unaccounted_balance().0, 0);
+
+        // Somebody sends 50 tokens to the lockup while everything is still locked.
+        context.account_balance = UncToken::from_attounc(to_atto(LOCKUP_UNC + 50));
+        testing_env!(context.clone());
+        assert_eq!(contract.get_locked_amount().0, to_atto(LOCKUP_UNC));
+        assert_eq!(contract.get_unaccounted_balance().0, to_atto(50));
+
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.view_config = None;
+        testing_env!(context.clone());
+        contract.withdraw_unaccounted(non_owner());
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, non_owner());
+        assert_eq!(
+            env::account_balance(),
+            UncToken::from_attounc(to_atto(LOCKUP_UNC))
+        );
+        assert_eq!(contract.get_unaccounted_balance().0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "There is no unaccounted balance to withdraw")]
+    fn test_withdraw_unaccounted_without_surplus() {
+        let (mut context, mut contract) = lockup_only_setup();
+        // Everything is unlocked, but there are no tokens above the lockup amount.
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        testing_env!(context.clone());
+        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(LOCKUP_UNC) - MIN_BALANCE_FOR_STORAGE);
+
+        contract.withdraw_unaccounted(non_owner());
+    }
+
+    #[test]
+    #[should_panic(expected = "Staking pool is not selected")]
+    fn test_staking_pool_is_not_selected() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2).try_into().unwrap();
+
+        let amount = to_atto(LOCKUP_UNC - 100);
+        testing_env!(context.clone());
+        contract.deposit_to_staking_pool(amount.into());
+    }
+
+    #[test]
+    fn test_staking_pool_success() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2).try_into().unwrap();
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_staking_pool_account_id(), Some(staking_pool));
+        assert_eq!(contract.get_known_deposited_balance().0, 0);
+        context.view_config = None;
+
+        // Deposit to the staking_pool
+        let amount = to_atto(LOCKUP_UNC - 100);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.deposit_to_staking_pool(amount.into());
+        context.account_balance = env::account_balance();
+        assert_eq!(context.account_balance, UncToken::from_attounc(to_atto(LOCKUP_UNC) - amount));
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_staking_pool_deposit(amount.into());
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_known_deposited_balance().0, amount);
+        context.view_config = None;
+
+        // Staking on the staking pool
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.stake(amount.into());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_staking_pool_stake(amount.into());
+
+        // Assuming there are 20 UNC tokens in rewards. Unstaking.
+        let unstake_amount = amount + to_atto(20);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unstake(unstake_amount.into());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_staking_pool_unstake(unstake_amount.into());
+
+        // Withdrawing
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.withdraw_from_staking_pool(unstake_amount.into());
+        context.account_balance = context.account_balance.saturating_add(UncToken::from_attounc(unstake_amount));
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_staking_pool_withdraw(unstake_amount.into());
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_known_deposited_balance().0, 0);
+        context.view_config = None;
+
+        // Unselecting staking pool
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unselect_staking_pool();
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"0\"".to_vec())],
+        );
+        assert!(contract.on_get_account_total_balance_to_unselect());
+        assert_eq!(contract.get_staking_pool_account_id(), None);
+    }
+
+    #[test]
+    fn test_staking_pool_refresh_balance() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2).try_into().unwrap();
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        // Deposit to the staking_pool
+        let amount = to_atto(LOCKUP_UNC - 100);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.deposit_to_staking_pool(amount.into());
+        context.account_balance = env::account_balance();
+        assert_eq!(context.account_balance, UncToken::from_attounc(to_atto(LOCKUP_UNC) - amount));
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_staking_pool_deposit(amount.into());
+
+        // Staking on the staking pool
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.stake(amount.into());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_staking_pool_stake(amount.into());
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_owners_balance().0, 0);
+        assert_eq!(contract.get_liquid_owners_balance().0, 0);
+        assert_eq!(contract.get_known_deposited_balance().0, amount);
+        context.view_config = None;
+
+        // Assuming there are 20 UNC tokens in rewards. Refreshing balance.
+        let total_balance = amount + to_atto(20);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.refresh_staking_pool_balance();
+
+        // In unit tests, the following call ignores the promise value, because it's passed directly.
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_get_account_total_balance(total_balance.into());
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_known_deposited_balance().0, total_balance);
+        assert_eq!(contract.get_owners_balance().0, to_atto(20));
+        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(20));
+        context.view_config = None;
+
+        // Withdrawing these tokens
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        let transfer_amount = to_atto(15);
+        contract.transfer(transfer_amount.into(), non_owner());
+        context.account_balance = env::account_balance();
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_known_deposited_balance().0, total_balance);
+        assert_eq!(contract.get_owners_balance().0, to_atto(5));
+        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(5));
+        context.view_config = None;
+    }
+
+    #[test]
+    #[should_panic(expected = "Staking pool is already selected")]
+    fn test_staking_pool_selected_again() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2).try_into().unwrap();
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        // Selecting another staking pool
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.select_staking_pool("staking_pool_2".parse().unwrap());
+    }
+
+    #[test]
+    fn test_select_staking_pool_checks_whitelist() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.select_staking_pool("staking_pool".parse().unwrap());
+
+        // The staking pool is only selected by the callback after the whitelist check.
+        assert_eq!(contract.get_staking_pool_account_id(), None);
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, contract.staking_pool_whitelist_account_id);
+        match &receipts[0].actions[..] {
+            [MockAction::FunctionCallWeight { method_name, args, .. }] => {
+                assert_eq!(method_name, b"is_whitelisted");
+                let args: unc_sdk::serde_json::Value =
+                    unc_sdk::serde_json::from_slice(args).unwrap();
+                assert_eq!(args["staking_pool_account_id"], "staking_pool");
+            }
+            actions => panic!("Unexpected actions {:?}", actions),
+        }
+        assert_eq!(receipts[1].receiver_id, lockup_account());
+        match &receipts[1].actions[..] {
+            [MockAction::FunctionCallWeight { method_name, .. }] => {
+                assert_eq!(method_name, b"on_whitelist_is_whitelisted");
+            }
+            actions => panic!("Unexpected actions {:?}", actions),
+        }
+    }
+
+    fn new_contract_with_staking_pool(staking_pool: AccountId) -> LockupContract {
+        LockupContract::new(
+            account_owner(),
+            to_nanos(YEAR).into(),
+            None,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            },
+            None,
+            None,
+            "whitelist".parse().unwrap(),
+            None,
+            None,
+            Some(staking_pool),
+        )
+    }
+
+    #[test]
+    fn test_new_with_staking_pool_checks_whitelist() {
+        let context = basic_context();
+        testing_env!(context.clone());
+        let contract = new_contract_with_staking_pool("staking_pool".parse().unwrap());
+
+        assert_eq!(contract.get_staking_pool_account_id(), None);
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, contract.staking_pool_whitelist_account_id);
+        match &receipts[0].actions[..] {
+            [MockAction::FunctionCallWeight { method_name, args, .. }] => {
+                assert_eq!(method_name, b"is_whitelisted");
+                let args: unc_sdk::serde_json::Value =
+                    unc_sdk::serde_json::from_slice(args).unwrap();
+                assert_eq!(args["staking_pool_account_id"], "staking_pool");
+            }
+            actions => panic!("Unexpected actions {:?}", actions),
+        }
+        assert_eq!(receipts[1].receiver_id, lockup_account());
+        match &receipts[1].actions[..] {
+            [MockAction::FunctionCallWeight { method_name, .. }] => {
+                assert_eq!(method_name, b"on_whitelist_is_whitelisted_to_stake");
+            }
+            actions => panic!("Unexpected actions {:?}", actions),
+        }
+    }
+
+    #[test]
+    fn test_on_whitelist_is_whitelisted_to_stake() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        let mut contract = new_contract_with_staking_pool(staking_pool.clone());
+        let amount = to_atto(LOCKUP_UNC) - MIN_BALANCE_FOR_STORAGE;
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted_to_stake(true, staking_pool.clone());
+        assert_eq!(contract.get_staking_pool_account_id(), Some(staking_pool.clone()));
+        assert!(
+            contract.staking_information.as_ref().unwrap().status == TransactionStatus::Busy
+        );
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, staking_pool);
+        match &receipts[0].actions[..] {
+            [MockAction::FunctionCallWeight { method_name, attached_deposit, .. }] => {
+                assert_eq!(method_name, b"deposit_and_stake");
+                assert_eq!(attached_deposit.as_attounc(), amount);
+            }
+            actions => panic!("Unexpected actions {:?}", actions),
+        }
+
+        context.account_balance = UncToken::from_attounc(MIN_BALANCE_FOR_STORAGE);
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_staking_pool_deposit_and_stake(amount.into());
+        assert_eq!(contract.get_known_deposited_balance().0, amount);
+        assert!(
+            contract.staking_information.as_ref().unwrap().status == TransactionStatus::Idle
+        );
+    }
+
+    #[test]
+    fn test_on_whitelist_is_whitelisted_to_stake_not_whitelisted() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        let mut contract = new_contract_with_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"false".to_vec())],
+        );
+        let result = contract.on_whitelist_is_whitelisted_to_stake(false, staking_pool);
+        assert!(matches!(result, unc_sdk::PromiseOrValue::Value(false)));
+        assert_eq!(contract.get_staking_pool_account_id(), None);
+        assert!(get_created_receipts().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "The given staking pool account ID is not whitelisted")]
+    fn test_staking_pool_not_whitelisted() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2).try_into().unwrap();
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"false".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(false, staking_pool.clone());
+    }
+
+    #[test]
+    #[should_panic(expected = "Staking pool is not selected")]
+    fn test_staking_pool_unselecting_non_selected() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2).try_into().unwrap();
+
+        // Unselecting staking pool
+        testing_env!(context.clone());
+        contract.unselect_staking_pool();
+    }
+
+    #[test]
+    #[should_panic(expected = "There is still a deposit on the staking pool")]
+    fn test_staking_pool_unselecting_with_deposit() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2).try_into().unwrap();
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        // Deposit to the staking_pool
+        let amount = to_atto(LOCKUP_UNC - 100);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.deposit_to_staking_pool(amount.into());
+        context.account_balance = env::account_balance();
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_staking_pool_deposit(amount.into());
+
+        // Unselecting staking pool
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unselect_staking_pool();
+    }
+
+    #[test]
+    fn test_staking_pool_unselecting_with_remote_balance() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2).try_into().unwrap();
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        // The known deposit is zero, but there is still staked balance at the staking pool.
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unselect_staking_pool();
+
+        let staked_balance = to_atto(20);
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(format!("\"{}\"", staked_balance).into_bytes())],
+        );
+        assert!(!contract.on_get_account_total_balance_to_unselect());
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_staking_pool_account_id(), Some(staking_pool));
+        assert_eq!(contract.get_known_deposited_balance().0, staked_balance);
+    }
+
+    #[test]
+    fn test_staking_pool_unselecting_with_failed_balance_check() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unselect_staking_pool();
+
+        // The staking pool fails to report the balance, so it stays selected.
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        assert!(!contract.on_get_account_total_balance_to_unselect());
+        assert_eq!(contract.get_staking_pool_account_id(), Some(staking_pool.clone()));
+
+        // The lockup isn't busy, so the owner can retry once the staking pool reports the balance.
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unselect_staking_pool();
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"0\"".to_vec())],
+        );
+        assert!(contract.on_get_account_total_balance_to_unselect());
+        assert_eq!(contract.get_staking_pool_account_id(), None);
+    }
+
+    #[test]
+    fn test_staking_pool_unstake_and_withdraw_all() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        // Deposit and stake to the staking_pool
+        let amount = to_atto(LOCKUP_UNC - 100);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.deposit_and_stake(amount.into());
+        context.account_balance = env::account_balance();
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_staking_pool_deposit_and_stake(amount.into());
+
+        // The first call queries the staked balance and unstakes all of it.
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unstake_and_withdraw_all_from_staking_pool();
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, staking_pool);
+
+        // Assuming there are 20 UNC tokens in rewards.
+        let staked_balance = amount + to_atto(20);
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(format!("\"{}\"", staked_balance).into_bytes())],
+        );
+        drop(contract.on_get_account_staked_balance_to_exit());
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, staking_pool);
+        assert_eq!(receipts[1].receiver_id, lockup_account());
+
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.on_staking_pool_unstake(staked_balance.into()));
+
+        // The second call finds nothing staked and withdraws the unstaked balance.
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unstake_and_withdraw_all_from_staking_pool();
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"0\"".to_vec())],
+        );
+        drop(contract.on_get_account_staked_balance_to_exit());
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, staking_pool);
+
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(format!("\"{}\"", staked_balance).into_bytes())],
+        );
+        drop(contract.on_get_account_unstaked_balance_to_withdraw_by_owner(staked_balance.into()));
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, staking_pool);
+
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.on_staking_pool_withdraw(staked_balance.into()));
+        context.account_balance = context.account_balance.saturating_add(UncToken::from_attounc(staked_balance));
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_known_deposited_balance().0, 0);
+        assert_eq!(contract.get_owners_balance().0, to_atto(20));
+    }
+
+    #[test]
+    fn test_staking_pool_unstake_and_withdraw_all_with_failed_balance_check() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unstake_and_withdraw_all_from_staking_pool();
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        match contract.on_get_account_staked_balance_to_exit() {
+            unc_sdk::PromiseOrValue::Value(value) => assert!(!value),
+            unc_sdk::PromiseOrValue::Promise(_) => panic!("Expected the value"),
+        }
+        assert!(get_created_receipts().is_empty());
+
+        // The lockup isn't busy, so the owner can try again.
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unstake_and_withdraw_all_from_staking_pool();
+    }
+
+    #[test]
+    #[should_panic(expected = "Staking pool is not selected")]
+    fn test_staking_pool_unstake_and_withdraw_all_without_staking_pool() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        testing_env!(context.clone());
+        contract.unstake_and_withdraw_all_from_staking_pool();
+    }
+
+    #[test]
+    fn test_staking_pool_unstake_all_and_withdraw_all() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2).try_into().unwrap();
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        // Deposit and stake to the staking_pool
+        let amount = to_atto(LOCKUP_UNC - 100);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.deposit_and_stake(amount.into());
+        context.account_balance = env::account_balance();
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_staking_pool_deposit_and_stake(amount.into());
+
+        // Unstaking everything
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.unstake_all();
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        assert!(contract.on_staking_pool_unstake_all());
+
+        // Querying the unstaked balance from the staking pool to withdraw everything.
+        // Assuming there are 20 UNC tokens in rewards.
+        let unstaked_balance = amount + to_atto(20);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.withdraw_all_from_staking_pool();
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, staking_pool);
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_get_account_unstaked_balance_to_withdraw_by_owner(unstaked_balance.into());
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, staking_pool);
+
+        // Withdrawing
+        context.account_balance = context.account_balance.saturating_add(UncToken::from_attounc(unstaked_balance));
+        testing_env!(
             context.clone(),
             test_vm_config(),
             RuntimeFeesConfig::test(),
             Default::default(),
             vec![PromiseResult::Successful(vec![])],
         );
-        contract.on_staking_pool_stake(amount.into());
+        assert!(contract.on_staking_pool_withdraw(unstaked_balance.into()));
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_known_deposited_balance().0, 0);
+        assert_eq!(contract.get_owners_balance().0, to_atto(20));
+    }
+
+    #[test]
+    #[should_panic(expected = "Staking pool is not selected")]
+    fn test_withdraw_all_staking_pool_is_not_selected() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        testing_env!(context.clone());
+
+        contract.withdraw_all_from_staking_pool();
+    }
+
+    #[test]
+    fn test_staking_pool_owner_balance() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2).try_into().unwrap();
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
+
+        let lockup_amount = to_atto(LOCKUP_UNC);
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_owners_balance().0, lockup_amount);
+        context.view_config = None;
+
+        // Selecting staking pool
+        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())],
+        );
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        // Deposit to the staking_pool
+        let mut total_amount = 0;
+        let amount = to_atto(100);
+        for _ in 1..=5 {
+            total_amount += amount;
+            context.predecessor_account_id = account_owner();
+            testing_env!(context.clone());
+            contract.deposit_to_staking_pool(amount.into());
+            context.account_balance = env::account_balance();
+            assert_eq!(context.account_balance, UncToken::from_attounc(lockup_amount - total_amount));
+
+            context.predecessor_account_id = lockup_account();
+            testing_env!(
+                context.clone(),
+                test_vm_config(),
+                RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![PromiseResult::Successful(vec![])],
+            );
+            contract.on_staking_pool_deposit(amount.into());
+            context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+            testing_env!(context.clone());
+            assert_eq!(contract.get_known_deposited_balance().0, total_amount);
+            assert_eq!(contract.get_owners_balance().0, lockup_amount);
+            assert_eq!(
+                contract.get_liquid_owners_balance().0,
+                lockup_amount - total_amount - MIN_BALANCE_FOR_STORAGE
+            );
+            context.view_config = None;
+        }
+
+        // Withdrawing from the staking_pool. Plus one extra time as a reward
+        let mut total_withdrawn_amount = 0;
+        for _ in 1..=6 {
+            total_withdrawn_amount += amount;
+            context.predecessor_account_id = account_owner();
+            testing_env!(context.clone());
+            contract.withdraw_from_staking_pool(amount.into());
+            context.account_balance = context.account_balance.saturating_add(UncToken::from_attounc(amount));
+            assert_eq!(
+                context.account_balance,
+                UncToken::from_attounc(lockup_amount - total_amount + total_withdrawn_amount)
+            );
+
+            context.predecessor_account_id = lockup_account();
+            testing_env!(
+                context.clone(),
+                test_vm_config(),
+                RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![PromiseResult::Successful(vec![])],
+            );
+            contract.on_staking_pool_withdraw(amount.into());
+            context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+            testing_env!(context.clone());
+            assert_eq!(
+                contract.get_known_deposited_balance().0,
+                total_amount.saturating_sub(total_withdrawn_amount)
+            );
+            assert_eq!(
+                contract.get_owners_balance().0,
+                lockup_amount + total_withdrawn_amount.saturating_sub(total_amount)
+            );
+            assert_eq!(
+                contract.get_liquid_owners_balance().0,
+                lockup_amount - total_amount + total_withdrawn_amount - MIN_BALANCE_FOR_STORAGE
+            );
+            context.view_config = None;
+        }
+    }
+
+    #[test]
+    fn test_lock_timestmap() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let contract = LockupContract::new(
+            account_owner(),
+            0.into(),
+            Some(to_ts(GENESIS_TIME_IN_DAYS + YEAR).into()),
+            TransfersInformation::TransfersDisabled {
+                transfer_poll_account_id: "transfers".parse().unwrap(),
+            },
+            None,
+            None,
+           "whitelist".parse().unwrap(),
+            None,
+            None,
+            None,
+        );
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        testing_env!(context.clone());
+        assert_eq!(contract.get_owners_balance().0, 0);
+        assert_eq!(contract.get_liquid_owners_balance().0, 0);
+        assert_eq!(
+            contract.get_locked_vested_amount(no_vesting_schedule()).0,
+            to_atto(1000)
+        );
+        assert_eq!(contract.get_locked_amount().0, to_atto(1000));
+        assert_eq!(
+            contract.get_unvested_amount(no_vesting_schedule()).0,
+            to_atto(0)
+        );
+        assert!(!contract.are_transfers_enabled());
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        testing_env!(context.clone());
+        assert_eq!(contract.get_owners_balance().0, 0);
+        assert_eq!(contract.get_liquid_owners_balance().0, 0);
+        assert_eq!(
+            contract.get_locked_vested_amount(no_vesting_schedule()).0,
+            to_atto(1000)
+        );
+        assert_eq!(contract.get_locked_amount().0, to_atto(1000));
+        assert_eq!(
+            contract.get_unvested_amount(no_vesting_schedule()).0,
+            to_atto(0)
+        );
+    }
+
+    #[test]
+    fn test_lock_timestmap_transfer_enabled() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let contract = LockupContract::new(
+            account_owner(),
+            0.into(),
+            Some(to_ts(GENESIS_TIME_IN_DAYS + YEAR).into()),
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS + YEAR / 2).into(),
+            },
+            None,
+            None,
+           "whitelist".parse().unwrap(),
+            None,
+            None,
+            None,
+        );
+
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        testing_env!(context.clone());
+        assert_eq!(contract.get_owners_balance().0, to_atto(1000));
+        assert_eq!(
+            contract.get_liquid_owners_balance().0,
+            to_atto(1000) - MIN_BALANCE_FOR_STORAGE
+        );
+        assert_eq!(
+            contract.get_locked_vested_amount(no_vesting_schedule()).0,
+            to_atto(0)
+        );
+        assert_eq!(contract.get_locked_amount().0, to_atto(0));
+        assert_eq!(
+            contract.get_unvested_amount(no_vesting_schedule()).0,
+            to_atto(0)
+        );
+    }
+
+    #[test]
+    fn test_get_vested_amount_and_vested_and_unlocked() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let contract = LockupContract::new(
+            account_owner(),
+            to_nanos(YEAR).into(),
+            None,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            },
+            Some(VestingScheduleOrHash::VestingSchedule(
+                vesting_schedule.clone(),
+            )),
+            None,
+            "whitelist".parse().unwrap(),
+            Some(account_foundation()),
+            None,
+            None,
+        );
 
-        // Assuming there are 20 UNC tokens in rewards. Unstaking.
-        let unstake_amount = amount + to_atto(20);
-        context.predecessor_account_id = account_owner();
+        // A quarter is vested at the cliff, but the lockup keeps all of it locked.
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
         testing_env!(context.clone());
-        contract.unstake(unstake_amount.into());
-
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(vec![])],
+        assert_eq!(contract.get_vested_amount().0, to_atto(250));
+        assert_eq!(contract.get_vested_and_unlocked().0, 0);
+        assert_eq!(
+            contract.get_locked_vested_amount(vesting_schedule.clone()).0,
+            to_atto(250)
         );
-        contract.on_staking_pool_unstake(unstake_amount.into());
 
-        // Withdrawing
-        context.predecessor_account_id = account_owner();
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR / 2);
         testing_env!(context.clone());
-        contract.withdraw_from_staking_pool(unstake_amount.into());
-        context.account_balance = context.account_balance.saturating_add(UncToken::from_attounc(unstake_amount));
-
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(vec![])],
+        let vested_amount = contract.get_vested_amount().0;
+        assert!(vested_amount > to_atto(250) && vested_amount < to_atto(500));
+        assert_eq!(
+            vested_amount,
+            to_atto(1000) - contract.get_unvested_amount(vesting_schedule.clone()).0
         );
-        contract.on_staking_pool_withdraw(unstake_amount.into());
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        assert_eq!(contract.get_vested_and_unlocked().0, 0);
+        assert_eq!(
+            contract.get_locked_vested_amount(vesting_schedule.clone()).0,
+            vested_amount
+        );
+
+        // Once the lockup ends, everything vested is available.
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
         testing_env!(context.clone());
-        assert_eq!(contract.get_known_deposited_balance().0, 0);
-        context.view_config = None;
+        assert_eq!(contract.get_vested_amount().0, to_atto(500));
+        assert_eq!(contract.get_vested_and_unlocked().0, to_atto(500));
+        assert_eq!(contract.get_locked_vested_amount(vesting_schedule).0, 0);
+        assert_eq!(contract.get_vested_and_unlocked(), contract.get_liquid_owners_balance());
 
-        // Unselecting staking pool
-        context.predecessor_account_id = account_owner();
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + 3 * YEAR);
         testing_env!(context.clone());
-        contract.unselect_staking_pool();
-        assert_eq!(contract.get_staking_pool_account_id(), None);
+        assert_eq!(contract.get_vested_amount().0, to_atto(1000));
+        assert_eq!(contract.get_vested_and_unlocked().0, to_atto(1000));
     }
 
     #[test]
-    fn test_staking_pool_refresh_balance() {
-        let (mut context, mut contract) = lockup_only_setup();
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(2).try_into().unwrap();
-
-        // Selecting staking pool
-        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+    fn test_termination_no_staking() {
+        let mut context = basic_context();
         testing_env!(context.clone());
-        contract.select_staking_pool(staking_pool.clone());
-
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(b"true".to_vec())],
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = LockupContract::new(
+            account_owner(),
+            to_nanos(YEAR).into(),
+            None,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            },
+            Some(VestingScheduleOrHash::VestingSchedule(
+                vesting_schedule.clone(),
+            )),
+            None,
+           "whitelist".parse().unwrap(),
+            Some(account_foundation()),
+            None,
+            None,
         );
-        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
 
-        // Deposit to the staking_pool
-        let amount = to_atto(LOCKUP_UNC - 100);
-        context.predecessor_account_id = account_owner();
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
         testing_env!(context.clone());
-        contract.deposit_to_staking_pool(amount.into());
-        context.account_balance = env::account_balance();
-        assert_eq!(context.account_balance, UncToken::from_attounc(to_atto(LOCKUP_UNC) - amount));
-
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(vec![])],
+        assert_eq!(
+            contract.get_vesting_information(),
+            VestingInformation::VestingSchedule(vesting_schedule.clone())
+        );
+        assert_eq!(contract.get_owners_balance().0, 0);
+        assert_eq!(contract.get_liquid_owners_balance().0, 0);
+        assert_eq!(
+            contract
+                .get_locked_vested_amount(vesting_schedule.clone())
+                .0,
+            to_atto(250)
+        );
+        assert_eq!(contract.get_locked_amount().0, to_atto(1000));
+        assert_eq!(
+            contract.get_unvested_amount(vesting_schedule.clone()).0,
+            to_atto(750)
         );
-        contract.on_staking_pool_deposit(amount.into());
 
-        // Staking on the staking pool
-        context.predecessor_account_id = account_owner();
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
         testing_env!(context.clone());
-        contract.stake(amount.into());
+        assert_eq!(contract.get_owners_balance().0, to_atto(500));
+        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(500));
+        assert_eq!(
+            contract
+                .get_locked_vested_amount(vesting_schedule.clone())
+                .0,
+            to_atto(0)
+        );
+        assert_eq!(contract.get_locked_amount().0, to_atto(500));
+        assert_eq!(
+            contract.get_unvested_amount(vesting_schedule.clone()).0,
+            to_atto(500)
+        );
 
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(vec![])],
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + 2 * YEAR);
+        testing_env!(context.clone());
+        assert_eq!(contract.get_owners_balance().0, to_atto(750));
+        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(750));
+        assert_eq!(contract.get_locked_amount().0, to_atto(250));
+        assert_eq!(
+            contract.get_unvested_amount(vesting_schedule.clone()).0,
+            to_atto(250)
         );
-        contract.on_staking_pool_stake(amount.into());
+
+        // Terminating
+        context.view_config = None;
+        context.predecessor_account_id = account_foundation();
+        context.signer_account_pk = public_key(3).into();
+        testing_env!(context.clone());
+        contract.terminate_vesting(None);
 
         context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
         testing_env!(context.clone());
-        assert_eq!(contract.get_owners_balance().0, 0);
-        assert_eq!(contract.get_liquid_owners_balance().0, 0);
-        assert_eq!(contract.get_known_deposited_balance().0, amount);
-        context.view_config = None;
+        assert_eq!(
+            contract.get_vesting_information(),
+            VestingInformation::Terminating(TerminationInformation {
+                unvested_amount: to_atto(250).into(),
+                status: TerminationStatus::ReadyToWithdraw,
+            })
+        );
+        assert_eq!(contract.get_owners_balance().0, to_atto(750));
+        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(750));
+        assert_eq!(contract.get_locked_amount().0, to_atto(250));
+        assert_eq!(
+            contract.get_unvested_amount(vesting_schedule.clone()).0,
+            to_atto(250)
+        );
+        assert_eq!(contract.get_terminated_unvested_balance().0, to_atto(250));
+        assert_eq!(
+            contract.get_terminated_unvested_balance_deficit().0,
+            to_atto(0)
+        );
+        assert_eq!(
+            contract.get_termination_status(),
+            Some(TerminationStatus::ReadyToWithdraw)
+        );
 
-        // Assuming there are 20 UNC tokens in rewards. Refreshing balance.
-        let total_balance = amount + to_atto(20);
-        context.predecessor_account_id = account_owner();
+        // Withdrawing
+        context.view_config = None;
         testing_env!(context.clone());
-        contract.refresh_staking_pool_balance();
+        let receiver_id: AccountId = "unc".parse().unwrap();
+        contract.termination_withdraw(receiver_id.clone());
+        context.account_balance = env::account_balance();
 
-        // In unit tests, the following call ignores the promise value, because it's passed directly.
         context.predecessor_account_id = lockup_account();
         testing_env!(
             context.clone(),
@@ -839,126 +3035,134 @@ mod tests {
             Default::default(),
             vec![PromiseResult::Successful(vec![])],
         );
-        contract.on_get_account_total_balance(total_balance.into());
+        contract.on_withdraw_unvested_amount(to_atto(250).into(), receiver_id);
 
         context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
         testing_env!(context.clone());
-        assert_eq!(contract.get_known_deposited_balance().0, total_balance);
-        assert_eq!(contract.get_owners_balance().0, to_atto(20));
-        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(20));
-        context.view_config = None;
+        assert_eq!(contract.get_owners_balance().0, to_atto(750));
+        assert_eq!(
+            contract.get_liquid_owners_balance().0,
+            to_atto(750) - MIN_BALANCE_FOR_STORAGE
+        );
+        assert_eq!(
+            contract.get_unvested_amount(vesting_schedule.clone()).0,
+            to_atto(0)
+        );
+        assert_eq!(contract.get_terminated_unvested_balance().0, to_atto(0));
+        assert_eq!(contract.get_termination_status(), None);
+        assert_eq!(contract.get_vesting_information(), VestingInformation::None);
+    }
 
-        // Withdrawing these tokens
-        context.predecessor_account_id = account_owner();
+    #[test]
+    fn test_termination_partial_withdrawals() {
+        let mut context = basic_context();
         testing_env!(context.clone());
-        let transfer_amount = to_atto(15);
-        contract.transfer(transfer_amount.into(), non_owner());
-        context.account_balance = env::account_balance();
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
 
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + 2 * YEAR);
+        context.predecessor_account_id = account_foundation();
         testing_env!(context.clone());
-        assert_eq!(contract.get_known_deposited_balance().0, total_balance);
-        assert_eq!(contract.get_owners_balance().0, to_atto(5));
-        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(5));
-        context.view_config = None;
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: SALT.to_vec().into(),
+        }));
+        assert_eq!(contract.get_terminated_unvested_balance().0, to_atto(250));
+
+        // Every withdrawal decreases the remaining balance exactly once, even though the withdrawn
+        // tokens are also accumulated in `termination_withdrawn_tokens`.
+        let receiver_id: AccountId = "unc".parse().unwrap();
+        context.predecessor_account_id = lockup_account();
+        for (amount, remaining_balance) in [(100, 150), (100, 50), (50, 0)] {
+            testing_env!(
+                context.clone(),
+                test_vm_config(),
+                RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![PromiseResult::Successful(vec![])],
+            );
+            assert!(contract.on_withdraw_unvested_amount(to_atto(amount).into(), receiver_id.clone()));
+            assert_eq!(
+                contract.get_terminated_unvested_balance().0,
+                to_atto(remaining_balance)
+            );
+        }
+        assert_eq!(
+            contract.lockup_information.termination_withdrawn_tokens,
+            to_atto(250)
+        );
+        assert_eq!(contract.get_termination_status(), None);
+        assert_eq!(contract.get_vesting_information(), VestingInformation::None);
     }
 
     #[test]
-    #[should_panic(expected = "Staking pool is already selected")]
-    fn test_staking_pool_selected_again() {
-        let (mut context, mut contract) = lockup_only_setup();
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(2).try_into().unwrap();
-
-        // Selecting staking pool
-        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+    fn test_cancel_termination() {
+        let mut context = basic_context();
         testing_env!(context.clone());
-        contract.select_staking_pool(staking_pool.clone());
-
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(b"true".to_vec())],
-        );
-        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
+        let vesting_information = contract.get_vesting_information();
 
-        // Selecting another staking pool
-        context.predecessor_account_id = account_owner();
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + 2 * YEAR);
+        context.predecessor_account_id = account_foundation();
         testing_env!(context.clone());
-        contract.select_staking_pool("staking_pool_2".parse().unwrap());
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: SALT.to_vec().into(),
+        }));
+        assert_eq!(contract.get_termination_status(), Some(TerminationStatus::ReadyToWithdraw));
+
+        contract.cancel_termination();
+        assert_eq!(contract.get_termination_status(), None);
+        assert_eq!(contract.get_vesting_information(), vesting_information);
+        assert_eq!(contract.terminated_vesting_information, None);
+        assert!(contract.is_terminable());
     }
 
     #[test]
-    #[should_panic(expected = "The given staking pool account ID is not whitelisted")]
-    fn test_staking_pool_not_whitelisted() {
-        let (mut context, mut contract) = lockup_only_setup();
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(2).try_into().unwrap();
+    fn test_cancel_termination_after_unstaking() {
+        let mut context = basic_context();
+        let mut contract = terminating_contract_with_staking_pool(&mut context);
+        contract.set_termination_status(TerminationStatus::EverythingUnstaked);
 
-        // Selecting staking pool
-        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+        context.predecessor_account_id = account_foundation();
         testing_env!(context.clone());
-        contract.select_staking_pool(staking_pool.clone());
+        contract.cancel_termination();
+        assert_eq!(contract.get_termination_status(), None);
 
-        context.predecessor_account_id = lockup_account();
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(b"false".to_vec())],
-        );
-        contract.on_whitelist_is_whitelisted(false, staking_pool.clone());
+        // The owner can stake again.
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.deposit_and_stake(to_atto(100).into());
     }
 
     #[test]
-    #[should_panic(expected = "Staking pool is not selected")]
-    fn test_staking_pool_unselecting_non_selected() {
-        let (mut context, mut contract) = lockup_only_setup();
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(2).try_into().unwrap();
+    #[should_panic(expected = "The termination can't be cancelled while a transaction is in progress")]
+    fn test_cancel_termination_while_unstaking() {
+        let mut context = basic_context();
+        let mut contract = terminating_contract_with_staking_pool(&mut context);
+        contract.set_termination_status(TerminationStatus::UnstakingInProgress);
 
-        // Unselecting staking pool
+        context.predecessor_account_id = account_foundation();
         testing_env!(context.clone());
-        contract.unselect_staking_pool();
+        contract.cancel_termination();
     }
 
     #[test]
-    #[should_panic(expected = "There is still a deposit on the staking pool")]
-    fn test_staking_pool_unselecting_with_deposit() {
-        let (mut context, mut contract) = lockup_only_setup();
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(2).try_into().unwrap();
-
-        // Selecting staking pool
-        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+    #[should_panic(expected = "The terminated unvested balance is already withdrawn")]
+    fn test_cancel_termination_after_withdrawal() {
+        let mut context = basic_context();
         testing_env!(context.clone());
-        contract.select_staking_pool(staking_pool.clone());
-
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(b"true".to_vec())],
-        );
-        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+        let vesting_schedule = new_vesting_schedule(0);
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
 
-        // Deposit to the staking_pool
-        let amount = to_atto(LOCKUP_UNC - 100);
-        context.predecessor_account_id = account_owner();
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + 2 * YEAR);
+        context.predecessor_account_id = account_foundation();
         testing_env!(context.clone());
-        contract.deposit_to_staking_pool(amount.into());
-        context.account_balance = env::account_balance();
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: SALT.to_vec().into(),
+        }));
 
         context.predecessor_account_id = lockup_account();
         testing_env!(
@@ -968,303 +3172,291 @@ mod tests {
             Default::default(),
             vec![PromiseResult::Successful(vec![])],
         );
-        contract.on_staking_pool_deposit(amount.into());
+        contract.on_withdraw_unvested_amount(to_atto(100).into(), "unc".parse().unwrap());
+        assert_eq!(contract.get_termination_status(), Some(TerminationStatus::ReadyToWithdraw));
 
-        // Unselecting staking pool
-        context.predecessor_account_id = account_owner();
+        context.predecessor_account_id = account_foundation();
         testing_env!(context.clone());
-        contract.unselect_staking_pool();
+        contract.cancel_termination();
     }
 
     #[test]
-    fn test_staking_pool_owner_balance() {
-        let (mut context, mut contract) = lockup_only_setup();
-        context.predecessor_account_id = account_owner();
-        context.signer_account_id = account_owner();
-        context.signer_account_pk = public_key(2).try_into().unwrap();
-        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR + 1);
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_cancel_termination_by_owner() {
+        let mut context = basic_context();
+        let mut contract = terminating_contract_with_staking_pool(&mut context);
+        contract.cancel_termination();
+    }
 
-        let lockup_amount = to_atto(LOCKUP_UNC);
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
-        testing_env!(context.clone());
-        assert_eq!(contract.get_owners_balance().0, lockup_amount);
-        context.view_config = None;
+    fn four_years_vesting_schedule() -> VestingSchedule {
+        VestingSchedule {
+            start_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            cliff_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            end_timestamp: to_ts(GENESIS_TIME_IN_DAYS + YEAR * 4).into(),
+        }
+    }
 
-        // Selecting staking pool
-        let staking_pool: AccountId = "staking_pool".parse().unwrap();
+    /// The lockup vests 1000 UNC linearly over 4 years from genesis without the lockup period,
+    /// and the foundation grants 400 UNC vesting after 1 year of cliff between the years 1 and 3.
+    fn new_contract_with_vesting_grant(context: &mut VMContext) -> LockupContract {
         testing_env!(context.clone());
-        contract.select_staking_pool(staking_pool.clone());
+        let mut contract = LockupContract::new(
+            account_owner(),
+            0.into(),
+            None,
+            TransfersInformation::TransfersEnabled {
+                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
+            },
+            Some(VestingScheduleOrHash::VestingSchedule(four_years_vesting_schedule())),
+            None,
+            "whitelist".parse().unwrap(),
+            Some(account_foundation()),
+            None,
+            None,
+        );
 
-        context.predecessor_account_id = lockup_account();
-        testing_env!(
-            context.clone(),
-            test_vm_config(),
-            RuntimeFeesConfig::test(),
-            Default::default(),
-            vec![PromiseResult::Successful(b"true".to_vec())],
+        context.predecessor_account_id = account_foundation();
+        context.attached_deposit = UncToken::from_attounc(to_atto(400));
+        context.account_balance = UncToken::from_attounc(to_atto(LOCKUP_UNC + 400));
+        testing_env!(context.clone());
+        contract.add_vesting_grant(
+            VestingSchedule {
+                start_timestamp: to_ts(GENESIS_TIME_IN_DAYS + YEAR).into(),
+                cliff_timestamp: to_ts(GENESIS_TIME_IN_DAYS + YEAR * 2).into(),
+                end_timestamp: to_ts(GENESIS_TIME_IN_DAYS + YEAR * 3).into(),
+            },
+            to_atto(400).into(),
         );
-        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
 
-        // Deposit to the staking_pool
-        let mut total_amount = 0;
-        let amount = to_atto(100);
-        for _ in 1..=5 {
-            total_amount += amount;
-            context.predecessor_account_id = account_owner();
-            testing_env!(context.clone());
-            contract.deposit_to_staking_pool(amount.into());
-            context.account_balance = env::account_balance();
-            assert_eq!(context.account_balance, UncToken::from_attounc(lockup_amount - total_amount));
+        context.predecessor_account_id = account_owner();
+        context.attached_deposit = UncToken::from_attounc(0);
+        testing_env!(context.clone());
+        contract
+    }
 
-            context.predecessor_account_id = lockup_account();
-            testing_env!(
-                context.clone(),
-                test_vm_config(),
-                RuntimeFeesConfig::test(),
-                Default::default(),
-                vec![PromiseResult::Successful(vec![])],
-            );
-            contract.on_staking_pool_deposit(amount.into());
-            context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
-            testing_env!(context.clone());
-            assert_eq!(contract.get_known_deposited_balance().0, total_amount);
-            assert_eq!(contract.get_owners_balance().0, lockup_amount);
-            assert_eq!(
-                contract.get_liquid_owners_balance().0,
-                lockup_amount - total_amount - MIN_BALANCE_FOR_STORAGE
-            );
-            context.view_config = None;
-        }
+    #[test]
+    fn test_vesting_grants() {
+        let mut context = basic_context();
+        let contract = new_contract_with_vesting_grant(&mut context);
+        assert_eq!(contract.get_vesting_grants().len(), 1);
+        assert_eq!(contract.lockup_information.lockup_amount, to_atto(LOCKUP_UNC + 400));
 
-        // Withdrawing from the staking_pool. Plus one extra time as a reward
-        let mut total_withdrawn_amount = 0;
-        for _ in 1..=6 {
-            total_withdrawn_amount += amount;
-            context.predecessor_account_id = account_owner();
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        for (years, unvested_amount) in [(0, 1400), (1, 1150), (2, 700), (3, 250), (4, 0)] {
+            context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR * years);
             testing_env!(context.clone());
-            contract.withdraw_from_staking_pool(amount.into());
-            context.account_balance = context.account_balance.saturating_add(UncToken::from_attounc(amount));
             assert_eq!(
-                context.account_balance,
-                UncToken::from_attounc(lockup_amount - total_amount + total_withdrawn_amount)
-            );
-
-            context.predecessor_account_id = lockup_account();
-            testing_env!(
-                context.clone(),
-                test_vm_config(),
-                RuntimeFeesConfig::test(),
-                Default::default(),
-                vec![PromiseResult::Successful(vec![])],
+                contract.get_unvested_amount(four_years_vesting_schedule()).0,
+                to_atto(unvested_amount)
             );
-            contract.on_staking_pool_withdraw(amount.into());
-            context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
-            testing_env!(context.clone());
+            assert_eq!(contract.get_locked_amount().0, to_atto(unvested_amount));
             assert_eq!(
-                contract.get_known_deposited_balance().0,
-                total_amount.saturating_sub(total_withdrawn_amount)
+                contract.get_balance_breakdown().unvested_amount.0,
+                to_atto(unvested_amount)
             );
             assert_eq!(
                 contract.get_owners_balance().0,
-                lockup_amount + total_withdrawn_amount.saturating_sub(total_amount)
+                to_atto(LOCKUP_UNC + 400 - unvested_amount)
             );
-            assert_eq!(
-                contract.get_liquid_owners_balance().0,
-                lockup_amount - total_amount + total_withdrawn_amount - MIN_BALANCE_FOR_STORAGE
-            );
-            context.view_config = None;
         }
     }
 
     #[test]
-    fn test_lock_timestmap() {
+    fn test_vesting_grants_next_unlock() {
         let mut context = basic_context();
-        testing_env!(context.clone());
-        let contract = LockupContract::new(
-            account_owner(),
-            0.into(),
-            Some(to_ts(GENESIS_TIME_IN_DAYS + YEAR).into()),
-            TransfersInformation::TransfersDisabled {
-                transfer_poll_account_id: "transfers".parse().unwrap(),
-            },
-            None,
-            None,
-           "whitelist".parse().unwrap(),
-            None,
-        );
+        let contract = new_contract_with_vesting_grant(&mut context);
 
+        // The base vesting is linear, so it unlocks continuously until the end of year 4.
         context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR * 3);
         testing_env!(context.clone());
-        assert_eq!(contract.get_owners_balance().0, 0);
-        assert_eq!(contract.get_liquid_owners_balance().0, 0);
-        assert_eq!(
-            contract.get_locked_vested_amount(no_vesting_schedule()).0,
-            to_atto(1000)
-        );
-        assert_eq!(contract.get_locked_amount().0, to_atto(1000));
-        assert_eq!(
-            contract.get_unvested_amount(no_vesting_schedule()).0,
-            to_atto(0)
-        );
-        assert!(!contract.are_transfers_enabled());
+        assert!(contract.is_terminable());
+        assert_eq!(contract.get_seconds_until_next_unlock(), YEAR * 24 * 60 * 60);
+    }
+
+    #[test]
+    fn test_terminate_vesting_with_grants() {
+        let mut context = basic_context();
+        let mut contract = new_contract_with_vesting_grant(&mut context);
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR * 2);
+        context.predecessor_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.terminate_vesting(None);
+        assert_eq!(contract.get_terminated_unvested_balance().0, to_atto(700));
+        assert_eq!(contract.get_unvested_amount(four_years_vesting_schedule()).0, to_atto(700));
+        assert_eq!(contract.get_locked_amount().0, to_atto(700));
+
+        // The termination covers the grants, so the cancellation restores them as well.
+        contract.cancel_termination();
+        assert_eq!(contract.get_unvested_amount(four_years_vesting_schedule()).0, to_atto(700));
+        assert_eq!(contract.get_vesting_grants().len(), 1);
+    }
 
-        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+    #[test]
+    fn test_terminate_vesting_grant_without_vesting() {
+        let mut context = basic_context();
         testing_env!(context.clone());
-        assert_eq!(contract.get_owners_balance().0, 0);
-        assert_eq!(contract.get_liquid_owners_balance().0, 0);
-        assert_eq!(
-            contract.get_locked_vested_amount(no_vesting_schedule()).0,
-            to_atto(1000)
-        );
-        assert_eq!(contract.get_locked_amount().0, to_atto(1000));
-        assert_eq!(
-            contract.get_unvested_amount(no_vesting_schedule()).0,
-            to_atto(0)
-        );
+        let mut contract = new_contract(true, None, None, true);
+
+        context.predecessor_account_id = account_foundation();
+        context.attached_deposit = UncToken::from_attounc(to_atto(400));
+        context.account_balance = UncToken::from_attounc(to_atto(LOCKUP_UNC + 400));
+        testing_env!(context.clone());
+        contract.add_vesting_grant(new_vesting_schedule(0), to_atto(400).into());
+        assert!(contract.is_terminable());
+
+        context.attached_deposit = UncToken::from_attounc(0);
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR * 2);
+        testing_env!(context.clone());
+        contract.terminate_vesting(None);
+        assert_eq!(contract.get_terminated_unvested_balance().0, to_atto(100));
+        assert_eq!(contract.get_locked_amount().0, to_atto(100));
+        assert_eq!(contract.get_owners_balance().0, to_atto(LOCKUP_UNC + 300));
     }
 
     #[test]
-    fn test_lock_timestmap_transfer_enabled() {
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_add_vesting_grant_by_owner() {
         let mut context = basic_context();
-        testing_env!(context.clone());
-        let contract = LockupContract::new(
-            account_owner(),
-            0.into(),
-            Some(to_ts(GENESIS_TIME_IN_DAYS + YEAR).into()),
-            TransfersInformation::TransfersEnabled {
-                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS + YEAR / 2).into(),
-            },
-            None,
-            None,
-           "whitelist".parse().unwrap(),
-            None,
-        );
+        let mut contract = new_contract_with_vesting_grant(&mut context);
 
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
-        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.attached_deposit = UncToken::from_attounc(to_atto(100));
         testing_env!(context.clone());
-        assert_eq!(contract.get_owners_balance().0, to_atto(1000));
-        assert_eq!(
-            contract.get_liquid_owners_balance().0,
-            to_atto(1000) - MIN_BALANCE_FOR_STORAGE
-        );
-        assert_eq!(
-            contract.get_locked_vested_amount(no_vesting_schedule()).0,
-            to_atto(0)
-        );
-        assert_eq!(contract.get_locked_amount().0, to_atto(0));
-        assert_eq!(
-            contract.get_unvested_amount(no_vesting_schedule()).0,
-            to_atto(0)
-        );
+        contract.add_vesting_grant(new_vesting_schedule(0), to_atto(100).into());
     }
 
     #[test]
-    fn test_termination_no_staking() {
+    #[should_panic(expected = "The attached deposit should be equal to the grant amount")]
+    fn test_add_vesting_grant_without_deposit() {
         let mut context = basic_context();
+        let mut contract = new_contract_with_vesting_grant(&mut context);
+
+        context.predecessor_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.add_vesting_grant(new_vesting_schedule(0), to_atto(100).into());
+    }
+
+    fn terminating_contract_with_staking_pool(context: &mut VMContext) -> LockupContract {
         testing_env!(context.clone());
         let vesting_schedule = new_vesting_schedule(0);
-        let mut contract = LockupContract::new(
-            account_owner(),
-            to_nanos(YEAR).into(),
-            None,
-            TransfersInformation::TransfersEnabled {
-                transfers_timestamp: to_ts(GENESIS_TIME_IN_DAYS).into(),
-            },
-            Some(VestingScheduleOrHash::VestingSchedule(
-                vesting_schedule.clone(),
-            )),
-            None,
-           "whitelist".parse().unwrap(),
-            Some(account_foundation()),
-        );
+        let mut contract = new_contract(true, Some(vesting_schedule.clone()), None, true);
+        contract.staking_information = Some(StakingInformation {
+            staking_pool_account_id: "staking_pool".parse().unwrap(),
+            status: TransactionStatus::Idle,
+            deposit_amount: 0.into(),
+        });
 
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + 2 * YEAR);
+        context.predecessor_account_id = account_foundation();
         testing_env!(context.clone());
-        assert_eq!(
-            contract.get_vesting_information(),
-            VestingInformation::VestingSchedule(vesting_schedule.clone())
-        );
-        assert_eq!(contract.get_owners_balance().0, 0);
-        assert_eq!(contract.get_liquid_owners_balance().0, 0);
-        assert_eq!(
-            contract
-                .get_locked_vested_amount(vesting_schedule.clone())
-                .0,
-            to_atto(250)
-        );
-        assert_eq!(contract.get_locked_amount().0, to_atto(1000));
-        assert_eq!(
-            contract.get_unvested_amount(vesting_schedule.clone()).0,
-            to_atto(750)
-        );
+        contract.terminate_vesting(Some(VestingScheduleWithSalt {
+            vesting_schedule,
+            salt: SALT.to_vec().into(),
+        }));
 
-        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        assert_eq!(contract.get_owners_balance().0, to_atto(500));
-        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(500));
-        assert_eq!(
-            contract
-                .get_locked_vested_amount(vesting_schedule.clone())
-                .0,
-            to_atto(0)
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "All operations are blocked until vesting termination is completed")]
+    fn test_select_staking_pool_during_termination() {
+        let mut context = basic_context();
+        let mut contract = terminating_contract_with_staking_pool(&mut context);
+        contract.staking_information = None;
+
+        contract.select_staking_pool("staking_pool".parse().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "All operations are blocked until vesting termination is completed")]
+    fn test_stake_during_termination() {
+        let mut context = basic_context();
+        let mut contract = terminating_contract_with_staking_pool(&mut context);
+
+        contract.stake(to_atto(100).into());
+    }
+
+    #[test]
+    #[should_panic(expected = "All operations are blocked until vesting termination is completed")]
+    fn test_withdraw_from_staking_pool_during_termination() {
+        let mut context = basic_context();
+        let mut contract = terminating_contract_with_staking_pool(&mut context);
+
+        contract.withdraw_from_staking_pool(to_atto(100).into());
+    }
+
+    #[test]
+    fn test_stake_after_termination() {
+        let mut context = basic_context();
+        let mut contract = terminating_contract_with_staking_pool(&mut context);
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(
+            context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
         );
-        assert_eq!(contract.get_locked_amount().0, to_atto(500));
-        assert_eq!(
-            contract.get_unvested_amount(vesting_schedule.clone()).0,
-            to_atto(500)
+        contract.on_withdraw_unvested_amount(to_atto(250).into(), "unc".parse().unwrap());
+        assert_eq!(contract.get_vesting_information(), VestingInformation::None);
+
+        // The owner can use the staking pool again once the termination is completed.
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.stake(to_atto(100).into());
+        assert!(
+            contract.staking_information.as_ref().unwrap().status == TransactionStatus::Busy
         );
+    }
 
-        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + 2 * YEAR);
+    #[test]
+    #[should_panic(expected = "Contract is currently busy with another operation")]
+    fn test_termination_prepare_to_withdraw_while_busy() {
+        let mut context = basic_context();
+        let mut contract = terminating_contract_with_staking_pool(&mut context);
+        contract.set_termination_status(TerminationStatus::VestingTerminatedWithDeficit);
+
+        context.predecessor_account_id = account_foundation();
         testing_env!(context.clone());
-        assert_eq!(contract.get_owners_balance().0, to_atto(750));
-        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(750));
-        assert_eq!(contract.get_locked_amount().0, to_atto(250));
-        assert_eq!(
-            contract.get_unvested_amount(vesting_schedule.clone()).0,
-            to_atto(250)
+        contract.termination_prepare_to_withdraw();
+        assert!(
+            contract.staking_information.as_ref().unwrap().status == TransactionStatus::Busy
         );
 
-        // Terminating
-        context.view_config = None;
+        // The unstaking is still in flight.
+        contract.termination_prepare_to_withdraw();
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is currently busy with another operation")]
+    fn test_termination_withdraw_while_busy() {
+        let mut context = basic_context();
+        let mut contract = terminating_contract_with_staking_pool(&mut context);
+        contract.set_staking_pool_status(TransactionStatus::Busy);
+
         context.predecessor_account_id = account_foundation();
-        context.signer_account_pk = public_key(3).into();
         testing_env!(context.clone());
-        contract.terminate_vesting(None);
+        contract.termination_withdraw(account_foundation());
+    }
 
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+    #[test]
+    fn test_termination_prepare_to_withdraw_idle_after_callback() {
+        let mut context = basic_context();
+        let mut contract = terminating_contract_with_staking_pool(&mut context);
+        contract.set_termination_status(TerminationStatus::VestingTerminatedWithDeficit);
+
+        context.predecessor_account_id = account_foundation();
         testing_env!(context.clone());
-        assert_eq!(
-            contract.get_vesting_information(),
-            VestingInformation::Terminating(TerminationInformation {
-                unvested_amount: to_atto(250).into(),
-                status: TerminationStatus::ReadyToWithdraw,
-            })
-        );
-        assert_eq!(contract.get_owners_balance().0, to_atto(750));
-        assert_eq!(contract.get_liquid_owners_balance().0, to_atto(750));
-        assert_eq!(contract.get_locked_amount().0, to_atto(250));
-        assert_eq!(
-            contract.get_unvested_amount(vesting_schedule.clone()).0,
-            to_atto(250)
-        );
-        assert_eq!(contract.get_terminated_unvested_balance().0, to_atto(250));
-        assert_eq!(
-            contract.get_terminated_unvested_balance_deficit().0,
-            to_atto(0)
-        );
+        contract.termination_prepare_to_withdraw();
         assert_eq!(
             contract.get_termination_status(),
-            Some(TerminationStatus::ReadyToWithdraw)
+            Some(TerminationStatus::UnstakingInProgress)
         );
 
-        // Withdrawing
-        context.view_config = None;
-        testing_env!(context.clone());
-        let receiver_id: AccountId = "unc".parse().unwrap();
-        contract.termination_withdraw(receiver_id.clone());
-        context.account_balance = env::account_balance();
-
         context.predecessor_account_id = lockup_account();
         testing_env!(
             context.clone(),
@@ -1273,22 +3465,23 @@ mod tests {
             Default::default(),
             vec![PromiseResult::Successful(vec![])],
         );
-        contract.on_withdraw_unvested_amount(to_atto(250).into(), receiver_id);
-
-        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
-        testing_env!(context.clone());
-        assert_eq!(contract.get_owners_balance().0, to_atto(750));
+        assert!(contract.on_staking_pool_unstake_for_termination(to_atto(100).into()));
+        assert!(
+            contract.staking_information.as_ref().unwrap().status == TransactionStatus::Idle
+        );
         assert_eq!(
-            contract.get_liquid_owners_balance().0,
-            to_atto(750) - MIN_BALANCE_FOR_STORAGE
+            contract.get_termination_status(),
+            Some(TerminationStatus::EverythingUnstaked)
         );
+
+        // The next termination step can start.
+        context.predecessor_account_id = account_foundation();
+        testing_env!(context.clone());
+        contract.termination_prepare_to_withdraw();
         assert_eq!(
-            contract.get_unvested_amount(vesting_schedule.clone()).0,
-            to_atto(0)
+            contract.get_termination_status(),
+            Some(TerminationStatus::WithdrawingFromStakingPoolInProgress)
         );
-        assert_eq!(contract.get_terminated_unvested_balance().0, to_atto(0));
-        assert_eq!(contract.get_termination_status(), None);
-        assert_eq!(contract.get_vesting_information(), VestingInformation::None);
     }
 
     #[test]
@@ -1348,6 +3541,48 @@ mod tests {
         assert_eq!(contract.get_locked_amount().0, to_atto(250));
     }
 
+    #[test]
+    fn test_release_duration_locked_amount_decreases() {
+        let mut context = basic_context();
+        testing_env!(context.clone());
+        let contract = new_contract(true, None, Some(to_nanos(4 * YEAR).into()), false);
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+
+        // The release starts after the 1 year lockup and ends 4 years later.
+        let mut last_locked_amount = to_atto(LOCKUP_UNC);
+        for days in (0..=6 * YEAR).step_by(30) {
+            context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + days);
+            testing_env!(context.clone());
+            let locked_amount = contract.get_locked_amount().0;
+            assert!(locked_amount <= last_locked_amount);
+            last_locked_amount = locked_amount;
+        }
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        testing_env!(context.clone());
+        assert_eq!(contract.get_locked_amount().0, to_atto(LOCKUP_UNC));
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + 3 * YEAR);
+        testing_env!(context.clone());
+        assert_eq!(contract.get_locked_amount().0, to_atto(LOCKUP_UNC) / 2);
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + 5 * YEAR);
+        testing_env!(context.clone());
+        assert_eq!(contract.get_locked_amount().0, 0);
+    }
+
+    #[test]
+    fn test_no_release_duration_unlocks_at_lockup_end() {
+        let (mut context, contract) = lockup_only_setup();
+        context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR) - 1;
+        testing_env!(context.clone());
+        assert_eq!(contract.get_locked_amount().0, to_atto(LOCKUP_UNC));
+
+        context.block_timestamp = to_ts(GENESIS_TIME_IN_DAYS + YEAR);
+        testing_env!(context.clone());
+        assert_eq!(contract.get_locked_amount().0, 0);
+    }
+
     #[test]
     fn test_vesting_and_release_duration() {
         let mut context = basic_context();
@@ -1464,6 +3699,8 @@ mod tests {
             Some(to_nanos(4 * YEAR).into()),
             "whitelist".parse().unwrap(),
             Some(account_foundation()),
+            None,
+            None,
         );
 
         context.view_config = Some(ViewConfig { max_gas_burnt: 200000000000000 });
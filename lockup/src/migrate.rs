@@ -0,0 +1,67 @@
+use unc_sdk::store::IterableSet;
+use unc_sdk::{assert_self, unc, AccountId, Gas, Promise};
+
+use crate::*;
+
+/// The layout of the contract state before the storage reserve, the foundation signers, the
+/// termination gas, the proposed owner, the vesting information before the termination and the
+/// vesting grants were added.
+#[unc(serializers = [borsh])]
+pub struct OldLockupContract {
+    pub owner_account_id: AccountId,
+    pub lockup_information: LockupInformation,
+    pub vesting_information: VestingInformation,
+    pub staking_pool_whitelist_account_id: AccountId,
+    pub staking_information: Option<StakingInformation>,
+    pub foundation_account_id: Option<AccountId>,
+}
+
+#[unc]
+impl LockupContract {
+    /// Migrates the state from the previous layout after the contract code is upgraded.
+    /// Should be called with the `deploy_contract` action in the same transaction.
+    /// The lockup, vesting and staking information is kept as is, including the termination in
+    /// progress, and the vesting schedule stays the only vesting grant. The new fields get their
+    /// default values.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        assert_self();
+        let old_state: OldLockupContract =
+            env::state_read().expect("The contract is not initialized");
+
+        Self {
+            owner_account_id: old_state.owner_account_id,
+            lockup_information: old_state.lockup_information,
+            vesting_information: old_state.vesting_information,
+            staking_pool_whitelist_account_id: old_state.staking_pool_whitelist_account_id,
+            staking_information: old_state.staking_information,
+            foundation_account_id: old_state.foundation_account_id,
+            storage_reserve: MIN_BALANCE_FOR_STORAGE,
+            foundation_signers: IterableSet::new(b"s".to_vec()),
+            termination_unstake_gas: Gas::from_gas(gas::staking_pool::UNSTAKE),
+            termination_withdraw_gas: Gas::from_gas(gas::staking_pool::WITHDRAW),
+            proposed_owner_account_id: None,
+            terminated_vesting_information: None,
+            vesting_grants: Vec::new(),
+        }
+    }
+
+    /// Deploys the given contract code and migrates the state in the same transaction.
+    /// Can only be called by the lockup factory, which is the parent account of this lockup and
+    /// only forwards the upgrades approved by the foundation. The owner can't upgrade the lockup,
+    /// since the new code could release the locked and unvested tokens.
+    pub fn upgrade(&mut self, code: Vec<u8>) -> Promise {
+        let current_account_id = env::current_account_id();
+        assert_eq!(
+            Some(env::predecessor_account_id().as_ref()),
+            current_account_id.get_parent_account_id(),
+            "Can only be called by the lockup factory"
+        );
+        Promise::new(current_account_id).deploy_contract(code).function_call(
+            "migrate".to_string(),
+            vec![],
+            NO_DEPOSIT,
+            Gas::from_gas(gas::upgrade::MIGRATE),
+        )
+    }
+}
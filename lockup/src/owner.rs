@@ -42,17 +42,16 @@ impl LockupContract {
 
     /// OWNER'S METHOD
     ///
-    /// Requires 25 TGas (1 * BASE_GAS)
+    /// Requires 75 TGas (3 * BASE_GAS)
     ///
     /// Unselects the current staking pool.
     /// It requires that there are no known deposits left on the currently selected staking pool.
-    pub fn unselect_staking_pool(&mut self) {
+    /// The total balance of this account on the staking pool is queried first and the staking pool
+    /// is only unselected if both the staked and the unstaked balances there are zero.
+    pub fn unselect_staking_pool(&mut self) -> Promise {
         self.assert_owner();
         self.assert_staking_pool_is_idle();
         self.assert_no_termination();
-        // NOTE: This is best effort checks. There is still some balance might be left on the
-        // staking pool, but it's up to the owner whether to unselect the staking pool.
-        // The contract doesn't care about leftovers.
         assert_eq!(
             self.staking_information.as_ref().unwrap().deposit_amount.0,
             0,
@@ -61,7 +60,7 @@ impl LockupContract {
 
         env::log_str(
             format!(
-                "Unselected current staking pool @{}.",
+                "Unselecting current staking pool @{}. Going to check the total balance first.",
                 self.staking_information
                     .as_ref()
                     .unwrap()
@@ -70,7 +69,25 @@ impl LockupContract {
             .as_str(),
         );
 
-        self.staking_information = None;
+        self.set_staking_pool_status(TransactionStatus::Busy);
+
+        ext_staking_pool::ext(self
+                .staking_information
+                .as_ref()
+                .unwrap()
+                .staking_pool_account_id
+                .clone()
+            )
+            .with_static_gas(Gas::from_gas(gas::staking_pool::GET_ACCOUNT_TOTAL_BALANCE))
+            .with_attached_deposit(NO_DEPOSIT)
+            .get_account_total_balance(
+                env::current_account_id()
+        )
+        .then(ext_self_owner::ext(env::current_account_id())
+            .with_static_gas(Gas::from_gas(gas::owner_callbacks::ON_GET_ACCOUNT_TOTAL_BALANCE_TO_UNSELECT))
+            .with_attached_deposit(NO_DEPOSIT)
+            .on_get_account_total_balance_to_unselect()
+        )
     }
 
     /// OWNER'S METHOD
@@ -137,38 +154,7 @@ impl LockupContract {
             "The balance that can be deposited to the staking pool is lower than the extra amount"
         );
 
-        env::log_str(
-            format!(
-                "Depositing and staking {} to the staking pool @{}",
-                amount.0,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
-            )
-            .as_str(),
-        );
-
-        self.set_staking_pool_status(TransactionStatus::Busy);
-
-        ext_staking_pool::ext(self
-                .staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
-                .clone()
-            )
-            .with_static_gas(Gas::from_gas(gas::staking_pool::DEPOSIT_AND_STAKE))
-            .with_attached_deposit(UncToken::from_attounc(amount.0))
-            .deposit_and_stake(
-                //amount.0,
-        )
-        .then(ext_self_owner::ext(env::current_account_id())
-            .with_static_gas(Gas::from_gas(gas::owner_callbacks::ON_STAKING_POOL_DEPOSIT_AND_STAKE))
-            .with_attached_deposit(NO_DEPOSIT)
-            .on_staking_pool_deposit_and_stake(
-                amount,
-        ))
+        self.internal_deposit_and_stake(amount)
     }
 
     /// OWNER'S METHOD
@@ -197,16 +183,17 @@ impl LockupContract {
 
         self.set_staking_pool_status(TransactionStatus::Busy);
 
-        ext_staking_pool::ext(env::current_account_id())
+        ext_staking_pool::ext(self
+                .staking_information
+                .as_ref()
+                .unwrap()
+                .staking_pool_account_id
+                .clone()
+            )
             .with_static_gas(Gas::from_gas(gas::staking_pool::GET_ACCOUNT_TOTAL_BALANCE))
             .with_attached_deposit(NO_DEPOSIT)
             .get_account_total_balance(
-                self
-                    .staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
-                    .clone()
+                env::current_account_id()
         )
         .then(ext_self_owner::ext(env::current_account_id())
             .with_static_gas(Gas::from_gas(gas::owner_callbacks::ON_GET_ACCOUNT_TOTAL_BALANCE))
@@ -283,16 +270,17 @@ impl LockupContract {
 
         self.set_staking_pool_status(TransactionStatus::Busy);
 
-        ext_staking_pool::ext(env::current_account_id())
+        ext_staking_pool::ext(self
+                .staking_information
+                .as_ref()
+                .unwrap()
+                .staking_pool_account_id
+                .clone()
+            )
             .with_static_gas(Gas::from_gas(gas::staking_pool::GET_ACCOUNT_UNSTAKED_BALANCE))
             .with_attached_deposit(NO_DEPOSIT)
             .get_account_unstaked_balance(
-                self
-                    .staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
-                    .clone()
+                env::current_account_id()
         )
         .then(
             ext_self_owner::ext(env::current_account_id())
@@ -303,6 +291,53 @@ impl LockupContract {
         )
     }
 
+    /// OWNER'S METHOD
+    ///
+    /// Requires 225 TGas (9 * BASE_GAS)
+    ///
+    /// Exits the staking pool in one flow, e.g. for a grant winddown. Queries the staked balance
+    /// from the staking pool first:
+    /// 1. If there is staked balance, unstakes all of it;
+    /// 2. Otherwise, withdraws all unstaked balance, which requires it to be unlocked.
+    /// So it should be invoked twice, the second time after 4 epochs (48 hours).
+    pub fn unstake_and_withdraw_all_from_staking_pool(&mut self) -> Promise {
+        self.assert_owner();
+        self.assert_staking_pool_is_idle();
+        self.assert_no_termination();
+
+        env::log_str(
+            format!(
+                "Going to query the staked balance at the staking pool @{}",
+                self.staking_information
+                    .as_ref()
+                    .unwrap()
+                    .staking_pool_account_id
+            )
+            .as_str(),
+        );
+
+        self.set_staking_pool_status(TransactionStatus::Busy);
+
+        ext_staking_pool::ext(self
+                .staking_information
+                .as_ref()
+                .unwrap()
+                .staking_pool_account_id
+                .clone()
+            )
+            .with_static_gas(Gas::from_gas(gas::staking_pool::GET_ACCOUNT_STAKED_BALANCE))
+            .with_attached_deposit(NO_DEPOSIT)
+            .get_account_staked_balance(
+                env::current_account_id()
+        )
+        .then(
+            ext_self_owner::ext(env::current_account_id())
+                .with_static_gas(Gas::from_gas(gas::owner_callbacks::ON_GET_ACCOUNT_STAKED_BALANCE_TO_EXIT))
+                .with_attached_deposit(NO_DEPOSIT)
+                .on_get_account_staked_balance_to_exit(),
+        )
+    }
+
     /// OWNER'S METHOD
     ///
     /// Requires 125 TGas (5 * BASE_GAS)
@@ -445,7 +480,7 @@ impl LockupContract {
         let transfer_poll_account_id = match &self.lockup_information.transfers_information {
             TransfersInformation::TransfersDisabled {
                 transfer_poll_account_id,
-            } => transfer_poll_account_id,
+            } => transfer_poll_account_id.clone(),
             _ => unreachable!(),
         };
 
@@ -456,12 +491,10 @@ impl LockupContract {
             ).as_str(),
         );
 
-        ext_transfer_poll::ext(env::current_account_id())
+        ext_transfer_poll::ext(transfer_poll_account_id)
             .with_static_gas(Gas::from_gas(gas::transfer_poll::GET_RESULT))
             .with_attached_deposit(NO_DEPOSIT)
-            .get_result(
-            //&transfer_poll_account_id,
-        )
+            .get_result()
         .then(ext_self_owner::ext(env::current_account_id())
             .with_static_gas(Gas::from_gas(gas::owner_callbacks::ON_VOTING_GET_RESULT))
             .with_attached_deposit(NO_DEPOSIT)
@@ -498,6 +531,33 @@ impl LockupContract {
         Promise::new(receiver_id).transfer(UncToken::from_attounc(amount.0))
     }
 
+    /// OWNER'S METHOD
+    ///
+    /// Requires 50 TGas (2 * BASE_GAS)
+    /// Not intended to hand over the access to someone else except the owner
+    ///
+    /// Transfers the tokens above the lockup amount, e.g. sent to this account by mistake, to the
+    /// given receiver account ID. It never transfers locked or unvested tokens.
+    pub fn withdraw_unaccounted(&mut self, receiver_id: AccountId) -> Promise {
+        self.assert_owner();
+        assert!(
+            env::is_valid_account_id(receiver_id.as_bytes()),
+            "The receiver account ID is invalid"
+        );
+        self.assert_transfers_enabled();
+        self.assert_no_staking_or_idle();
+        self.assert_no_termination();
+        let amount = self.get_unaccounted_balance();
+        assert!(amount.0 > 0, "There is no unaccounted balance to withdraw");
+
+        env::log_str(
+            format!("Withdrawing unaccounted balance of {} to account @{}", amount.0, receiver_id)
+                .as_str(),
+        );
+
+        Promise::new(receiver_id).transfer(UncToken::from_attounc(amount.0))
+    }
+
     /// OWNER'S METHOD
     ///
     /// Requires 50 TGas (2 * BASE_GAS)
@@ -524,4 +584,29 @@ impl LockupContract {
 
         Promise::new(env::current_account_id()).add_full_access_key(new_public_key)
     }
+
+    /// OWNER'S METHOD
+    ///
+    /// Requires 25 TGas (1 * BASE_GAS)
+    ///
+    /// Proposes to reassign the ownership of this lockup to the given account, e.g. after a legal
+    /// entity change. The change only takes effect once approved by the UNC Foundation with
+    /// `approve_owner_change`. A new proposal replaces the previous one, and the foundation has to
+    /// approve it again.
+    pub fn propose_owner_change(&mut self, new_owner_account_id: AccountId) {
+        self.assert_owner();
+        assert!(
+            env::is_valid_account_id(new_owner_account_id.as_bytes()),
+            "The new owner account ID is invalid"
+        );
+        assert_ne!(
+            new_owner_account_id, self.owner_account_id,
+            "The new owner account ID should be different from the current one"
+        );
+
+        env::log_str(
+            format!("Proposing to reassign the ownership to @{}", new_owner_account_id).as_str(),
+        );
+        self.proposed_owner_account_id = Some(new_owner_account_id);
+    }
 }
@@ -1,5 +1,5 @@
 use crate::*;
-use unc_sdk::{unc, PromiseOrValue, assert_self, is_promise_success};
+use unc_sdk::{unc, PromiseOrValue, PromiseResult, assert_self, is_promise_success};
 
 #[unc]
 impl LockupContract {
@@ -24,6 +24,40 @@ impl LockupContract {
         true
     }
 
+    /// Called after the staking pool given on init was checked in the whitelist.
+    /// Selects the staking pool and deposits and stakes the account balance to it. If the staking
+    /// pool is not whitelisted, the lockup stays without a staking pool and `false` is returned.
+    pub fn on_whitelist_is_whitelisted_to_stake(
+        &mut self,
+        #[callback] is_whitelisted: bool,
+        staking_pool_account_id: AccountId,
+    ) -> PromiseOrValue<bool> {
+        assert_self();
+        if !is_whitelisted {
+            env::log_str(
+                format!(
+                    "The staking pool @{} is not whitelisted, the lockup is not staking",
+                    staking_pool_account_id
+                )
+                .as_str(),
+            );
+            return PromiseOrValue::Value(false);
+        }
+        self.assert_staking_pool_is_not_selected();
+        self.assert_no_termination();
+        self.staking_information = Some(StakingInformation {
+            staking_pool_account_id,
+            status: TransactionStatus::Idle,
+            deposit_amount: 0.into(),
+        });
+
+        let amount = self.get_account_balance();
+        if amount.0 == 0 {
+            return PromiseOrValue::Value(true);
+        }
+        self.internal_deposit_and_stake(amount).into()
+    }
+
     /// Called after a deposit amount was transferred out of this account to the staking pool.
     /// This method needs to update staking pool status.
     pub fn on_staking_pool_deposit(&mut self, amount: WrappedBalance) -> bool {
@@ -290,7 +324,67 @@ impl LockupContract {
             .as_str(),
         );
 
-        self.staking_information.as_mut().unwrap().deposit_amount = total_balance;
+        let staking_information = self.staking_information.as_mut().unwrap();
+        // The balance above the known deposit is the reward accrued on the staking pool. It
+        // becomes part of the owner's balance once it's recorded as deposited.
+        let rewards = total_balance.0.saturating_sub(staking_information.deposit_amount.0);
+        if rewards > 0 {
+            env::log_str(format!("Recognized staking rewards of {}", rewards).as_str());
+        }
+        staking_information.deposit_amount = total_balance;
+    }
+
+    /// Called after the request to get the current total balance from the staking pool to unselect
+    /// it. The staking pool is only unselected when nothing is left on it.
+    /// If the staking pool can't report the balance, the staking pool stays selected, so the funds
+    /// that might still be there are not lost. The owner can try to unselect it again.
+    pub fn on_get_account_total_balance_to_unselect(&mut self) -> bool {
+        assert_self();
+        self.set_staking_pool_status(TransactionStatus::Idle);
+
+        let staking_pool_account_id = self
+            .staking_information
+            .as_ref()
+            .unwrap()
+            .staking_pool_account_id
+            .clone();
+        let total_balance = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                unc_sdk::serde_json::from_slice::<WrappedBalance>(&result).ok()
+            }
+            PromiseResult::Failed => None,
+        };
+        match total_balance {
+            Some(total_balance) if total_balance.0 > 0 => {
+                env::log_str(
+                    format!(
+                        "The staking pool @{} still has a total balance of {}. Can't unselect it.",
+                        staking_pool_account_id, total_balance.0
+                    )
+                    .as_str(),
+                );
+                self.staking_information.as_mut().unwrap().deposit_amount = total_balance;
+                false
+            }
+            Some(_) => {
+                env::log_str(
+                    format!("Unselected current staking pool @{}.", staking_pool_account_id)
+                        .as_str(),
+                );
+                self.staking_information = None;
+                true
+            }
+            None => {
+                env::log_str(
+                    format!(
+                        "Failed to get the total balance from the staking pool @{}. Can't unselect it.",
+                        staking_pool_account_id
+                    )
+                    .as_str(),
+                );
+                false
+            }
+        }
     }
 
     /// Called after the request to get the current unstaked balance to withdraw everything by th
@@ -339,4 +433,78 @@ impl LockupContract {
             PromiseOrValue::Value(true)
         }
     }
+
+    /// Called after the request to get the current staked balance from the staking pool to exit
+    /// it. Unstakes everything if there is staked balance, otherwise proceeds with withdrawing the
+    /// unstaked balance.
+    /// If the staking pool can't report the balance, the lockup goes back to idle.
+    pub fn on_get_account_staked_balance_to_exit(&mut self) -> PromiseOrValue<bool> {
+        assert_self();
+
+        let staking_pool_account_id = self
+            .staking_information
+            .as_ref()
+            .unwrap()
+            .staking_pool_account_id
+            .clone();
+        let staked_balance = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                unc_sdk::serde_json::from_slice::<WrappedBalance>(&result).ok()
+            }
+            PromiseResult::Failed => None,
+        };
+        match staked_balance {
+            Some(staked_balance) if staked_balance.0 > 0 => {
+                env::log_str(
+                    format!(
+                        "Unstaking {} from the staking pool @{}. Withdraw it once it's unlocked",
+                        staked_balance.0, staking_pool_account_id
+                    )
+                    .as_str(),
+                );
+
+                ext_staking_pool::ext(staking_pool_account_id)
+                    .with_static_gas(Gas::from_gas(gas::staking_pool::UNSTAKE))
+                    .with_attached_deposit(NO_DEPOSIT)
+                    .unstake(staked_balance)
+                .then(ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(Gas::from_gas(gas::owner_callbacks::ON_STAKING_POOL_UNSTAKE))
+                    .with_attached_deposit(NO_DEPOSIT)
+                    .on_staking_pool_unstake(staked_balance),
+                )
+                .into()
+            }
+            Some(_) => {
+                env::log_str(
+                    format!(
+                        "Going to query the unstaked balance at the staking pool @{}",
+                        staking_pool_account_id
+                    )
+                    .as_str(),
+                );
+
+                ext_staking_pool::ext(staking_pool_account_id)
+                    .with_static_gas(Gas::from_gas(gas::staking_pool::GET_ACCOUNT_UNSTAKED_BALANCE))
+                    .with_attached_deposit(NO_DEPOSIT)
+                    .get_account_unstaked_balance(env::current_account_id())
+                .then(ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(Gas::from_gas(gas::owner_callbacks::ON_GET_ACCOUNT_UNSTAKED_BALANCE_TO_WITHDRAW_BY_OWNER))
+                    .with_attached_deposit(NO_DEPOSIT)
+                    .on_get_account_unstaked_balance_to_withdraw_by_owner(),
+                )
+                .into()
+            }
+            None => {
+                env::log_str(
+                    format!(
+                        "Failed to get the staked balance from the staking pool @{}",
+                        staking_pool_account_id
+                    )
+                    .as_str(),
+                );
+                self.set_staking_pool_status(TransactionStatus::Idle);
+                PromiseOrValue::Value(false)
+            }
+        }
+    }
 }
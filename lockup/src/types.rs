@@ -22,6 +22,17 @@ pub type WrappedBalance = U128;
 /// Hash of Vesting schedule.
 pub type Hash = Vec<u8>;
 
+/// A vesting tranche: the timestamp in nanosecond and the amount of tokens that becomes vested
+/// at this timestamp.
+pub type VestingTranche = (WrappedTimestamp, WrappedBalance);
+
+/// The maximum number of vesting tranches, to bound the storage and the gas of the getters.
+pub const MAX_VESTING_TRANCHES: usize = 100;
+
+/// The maximum number of additional vesting grants, to bound the storage and the gas of the
+/// getters.
+pub const MAX_VESTING_GRANTS: usize = 100;
+
 /// Contains information about token lockups.
 #[unc]
 pub struct LockupInformation {
@@ -52,7 +63,7 @@ pub struct LockupInformation {
 }
 
 /// Contains information about the transfers. Whether transfers are enabled or disabled.
-#[derive(Debug)]
+#[derive(Clone, PartialEq, Debug)]
 #[unc(serializers = [borsh, json])]
 pub enum TransfersInformation {
     /// The timestamp when the transfers were enabled.
@@ -120,6 +131,37 @@ impl VestingSchedule {
             "The total vesting time should be positive"
         );
     }
+
+    /// Returns the amount of the given total amount that is unvested at the given timestamp.
+    pub fn unvested_amount_at(&self, total_amount: u128, block_timestamp: Timestamp) -> u128 {
+        if block_timestamp < self.cliff_timestamp.0 {
+            // Before the cliff, nothing is vested
+            total_amount
+        } else if block_timestamp >= self.end_timestamp.0 {
+            // After the end, everything is vested
+            0
+        } else {
+            // cannot overflow since block_timestamp < vesting_schedule.end_timestamp
+            let time_left = U256::from(self.end_timestamp.0 - block_timestamp);
+            // The total time is positive. Checked at the contract initialization.
+            let total_time = U256::from(self.end_timestamp.0 - self.start_timestamp.0);
+            let unvested_amount = U256::from(total_amount) * time_left / total_time;
+            // The unvested amount can't be larger than total_amount because the
+            // time_left is smaller than total_time.
+            unvested_amount.as_u128()
+        }
+    }
+}
+
+/// An additional grant vested to the owner on its own schedule, on top of the vesting the lockup
+/// was initialized with.
+#[derive(Clone, PartialEq, Debug)]
+#[unc(serializers = [borsh, json])]
+pub struct VestingGrant {
+    /// The vesting schedule of the grant.
+    pub vesting_schedule: VestingSchedule,
+    /// The amount of tokens vested by the grant.
+    pub amount: WrappedBalance,
 }
 
 /// Initialization argument type to define the vesting schedule
@@ -132,6 +174,26 @@ pub enum VestingScheduleOrHash {
     VestingHash(Base64VecU8),
     /// The vesting schedule (public)
     VestingSchedule(VestingSchedule),
+    /// The vesting in discrete tranches (public), e.g. 25% at the end of every year.
+    VestingTranches(Vec<VestingTranche>),
+}
+
+/// Panics if the vesting tranches are empty, too many, not ordered by the timestamp or contain
+/// an empty tranche.
+pub fn assert_valid_vesting_tranches(tranches: &[VestingTranche]) {
+    assert!(!tranches.is_empty(), "The vesting tranches can't be empty");
+    assert!(
+        tranches.len() <= MAX_VESTING_TRANCHES,
+        "Too many vesting tranches"
+    );
+    assert!(
+        tranches.windows(2).all(|pair| pair[0].0 .0 < pair[1].0 .0),
+        "The vesting tranche timestamps should be increasing"
+    );
+    assert!(
+        tranches.iter().all(|(_, amount)| amount.0 > 0),
+        "The vesting tranche amount should be positive"
+    );
 }
 
 /// Contains information about vesting that contains vesting schedule and termination information.
@@ -151,6 +213,8 @@ pub enum VestingInformation {
     /// It means the termination of the vesting is currently in progress.
     /// Once the unvested amount is transferred out, `VestingInformation` is removed.
     Terminating(TerminationInformation),
+    /// Explicit vesting in discrete tranches.
+    VestingTranches(Vec<VestingTranche>),
 }
 
 /// Describes the status of transactions with the staking pool contract or terminated unvesting
@@ -204,3 +268,60 @@ impl VestingScheduleWithSalt {
         env::sha256(&borsh::to_vec(&self).expect("Failed to serialize"))
     }
 }
+
+/// The summary of the lockup state for tooling.
+#[derive(Debug)]
+#[unc(serializers = [json])]
+pub struct LockupInfo {
+    /// The account ID of the owner.
+    pub owner_account_id: AccountId,
+    /// The optional absolute lockup timestamp.
+    pub lockup_timestamp: Option<WrappedTimestamp>,
+    /// The lockup duration from the moment the transfers are enabled.
+    pub lockup_duration: WrappedDuration,
+    /// The optional duration of the linear release after the tokens are unlocked.
+    pub release_duration: Option<WrappedDuration>,
+    /// The information about the transfers.
+    pub transfers_information: TransfersInformation,
+    /// The vesting information including the termination status.
+    pub vesting_information: VestingInformation,
+    /// The current termination status or `None` in case of no termination.
+    pub termination_status: Option<TerminationStatus>,
+    /// The account ID of the selected staking pool.
+    pub staking_pool_account_id: Option<AccountId>,
+    /// The amount of tokens that were deposited to the staking pool.
+    pub known_deposited_balance: WrappedBalance,
+    /// The amount of tokens that are locked due to lockup or vesting.
+    pub locked_amount: WrappedBalance,
+    /// The amount of tokens the owner can transfer from the account.
+    pub liquid_owners_balance: WrappedBalance,
+    /// The amount of tokens that are not going to be vested due to the termination.
+    pub terminated_unvested_balance: WrappedBalance,
+}
+
+/// The raw balance breakdown of the lockup account for auditing.
+#[derive(Debug)]
+#[unc(serializers = [json])]
+pub struct BalanceBreakdown {
+    /// The balance of the account, including the storage reserve.
+    pub account_balance: WrappedBalance,
+    /// The balance of the account and the tokens deposited to the staking pool.
+    pub total_balance: WrappedBalance,
+    /// The amount of tokens that are locked due to lockup or vesting.
+    pub locked_amount: WrappedBalance,
+    /// The amount of tokens that are not vested yet. It's `0` for the hashed vesting schedule,
+    /// which is private.
+    pub unvested_amount: WrappedBalance,
+    /// The amount of tokens that are not going to be vested due to the termination.
+    pub terminated_unvested_balance: WrappedBalance,
+    /// The amount of tokens that were deposited to the staking pool.
+    pub known_deposited_balance: WrappedBalance,
+    /// The amount of tokens withdrawn by the foundation due to the termination.
+    pub termination_withdrawn_tokens: WrappedBalance,
+    /// The amount of tokens kept in the account to cover the storage.
+    pub storage_reserve: WrappedBalance,
+    /// The balance of the owner: the total balance excluding the locked amount.
+    pub owners_balance: WrappedBalance,
+    /// The amount of tokens the owner can transfer from the account.
+    pub liquid_owners_balance: WrappedBalance,
+}
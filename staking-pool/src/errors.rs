@@ -0,0 +1,142 @@
+use std::fmt;
+
+/// The errors the staking pool panics with. The messages are stable, so integration tests and
+/// downstream tooling can match them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StakingError {
+    AlreadyInitialized,
+    InvalidOwnerAccountId,
+    StakingAtInitialization,
+    NotOwner,
+    NotSelf,
+    NotCallback,
+    NoCallbackResult,
+    NotAllowedInRestrictedMode,
+    MaxNumAccountsReached,
+    ZeroWithdrawalAmount,
+    NotEnoughUnstakedBalanceToWithdraw,
+    UnstakedBalanceNotAvailable,
+    ZeroStakingAmount,
+    ZeroStakeSharesToStake,
+    ZeroStakedAmount,
+    NotEnoughUnstakedBalanceToStake,
+    ZeroUnstakingAmount,
+    NoStakedBalance,
+    ZeroStakeSharesToUnstake,
+    NotEnoughStakedBalanceToUnstake,
+    TotalBalanceDecreased,
+    ZeroTotalStakedBalance,
+    ZeroTotalStakeShares,
+    StakeAmountBelowMinimum,
+    MaxTotalStakedBalanceExceeded,
+    ZeroEpochsPerYear,
+    ZeroRewardAmount,
+    NotOwnerOrAccount,
+    TooManyAccountsToWithdraw,
+    NotProposedOwner,
+    StakingPaused,
+    StakingAlreadyPaused,
+    StakingNotPaused,
+    ZeroRewardFeeDenominator,
+    RewardFeeAboveOne,
+    RewardFeeTooHigh,
+    TooManyFeeSplitRecipients,
+    FeeSplitNotSummingToOne,
+    NumEpochsToUnlockOutOfRange,
+    ZeroSharesToGift,
+    GiftToOwner,
+    NotEnoughSharesToGift,
+    NoSlashing,
+    NoSurplusReceiver,
+    UnstakedBalancesMismatch,
+    BalancesExceedLastTotalBalance,
+    AccountSharesExceedTotalStakeShares,
+    InvalidVotingAccountId,
+}
+
+impl AsRef<str> for StakingError {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::AlreadyInitialized => "Already initialized",
+            Self::InvalidOwnerAccountId => "The owner account ID is invalid",
+            Self::StakingAtInitialization => {
+                "The staking pool shouldn't be staking at the initialization"
+            }
+            Self::NotOwner => "Can only be called by the owner",
+            Self::NotSelf => "Can be called only by the contract itself",
+            Self::NotCallback => "Can be called only as a callback",
+            Self::NoCallbackResult => "Contract expected a result on the callback",
+            Self::NotAllowedInRestrictedMode => {
+                "The account is not allowed to deposit in the restricted mode"
+            }
+            Self::MaxNumAccountsReached => "The maximum number of accounts is reached",
+            Self::ZeroWithdrawalAmount => "Withdrawal amount should be positive",
+            Self::NotEnoughUnstakedBalanceToWithdraw => "Not enough unstaked balance to withdraw",
+            Self::UnstakedBalanceNotAvailable => {
+                "The unstaked balance is not yet available due to unstaking delay"
+            }
+            Self::ZeroStakingAmount => "Staking amount should be positive",
+            Self::ZeroStakeSharesToStake => {
+                "The calculated number of \"stake\" shares received for staking should be positive"
+            }
+            Self::ZeroStakedAmount => {
+                "Invariant violation. Calculated staked amount must be positive, because \"stake\" share price should be at least 1"
+            }
+            Self::NotEnoughUnstakedBalanceToStake => "Not enough unstaked balance to stake",
+            Self::ZeroUnstakingAmount => "Unstaking amount should be positive",
+            Self::NoStakedBalance => "The contract doesn't have staked balance",
+            Self::ZeroStakeSharesToUnstake => {
+                "Invariant violation. The calculated number of \"stake\" shares for unstaking should be positive"
+            }
+            Self::NotEnoughStakedBalanceToUnstake => "Not enough staked balance to unstake",
+            Self::TotalBalanceDecreased => {
+                "The new total balance should not be less than the old total balance"
+            }
+            Self::ZeroTotalStakedBalance => "The total staked balance can't be 0",
+            Self::ZeroTotalStakeShares => "The total number of stake shares can't be 0",
+            Self::StakeAmountBelowMinimum => {
+                "The staking amount is less than the minimum stake amount"
+            }
+            Self::MaxTotalStakedBalanceExceeded => {
+                "The staking would exceed the maximum total staked balance"
+            }
+            Self::ZeroEpochsPerYear => "The number of epochs per year should be positive",
+            Self::ZeroRewardAmount => "Reward amount should be positive",
+            Self::NotOwnerOrAccount => "Can only be called by the owner or the account itself",
+            Self::TooManyAccountsToWithdraw => "Too many accounts to withdraw at once",
+            Self::NotProposedOwner => "Can only be called by the proposed owner",
+            Self::StakingPaused => "The staking is paused",
+            Self::StakingAlreadyPaused => "The staking is already paused",
+            Self::StakingNotPaused => "The staking is not paused",
+            Self::ZeroRewardFeeDenominator => "Denominator must be a positive number",
+            Self::RewardFeeAboveOne => "The reward fee must be less or equal to 1",
+            Self::RewardFeeTooHigh => "The reward fee is too high",
+            Self::TooManyFeeSplitRecipients => "The reward fee is split among too many recipients",
+            Self::FeeSplitNotSummingToOne => "The fee split fractions must sum to 1",
+            Self::NumEpochsToUnlockOutOfRange => "The number of epochs to unlock is out of range",
+            Self::ZeroSharesToGift => "The number of shares to gift should be positive",
+            Self::GiftToOwner => "Can't gift shares to the owner",
+            Self::NotEnoughSharesToGift => "The owner doesn't have enough shares to gift",
+            Self::NoSlashing => {
+                "The total balance is not less than the last total balance, there is no slashing"
+            }
+            Self::NoSurplusReceiver => "The receiver of the surplus should be specified",
+            Self::UnstakedBalancesMismatch => {
+                "The unstaked balances of the accounts don't add up to the total unstaked balance"
+            }
+            Self::BalancesExceedLastTotalBalance => {
+                "The unstaked balances and the total staked balance exceed the last total balance"
+            }
+            Self::AccountSharesExceedTotalStakeShares => {
+                "The stake shares of the accounts exceed the total number of stake shares"
+            }
+            Self::InvalidVotingAccountId => "Invalid voting account ID",
+        }
+    }
+}
+
+impl fmt::Display for StakingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
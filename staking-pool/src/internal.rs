@@ -6,10 +6,23 @@ impl StakingContract {
     /********************/
 
     /// Restakes the current `total_staked_balance` again.
+    /// The stake action sets an absolute amount, so if the last stake action already sent
+    /// `total_staked_balance` there is nothing to change and no stake action is issued.
     pub(crate) fn internal_restake(&mut self) {
+        if self.last_staked_balance == Some(self.total_staked_balance) {
+            return;
+        }
+        self.internal_stake_action();
+    }
+
+    /// Issues the stake action for the current `total_staked_balance` unconditionally.
+    /// Used when the staking key changes or the staking resumes, since the protocol stake has to be
+    /// refreshed even if the amount is the same.
+    pub(crate) fn internal_stake_action(&mut self) {
         if self.paused {
             return;
         }
+        self.last_staked_balance = Some(self.total_staked_balance);
         // Stakes with the staking public key. If the public key is invalid the entire function
         // call will be rolled back.
         Promise::new(env::current_account_id())
@@ -22,38 +35,73 @@ impl StakingContract {
     }
 
     pub(crate) fn internal_deposit(&mut self) -> u128 {
-        let account_id = env::predecessor_account_id();
-        let mut account = self.internal_get_account(&account_id);
+        self.internal_deposit_for(&env::predecessor_account_id())
+    }
+
+    /// Credits the attached deposit to the unstaked balance of the given account.
+    pub(crate) fn internal_deposit_for(&mut self, account_id: &AccountId) -> u128 {
+        assert!(
+            !self.restricted_mode || self.delegators.contains(account_id),
+            "{}",
+            StakingError::NotAllowedInRestrictedMode
+        );
+        if let Some(max_num_accounts) = self.max_num_accounts {
+            assert!(
+                self.accounts.contains_key(account_id)
+                    || u64::from(self.accounts.len()) < max_num_accounts,
+                "{}",
+                StakingError::MaxNumAccountsReached
+            );
+        }
+        let mut account = self.internal_get_account(account_id);
         let amount = env::attached_deposit();
         account.unstaked = account.unstaked.saturating_add(amount);
-        self.internal_save_account(&account_id, &account);
+        self.internal_save_account(account_id, &account);
         self.last_total_balance = self.last_total_balance.saturating_add(amount);
 
-        env::log_str(
-            format!(
-                "@{} deposited {}. New unstaked balance is {}",
-                account_id, amount, account.unstaked
-            )
-            .as_str(),
-        );
+        let predecessor_account_id = env::predecessor_account_id();
+        if &predecessor_account_id == account_id {
+            env::log_str(
+                format!(
+                    "@{} deposited {}. New unstaked balance is {}",
+                    account_id, amount, account.unstaked
+                )
+                .as_str(),
+            );
+        } else {
+            env::log_str(
+                format!(
+                    "@{} deposited {} for @{}. New unstaked balance is {}",
+                    predecessor_account_id, amount, account_id, account.unstaked
+                )
+                .as_str(),
+            );
+        }
         amount.as_attounc()
     }
 
-    pub(crate) fn internal_withdraw(&mut self, amount: UncToken) {
-        assert!(amount.as_attounc() > 0, "Withdrawal amount should be positive");
+    /// Withdraws the given amount from the inner account of the given account ID and transfers it
+    /// to this account.
+    pub(crate) fn internal_withdraw(&mut self, account_id: &AccountId, amount: UncToken) {
+        assert!(
+            amount.as_attounc() > 0,
+            "{}",
+            StakingError::ZeroWithdrawalAmount
+        );
 
-        let account_id = env::predecessor_account_id();
-        let mut account = self.internal_get_account(&account_id);
+        let mut account = self.internal_get_account(account_id);
         assert!(
             account.unstaked >= amount,
-            "Not enough unstaked balance to withdraw"
+            "{}",
+            StakingError::NotEnoughUnstakedBalanceToWithdraw
         );
         assert!(
             account.unstaked_available_epoch_height <= env::epoch_height(),
-            "The unstaked balance is not yet available due to unstaking delay"
+            "{}",
+            StakingError::UnstakedBalanceNotAvailable
         );
         account.unstaked = account.unstaked.saturating_sub(amount);
-        self.internal_save_account(&account_id, &account);
+        self.internal_save_account(account_id, &account);
 
         env::log_str(
             format!(
@@ -63,35 +111,70 @@ impl StakingContract {
             .as_str(),
         );
 
-        Promise::new(account_id).transfer(amount);
+        Promise::new(account_id.clone()).transfer(amount).then(
+            ext_self::ext(env::current_account_id())
+                .with_static_gas(ON_WITHDRAW_GAS)
+                .with_attached_deposit(NO_DEPOSIT)
+                .on_withdraw(account_id.clone(), amount.as_attounc().into()),
+        );
         self.last_total_balance = self.last_total_balance.saturating_sub(amount);
     }
 
-    pub(crate) fn internal_stake(&mut self, amount: UncToken) {
-        assert!(amount.as_attounc() > 0, "Staking amount should be positive");
+    /// Stakes the given amount from the inner account of the predecessor.
+    /// When `enforce_min_stake_amount` is `true`, the amount charged from the unstaked balance
+    /// must be at least `min_stake_amount`.
+    pub(crate) fn internal_stake(&mut self, amount: UncToken, enforce_min_stake_amount: bool) {
+        assert!(
+            amount.as_attounc() > 0,
+            "{}",
+            StakingError::ZeroStakingAmount
+        );
 
         let account_id = env::predecessor_account_id();
         let mut account = self.internal_get_account(&account_id);
 
+        // A pool initialized without the balance above the guarantee fund has no shares, so the
+        // share price is not defined yet. The first stake mints the shares 1:1 instead.
+        let is_bootstrap = self.total_stake_shares.is_zero();
         // Calculate the number of "stake" shares that the account will receive for staking the
         // given amount.
-        let num_shares = self.num_shares_from_staked_amount_rounded_down(amount);
+        let num_shares = if is_bootstrap {
+            amount
+        } else {
+            self.num_shares_from_staked_amount_rounded_down(amount)
+        };
         assert!(
             num_shares.as_attounc() > 0,
-            "The calculated number of \"stake\" shares received for staking should be positive"
+            "{}",
+            StakingError::ZeroStakeSharesToStake
         );
         // The amount of tokens the account will be charged from the unstaked balance.
         // Rounded down to avoid overcharging the account to guarantee that the account can always
         // unstake at least the same amount as staked.
-        let charge_amount = self.staked_amount_from_num_shares_rounded_down(num_shares);
+        let charge_amount = if is_bootstrap {
+            amount
+        } else {
+            self.staked_amount_from_num_shares_rounded_down(num_shares)
+        };
         assert!(
             charge_amount.as_attounc() > 0,
-            "Invariant violation. Calculated staked amount must be positive, because \"stake\" share price should be at least 1"
+            "{}",
+            StakingError::ZeroStakedAmount
         );
+        if enforce_min_stake_amount {
+            assert!(
+                charge_amount >= self.min_stake_amount,
+                "{}: {} < {}",
+                StakingError::StakeAmountBelowMinimum,
+                charge_amount,
+                self.min_stake_amount
+            );
+        }
 
         assert!(
             account.unstaked >= charge_amount,
-            "Not enough unstaked balance to stake"
+            "{}",
+            StakingError::NotEnoughUnstakedBalanceToStake
         );
         account.unstaked = account.unstaked.saturating_sub(charge_amount);
         account.stake_shares = account.stake_shares.saturating_add(num_shares);
@@ -100,7 +183,20 @@ impl StakingContract {
         // The staked amount that will be added to the total to guarantee the "stake" share price
         // never decreases. The difference between `stake_amount` and `charge_amount` is paid
         // from the allocated STAKE_SHARE_PRICE_GUARANTEE_FUND.
-        let stake_amount = self.staked_amount_from_num_shares_rounded_up(num_shares);
+        let stake_amount = if is_bootstrap {
+            charge_amount
+        } else {
+            self.staked_amount_from_num_shares_rounded_up(num_shares)
+        };
+        if let Some(max_total_staked_balance) = self.max_total_staked_balance {
+            assert!(
+                self.total_staked_balance.saturating_add(stake_amount) <= max_total_staked_balance,
+                "{}: staking {} with the maximum of {}",
+                StakingError::MaxTotalStakedBalanceExceeded,
+                charge_amount,
+                max_total_staked_balance
+            );
+        }
 
         self.total_staked_balance = self.total_staked_balance.saturating_add(stake_amount);
         self.total_stake_shares = self.total_stake_shares.saturating_add(num_shares);
@@ -122,25 +218,28 @@ impl StakingContract {
     }
 
     pub(crate) fn inner_unstake(&mut self, amount: u128) {
-        assert!(amount > 0, "Unstaking amount should be positive");
+        assert!(amount > 0, "{}", StakingError::ZeroUnstakingAmount);
 
         let account_id = env::predecessor_account_id();
         let mut account = self.internal_get_account(&account_id);
 
         assert!(
             self.total_staked_balance.as_attounc() > 0,
-            "The contract doesn't have staked balance"
+            "{}",
+            StakingError::NoStakedBalance
         );
         // Calculate the number of shares required to unstake the given amount.
         // NOTE: The number of shares the account will pay is rounded up.
         let num_shares = self.num_shares_from_staked_amount_rounded_up(UncToken::from_attounc(amount));
         assert!(
             num_shares.as_attounc() > 0,
-            "Invariant violation. The calculated number of \"stake\" shares for unstaking should be positive"
+            "{}",
+            StakingError::ZeroStakeSharesToUnstake
         );
         assert!(
             account.stake_shares >= num_shares,
-            "Not enough staked balance to unstake"
+            "{}",
+            StakingError::NotEnoughStakedBalanceToUnstake
         );
 
         // Calculating the amount of tokens the account will receive by unstaking the corresponding
@@ -148,12 +247,13 @@ impl StakingContract {
         let receive_amount = self.staked_amount_from_num_shares_rounded_up(num_shares);
         assert!(
             receive_amount.as_attounc() > 0,
-            "Invariant violation. Calculated staked amount must be positive, because \"stake\" share price should be at least 1"
+            "{}",
+            StakingError::ZeroStakedAmount
         );
 
-        account.stake_shares = account.stake_shares.saturating_add(num_shares);
+        account.stake_shares = account.stake_shares.saturating_sub(num_shares);
         account.unstaked = account.unstaked.saturating_add(receive_amount);
-        account.unstaked_available_epoch_height = env::epoch_height() + NUM_EPOCHS_TO_UNLOCK;
+        account.unstaked_available_epoch_height = env::epoch_height() + self.num_epochs_to_unlock;
         self.internal_save_account(&account_id, &account);
 
         // The amount tokens that will be unstaked from the total to guarantee the "stake" share
@@ -185,8 +285,118 @@ impl StakingContract {
         assert_eq!(
             env::predecessor_account_id(),
             self.owner_id,
-            "Can only be called by the owner"
+            "{}",
+            StakingError::NotOwner
+        );
+    }
+
+    /// Returns the total balance of the account (both locked and unlocked balances), excluding
+    /// the attached deposit which is not accounted yet.
+    pub(crate) fn internal_total_balance(&self) -> UncToken {
+        env::account_locked_balance()
+            .saturating_add(env::account_balance())
+            .saturating_sub(env::attached_deposit())
+    }
+
+    /// Returns the amount the total balance dropped below the last total balance, e.g. after the
+    /// validator was slashed.
+    pub(crate) fn internal_slashed_amount(&self) -> UncToken {
+        self.last_total_balance.saturating_sub(self.internal_total_balance())
+    }
+
+    /// Asserts the accounting invariants by iterating over all accounts:
+    /// - the unstaked balances of the accounts add up to the total unstaked balance;
+    /// - the unstaked balances of the accounts together with the total staked balance don't
+    ///   exceed the last total balance;
+    /// - the "stake" shares of the accounts don't exceed the total number of "stake" shares. The
+    ///   shares of the initial balance staked on init don't belong to any account, so the sum is
+    ///   usually less than the total.
+    pub(crate) fn assert_invariants(&self) {
+        let (total_unstaked_balance, total_account_shares) = self.accounts.values().fold(
+            (UncToken::from_attounc(0), UncToken::from_attounc(0)),
+            |(unstaked, shares), account| {
+                (
+                    unstaked.saturating_add(account.unstaked),
+                    shares.saturating_add(account.stake_shares),
+                )
+            },
+        );
+        assert_eq!(
+            total_unstaked_balance,
+            self.total_unstaked_balance,
+            "{}",
+            StakingError::UnstakedBalancesMismatch
+        );
+        assert!(
+            total_unstaked_balance.saturating_add(self.total_staked_balance)
+                <= self.last_total_balance,
+            "{}: {} > {}",
+            StakingError::BalancesExceedLastTotalBalance,
+            total_unstaked_balance.saturating_add(self.total_staked_balance),
+            self.last_total_balance
         );
+        assert!(
+            total_account_shares <= self.total_stake_shares,
+            "{}: {} > {}",
+            StakingError::AccountSharesExceedTotalStakeShares,
+            total_account_shares.as_attounc(),
+            self.total_stake_shares.as_attounc()
+        );
+    }
+
+    /// Adds the "stake" shares bought with the reward fee to the inner accounts of the fee split
+    /// recipients proportionally to their fractions, or to the fee recipient, which is the owner
+    /// by default. The last recipient of the split gets the shares left after rounding down.
+    fn internal_distribute_fee_shares(&mut self, num_shares: NumStakeShares) {
+        let recipients = if self.fee_split.is_empty() {
+            let fee_recipient = self.fee_recipient.clone().unwrap_or_else(|| self.owner_id.clone());
+            vec![(fee_recipient, RewardFeeFraction { numerator: 1, denominator: 1 })]
+        } else {
+            self.fee_split.clone()
+        };
+        let mut remaining_shares = num_shares;
+        for (index, (recipient, fraction)) in recipients.iter().enumerate() {
+            let recipient_shares = if index + 1 == recipients.len() {
+                remaining_shares
+            } else {
+                fraction.multiply(num_shares)
+            };
+            remaining_shares = remaining_shares.saturating_sub(recipient_shares);
+            let mut account = self.internal_get_account(recipient);
+            account.stake_shares = account.stake_shares.saturating_add(recipient_shares);
+            self.internal_save_account(recipient, &account);
+        }
+    }
+
+    /// Returns the total staked balance and the total number of "stake" shares after the reward
+    /// that `internal_ping` would distribute in the current epoch, without changing the state.
+    pub(crate) fn internal_projected_totals(&self) -> (UncToken, NumStakeShares) {
+        if self.last_epoch_height == env::epoch_height() {
+            return (self.total_staked_balance, self.total_stake_shares);
+        }
+        let total_reward = self.internal_total_balance().saturating_sub(self.last_total_balance);
+        if total_reward < self.min_reward_to_distribute {
+            return (self.total_staked_balance, self.total_stake_shares);
+        }
+        let owners_fee = self.reward_fee_fraction.multiply(total_reward);
+        // The remaining reward goes to the delegators first, then the owner buys the shares at the
+        // new price.
+        let total_staked_balance = self
+            .total_staked_balance
+            .saturating_add(total_reward.saturating_sub(owners_fee));
+        let num_shares = if total_staked_balance.is_zero() {
+            UncToken::from_attounc(0)
+        } else {
+            num_shares_from_staked_amount_rounded_down(
+                total_staked_balance,
+                self.total_stake_shares,
+                owners_fee,
+            )
+        };
+        (
+            total_staked_balance.saturating_add(owners_fee),
+            self.total_stake_shares.saturating_add(num_shares),
+        )
     }
 
     /// Distributes rewards after the new epoch. It's automatically called before every action.
@@ -202,15 +412,53 @@ impl StakingContract {
         // NOTE: We need to subtract `attached_deposit` in case `ping` called from `deposit` call
         // since the attached deposit gets included in the `account_balance`, and we have not
         // accounted it yet.
-        let total_balance =
-            env::account_locked_balance().saturating_add(env::account_balance()).saturating_sub(env::attached_deposit());
+        let total_balance = self.internal_total_balance();
 
         assert!(
             total_balance >= self.last_total_balance,
-            "The new total balance should not be less than the old total balance"
+            "{}",
+            StakingError::TotalBalanceDecreased
         );
         let total_reward = total_balance.saturating_sub(self.last_total_balance);
-        if total_reward.as_attounc() > 0 {
+        if total_reward < self.min_reward_to_distribute {
+            // The reward is deferred by keeping the last total balance, so it accumulates with the
+            // rewards of the next epochs until it reaches the minimum.
+            env::log_str(
+                format!(
+                    "Epoch {}: The reward of {} is below the minimum of {} and is deferred",
+                    epoch_height, total_reward, self.min_reward_to_distribute,
+                )
+                .as_str(),
+            );
+            return true;
+        }
+        self.internal_distribute_reward(epoch_height, total_reward);
+
+        self.last_total_balance = total_balance;
+        true
+    }
+
+    /// Distributes the given reward to the delegators by increasing the total staked balance, and
+    /// buys "stake" shares for the fee recipient with the reward fee.
+    /// It doesn't update `last_total_balance`.
+    pub(crate) fn internal_distribute_reward(
+        &mut self,
+        epoch_height: EpochHeight,
+        total_reward: UncToken,
+    ) {
+        if total_reward.as_attounc() > 0 && self.reward_fee_fraction.numerator == 0 {
+            // Zero-fee pool: the whole reward goes to the delegators and the owner never receives
+            // any "stake" shares.
+            self.total_staked_balance = self.total_staked_balance.saturating_add(total_reward);
+
+            env::log_str(
+                format!(
+                    "Epoch {}: Contract received total rewards of {} tokens. New total staked balance is {}. Total number of shares {}",
+                    epoch_height, total_reward, self.total_staked_balance, self.total_stake_shares,
+                )
+                    .as_str(),
+            );
+        } else if total_reward.as_attounc() > 0 {
             // The validation fee that the contract owner takes.
             let owners_fee = self.reward_fee_fraction.multiply(total_reward);
 
@@ -218,14 +466,10 @@ impl StakingContract {
             let remaining_reward = total_reward.saturating_sub(owners_fee);
             self.total_staked_balance = self.total_staked_balance.saturating_add(remaining_reward);
 
-            // Now buying "stake" shares for the contract owner at the new share price.
+            // Now buying "stake" shares for the fee recipient at the new share price.
             let num_shares = self.num_shares_from_staked_amount_rounded_down(owners_fee);
             if num_shares.as_attounc() > 0 {
-                // Updating owner's inner account
-                let owner_id = self.owner_id.clone();
-                let mut account = self.internal_get_account(&owner_id);
-                account.stake_shares = account.stake_shares.saturating_add(num_shares);
-                self.internal_save_account(&owner_id, &account);
+                self.internal_distribute_fee_shares(num_shares);
                 // Increasing the total amount of "stake" shares.
                 self.total_stake_shares = self.total_stake_shares.saturating_add(num_shares);
             }
@@ -244,9 +488,34 @@ impl StakingContract {
                 env::log_str(format!("Total rewards fee is {} stake shares.", num_shares).as_str());
             }
         }
+        if total_reward.as_attounc() > 0 {
+            self.internal_record_reward(epoch_height, total_reward);
+        }
+    }
 
-        self.last_total_balance = total_balance;
-        true
+    /// Appends the reward snapshot to the reward history, dropping the oldest snapshot once the
+    /// history holds `REWARD_HISTORY_LENGTH` entries.
+    /// A reward in the epoch of the last snapshot, e.g. a bonus reward after `ping`, is merged into
+    /// that snapshot, so every epoch has at most one snapshot and the estimated APY doesn't count
+    /// a rate without the epochs it was earned over.
+    pub(crate) fn internal_record_reward(&mut self, epoch_height: EpochHeight, total_reward: UncToken) {
+        if let Some(snapshot) = self
+            .reward_history
+            .last_mut()
+            .filter(|snapshot| snapshot.epoch_height == epoch_height)
+        {
+            snapshot.total_reward = snapshot.total_reward.saturating_add(total_reward);
+            snapshot.total_staked_balance = self.total_staked_balance;
+            return;
+        }
+        if self.reward_history.len() >= REWARD_HISTORY_LENGTH {
+            self.reward_history.remove(0);
+        }
+        self.reward_history.push(RewardSnapshot {
+            epoch_height,
+            total_reward,
+            total_staked_balance: self.total_staked_balance,
+        });
     }
 
     /// Returns the number of "stake" shares rounded down corresponding to the given staked balance
@@ -262,13 +531,11 @@ impl StakingContract {
         &self,
         amount: UncToken,
     ) -> NumStakeShares {
-        assert!(
-            self.total_staked_balance.as_attounc() > 0,
-            "The total staked balance can't be 0"
-        );
-        UncToken::from_attounc((U256::from(self.total_stake_shares.as_attounc()) * U256::from(amount.as_attounc())
-            / U256::from(self.total_staked_balance.as_attounc()))
-        .as_u128())
+        num_shares_from_staked_amount_rounded_down(
+            self.total_staked_balance,
+            self.total_stake_shares,
+            amount,
+        )
     }
 
     /// Returns the number of "stake" shares rounded up corresponding to the given staked balance
@@ -281,7 +548,8 @@ impl StakingContract {
     ) -> NumStakeShares {
         assert!(
             self.total_staked_balance.as_attounc() > 0,
-            "The total staked balance can't be 0"
+            "{}",
+            StakingError::ZeroTotalStakedBalance
         );
         UncToken::from_attounc(((U256::from(self.total_stake_shares.as_attounc()) * U256::from(amount.as_attounc())
             + U256::from(self.total_staked_balance.as_attounc() - 1))
@@ -294,13 +562,11 @@ impl StakingContract {
         &self,
         num_shares: NumStakeShares,
     ) -> UncToken {
-        assert!(
-            self.total_stake_shares.as_attounc() > 0,
-            "The total number of stake shares can't be 0"
-        );
-        UncToken::from_attounc((U256::from(self.total_staked_balance.as_attounc()) * U256::from(num_shares.as_attounc())
-            / U256::from(self.total_stake_shares.as_attounc()))
-        .as_u128())
+        staked_amount_from_num_shares_rounded_down(
+            self.total_staked_balance,
+            self.total_stake_shares,
+            num_shares,
+        )
     }
 
     /// Returns the staked amount rounded up corresponding to the given number of "stake" shares.
@@ -310,14 +576,11 @@ impl StakingContract {
         &self,
         num_shares: NumStakeShares,
     ) -> UncToken {
-        assert!(
-            self.total_stake_shares.as_attounc() > 0,
-            "The total number of stake shares can't be 0"
-        );
-        UncToken::from_attounc(((U256::from(self.total_staked_balance.as_attounc()) * U256::from(num_shares.as_attounc())
-            + U256::from(self.total_stake_shares.as_attounc() - 1))
-            / U256::from(self.total_stake_shares.as_attounc()))
-        .as_u128())
+        staked_amount_from_num_shares_rounded_up(
+            self.total_staked_balance,
+            self.total_stake_shares,
+            num_shares,
+        )
     }
 
     /// Inner method to get the given account or a new default value account.
@@ -327,7 +590,13 @@ impl StakingContract {
 
     /// Inner method to save the given account for a given account ID.
     /// If the account balances are 0, the account is deleted instead to release storage.
+    /// Updates the total unstaked balance by the change of the unstaked balance of the account.
     pub(crate) fn internal_save_account(&mut self, account_id: &AccountId, account: &Account) {
+        let old_unstaked = self.internal_get_account(account_id).unstaked;
+        self.total_unstaked_balance = self
+            .total_unstaked_balance
+            .saturating_sub(old_unstaked)
+            .saturating_add(account.unstaked);
         if account.unstaked.as_attounc() > 0 || account.stake_shares.as_attounc() > 0 {
             self.accounts.insert(account_id.clone(), account.clone());
         } else {
@@ -335,3 +604,65 @@ impl StakingContract {
         }
     }
 }
+
+/// Returns the number of "stake" shares rounded down corresponding to the given staked balance
+/// amount for the given total staked balance and total number of "stake" shares.
+pub(crate) fn num_shares_from_staked_amount_rounded_down(
+    total_staked_balance: UncToken,
+    total_stake_shares: NumStakeShares,
+    amount: UncToken,
+) -> NumStakeShares {
+    assert!(
+        total_staked_balance.as_attounc() > 0,
+        "{}",
+        StakingError::ZeroTotalStakedBalance
+    );
+    UncToken::from_attounc((U256::from(total_stake_shares.as_attounc()) * U256::from(amount.as_attounc())
+        / U256::from(total_staked_balance.as_attounc()))
+    .as_u128())
+}
+
+/// Returns the staked amount rounded down corresponding to the given number of "stake" shares for
+/// the given total staked balance and total number of "stake" shares.
+pub(crate) fn staked_amount_from_num_shares_rounded_down(
+    total_staked_balance: UncToken,
+    total_stake_shares: NumStakeShares,
+    num_shares: NumStakeShares,
+) -> UncToken {
+    assert!(
+        total_stake_shares.as_attounc() > 0,
+        "{}",
+        StakingError::ZeroTotalStakeShares
+    );
+    UncToken::from_attounc((U256::from(total_staked_balance.as_attounc()) * U256::from(num_shares.as_attounc())
+        / U256::from(total_stake_shares.as_attounc()))
+    .as_u128())
+}
+
+/// Returns the staked amount rounded up corresponding to the given number of "stake" shares for
+/// the given total staked balance and total number of "stake" shares.
+///
+/// Rounding up division of `a / b` is done using `(a + b - 1) / b`.
+pub(crate) fn staked_amount_from_num_shares_rounded_up(
+    total_staked_balance: UncToken,
+    total_stake_shares: NumStakeShares,
+    num_shares: NumStakeShares,
+) -> UncToken {
+    assert!(
+        total_stake_shares.as_attounc() > 0,
+        "{}",
+        StakingError::ZeroTotalStakeShares
+    );
+    UncToken::from_attounc(((U256::from(total_staked_balance.as_attounc()) * U256::from(num_shares.as_attounc())
+        + U256::from(total_stake_shares.as_attounc() - 1))
+        / U256::from(total_stake_shares.as_attounc()))
+    .as_u128())
+}
+
+/// Returns the greatest common divisor of the given numbers.
+pub(crate) fn gcd(mut a: U256, mut b: U256) -> U256 {
+    while !b.is_zero() {
+        (a, b) = (b, a % b);
+    }
+    a
+}
@@ -1,5 +1,5 @@
 use std::convert::TryInto;
-use unc_sdk::store::IterableMap;
+use unc_sdk::store::{IterableMap, IterableSet};
 use unc_sdk::json_types::U128;
 use unc_sdk::{
     env, ext_contract, unc, AccountId, UncToken, Gas, EpochHeight, Promise, PromiseResult,
@@ -7,7 +7,11 @@ use unc_sdk::{
 };
 use uint::construct_uint;
 
+mod errors;
 mod internal;
+mod migrate;
+
+pub use crate::errors::StakingError;
 
 /// The amount of gas given to complete `vote` call.
 const VOTE_GAS: Gas = Gas::from_gas(100_000_000_000_000);
@@ -15,10 +19,18 @@ const VOTE_GAS: Gas = Gas::from_gas(100_000_000_000_000);
 /// The amount of gas given to complete internal `on_stake_action` call.
 const ON_STAKE_ACTION_GAS: Gas = Gas::from_gas(20_000_000_000_000);
 
+/// The amount of gas given to complete internal `on_withdraw` call.
+const ON_WITHDRAW_GAS: Gas = Gas::from_gas(20_000_000_000_000);
+
 /// The amount of atto UNC the contract dedicates to guarantee that the "share" price never
 /// decreases. It's used during rounding errors for share -> amount conversions.
 const STAKE_SHARE_PRICE_GUARANTEE_FUND: UncToken = UncToken::from_attounc(1_000_000_000_000);
 
+/// The maximum number of accounts `withdraw_matured` processes in one call. Every withdrawal
+/// creates a transfer and a callback with `ON_WITHDRAW_GAS`, which take about 35 Tgas together,
+/// so a full batch with the restake stays well below the 300 Tgas limit.
+const MAX_WITHDRAW_MATURED_ACCOUNTS: usize = 5;
+
 /// There is no deposit balance attached.
 const NO_DEPOSIT: UncToken = UncToken::from_attounc(0);
 
@@ -62,6 +74,20 @@ pub struct HumanReadableAccount {
     pub can_withdraw: bool,
 }
 
+/// The preview of the recovery after slashing done by `handle_slashing`.
+#[derive(Debug, PartialEq)]
+#[unc(serializers=[json])]
+pub struct SlashingPreview {
+    /// The amount the total balance of the account dropped below the last total balance.
+    pub slashed_amount: U128,
+    /// The total staked balance after the recovery.
+    pub total_staked_balance: U128,
+    /// The staked balance of `10^24` "stake" shares after the recovery.
+    pub stake_share_price: U128,
+    /// The loss of the staked balance of every delegator in basis points (1/100 of a percent).
+    pub loss_basis_points: u32,
+}
+
 impl Default for Account {
     fn default() -> Self {
         Self {
@@ -72,11 +98,69 @@ impl Default for Account {
     }
 }
 
-/// The number of epochs required for the locked balance to become unlocked.
+/// The default number of epochs required for the locked balance to become unlocked.
 /// NOTE: The actual number of epochs when the funds are unlocked is 3. But there is a corner case
 /// when the unstaking promise can arrive at the next epoch, while the inner state is already
 /// updated in the previous epoch. It will not unlock the funds for 4 epochs.
-const NUM_EPOCHS_TO_UNLOCK: EpochHeight = 4;
+const DEFAULT_NUM_EPOCHS_TO_UNLOCK: EpochHeight = 4;
+
+/// The maximum number of epochs the owner can set for the unstaked balance to stay locked, i.e.
+/// 30 days with 12 hour epochs.
+const MAX_NUM_EPOCHS_TO_UNLOCK: EpochHeight = 60;
+
+/// The number of "stake" shares used to express the share price, i.e. 1 UNC worth of shares at the
+/// initial price.
+const STAKE_SHARE_PRICE_UNIT: NumStakeShares = UncToken::from_attounc(1_000_000_000_000_000_000_000_000);
+
+/// The number of the most recent reward snapshots kept in the reward history.
+const REWARD_HISTORY_LENGTH: usize = 30;
+
+/// The number of basis points in 1, i.e. 100%.
+const MAX_BASIS_POINTS: u16 = 10_000;
+
+/// The maximum number of recipients the reward fee can be split among.
+const MAX_FEE_SPLIT_RECIPIENTS: usize = 10;
+
+/// The default number of epochs in a year on mainnet, assuming 12 hour epochs.
+const DEFAULT_EPOCHS_PER_YEAR: u64 = 730;
+
+/// The reward distributed by the pool at some epoch.
+#[derive(Debug, Clone, PartialEq)]
+#[unc(serializers=[borsh, json])]
+pub struct RewardSnapshot {
+    /// The epoch height when the reward was distributed.
+    pub epoch_height: EpochHeight,
+    /// The total reward received by the pool, including the owner's fee.
+    pub total_reward: UncToken,
+    /// The total staked balance after the reward was distributed.
+    pub total_staked_balance: UncToken,
+}
+
+/// Events emitted by the staking pool.
+#[unc(event_json(standard = "staking_pool"))]
+pub enum StakingPoolEvent {
+    /// The pool distributed the reward received since the last epoch.
+    #[event_version("1.0.0")]
+    RewardDistributed {
+        epoch_height: EpochHeight,
+        total_reward: U128,
+        total_staked_balance: U128,
+    },
+    /// The pool lowered the total staked balance after the validator was slashed.
+    #[event_version("1.0.0")]
+    Slashed {
+        epoch_height: EpochHeight,
+        slashed_amount: U128,
+        total_staked_balance: U128,
+    },
+    /// The owner moved "stake" shares from their account to the given account.
+    #[event_version("1.0.0")]
+    SharesGifted {
+        owner_id: AccountId,
+        account_id: AccountId,
+        shares: U128,
+    },
+}
 
 #[unc(contract_state)]
 pub struct StakingContract {
@@ -107,6 +191,53 @@ pub struct StakingContract {
     /// Pausing is useful for node maintenance. Only the owner can pause and resume staking.
     /// The contract is not paused by default.
     pub paused: bool,
+    /// The optional cap on the total staked balance of the pool. Staking that would push the
+    /// total staked balance over the cap is rejected. Unstaking is always allowed.
+    /// Only the owner can change the cap. There is no cap by default.
+    pub max_total_staked_balance: Option<UncToken>,
+    /// The minimum amount that can be staked with `stake` or `deposit_and_stake` to avoid dust
+    /// accounts. `stake_all` is exempt, so the remainder of the unstaked balance can always be
+    /// staked. Only the owner can change it. It's zero by default.
+    pub min_stake_amount: UncToken,
+    /// The most recent reward snapshots, oldest first. Holds at most `REWARD_HISTORY_LENGTH`
+    /// entries, so the storage doesn't grow over time.
+    pub reward_history: Vec<RewardSnapshot>,
+    /// The number of epochs the unstaked balance stays locked after an unstake action.
+    /// It's set on init and can be changed by the owner. It only applies to new unstake actions.
+    pub num_epochs_to_unlock: EpochHeight,
+    /// Whether only the allowed delegators can deposit. Existing delegators can always unstake and
+    /// withdraw. Only the owner can change it. The pool is not restricted by default.
+    pub restricted_mode: bool,
+    /// The accounts allowed to deposit when the pool is in the restricted mode.
+    pub delegators: IterableSet<AccountId>,
+    /// The account that receives the "stake" shares of the reward fee instead of the owner.
+    /// Only the owner can change it. The owner receives the fee by default.
+    pub fee_recipient: Option<AccountId>,
+    /// The account proposed by the owner to become the new owner. The ownership is only
+    /// transferred once this account accepts it.
+    pub proposed_owner_id: Option<AccountId>,
+    /// The optional cap on the number of accounts. A deposit that would create a new account over
+    /// the cap is rejected. Existing accounts can always deposit more.
+    /// Only the owner can change the cap. There is no cap by default.
+    pub max_num_accounts: Option<u64>,
+    /// The number of epochs in a year used to annualize the rewards in the projection views.
+    /// It's set on init, since the epoch length differs between networks.
+    pub epochs_per_year: u64,
+    /// The sum of the unstaked balances of all accounts, waiting to be withdrawn or staked.
+    /// It's updated whenever an account is saved, so it doesn't require iterating the accounts.
+    pub total_unstaked_balance: UncToken,
+    /// The minimum reward `ping` distributes. A smaller reward isn't distributed, but accumulates
+    /// with the rewards of the next epochs until it reaches the minimum, so dust rewards don't
+    /// waste gas. Only the owner can change it. It's zero by default.
+    pub min_reward_to_distribute: UncToken,
+    /// The recipients the "stake" shares of the reward fee are split among, with the fraction of
+    /// the fee each of them receives. The fractions sum to 1. When it's empty, the fee recipient
+    /// receives the whole fee. Only the owner can change it. It's empty by default.
+    pub fee_split: Vec<(AccountId, RewardFeeFraction)>,
+    /// The amount sent with the most recent stake action, or `None` if it's unknown, e.g. after a
+    /// failed stake action. The locked balance only decreases once an unstake takes effect, so
+    /// `internal_restake` compares `total_staked_balance` with this amount instead.
+    pub last_staked_balance: Option<UncToken>,
 }
 
 impl Default for StakingContract {
@@ -115,7 +246,7 @@ impl Default for StakingContract {
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 #[unc(serializers=[borsh, json])]
 pub struct RewardFeeFraction {
     pub numerator: u32,
@@ -124,11 +255,34 @@ pub struct RewardFeeFraction {
 
 impl RewardFeeFraction {
     pub fn assert_valid(&self) {
-        assert_ne!(self.denominator, 0, "Denominator must be a positive number");
+        assert_ne!(
+            self.denominator,
+            0,
+            "{}",
+            StakingError::ZeroRewardFeeDenominator
+        );
         assert!(
             self.numerator <= self.denominator,
-            "The reward fee must be less or equal to 1"
+            "{}",
+            StakingError::RewardFeeAboveOne
+        );
+    }
+
+    /// Returns the fraction equivalent to the given number of basis points, e.g. 500 is 5%.
+    pub fn from_basis_points(basis_points: u16) -> Self {
+        assert!(
+            basis_points <= MAX_BASIS_POINTS,
+            "{}, it must be less or equal to {} basis points",
+            StakingError::RewardFeeTooHigh,
+            MAX_BASIS_POINTS
         );
+        Self { numerator: basis_points.into(), denominator: MAX_BASIS_POINTS.into() }
+    }
+
+    /// Returns the fraction in basis points, rounded down.
+    pub fn basis_points(&self) -> u16 {
+        (u64::from(self.numerator) * u64::from(MAX_BASIS_POINTS) / u64::from(self.denominator))
+            as u16
     }
 
     pub fn multiply(&self, value: UncToken) -> UncToken {
@@ -153,6 +307,15 @@ pub trait SelfContract {
     /// follow withdraw calls might fail. To mitigate this, the contract will issue a new unstaking
     /// action in case of the failure of the first staking action.
     fn on_stake_action(&mut self);
+
+    /// A callback to check the result of the staking action with the new staking key.
+    /// The stored staking key is only replaced with `new_key` if the staking action succeeded.
+    fn on_migrate_staking_key_action(&mut self, new_key: PublicKey);
+
+    /// A callback to check the result of the withdrawal transfer.
+    /// If the transfer failed, e.g. because the account was deleted, the tokens are returned to
+    /// this contract, so the amount is credited back to the unstaked balance of the account.
+    fn on_withdraw(&mut self, account_id: AccountId, amount: U128);
 }
 
 #[unc]
@@ -163,24 +326,36 @@ impl StakingContract {
     /// The entire current balance of this contract will be used to stake. This allows contract to
     /// always maintain staking shares that can't be unstaked or withdrawn.
     /// It prevents inflating the price of the share too much.
+    ///
+    /// The optional `num_epochs_to_unlock` sets the number of epochs the unstaked balance stays
+    /// locked. Defaults to 4 epochs.
+    ///
+    /// The optional `epochs_per_year` sets the number of epochs in a year used by the reward
+    /// projections, e.g. for a network with a different epoch length. Defaults to 730 epochs.
     #[init]
     pub fn new(
         owner_id: AccountId,
         stake_public_key: PublicKey,
         reward_fee_fraction: RewardFeeFraction,
+        num_epochs_to_unlock: Option<EpochHeight>,
+        epochs_per_year: Option<u64>,
     ) -> Self {
-        assert!(!env::state_exists(), "Already initialized");
+        assert!(!env::state_exists(), "{}", StakingError::AlreadyInitialized);
         reward_fee_fraction.assert_valid();
+        let epochs_per_year = epochs_per_year.unwrap_or(DEFAULT_EPOCHS_PER_YEAR);
+        assert!(epochs_per_year > 0, "{}", StakingError::ZeroEpochsPerYear);
         assert!(
             env::is_valid_account_id(owner_id.as_bytes()),
-            "The owner account ID is invalid"
+            "{}",
+            StakingError::InvalidOwnerAccountId
         );
         let account_balance = env::account_balance();
         let total_staked_balance = account_balance.saturating_sub(STAKE_SHARE_PRICE_GUARANTEE_FUND);
         assert_eq!(
             env::account_locked_balance(),
             UncToken::from_attounc(0),
-            "The staking pool shouldn't be staking at the initialization"
+            "{}",
+            StakingError::StakingAtInitialization
         );
         let mut this = Self {
             owner_id,
@@ -192,19 +367,71 @@ impl StakingContract {
             reward_fee_fraction,
             accounts: IterableMap::new(b"u".to_vec()),
             paused: false,
+            max_total_staked_balance: None,
+            min_stake_amount: UncToken::from_attounc(0),
+            reward_history: vec![],
+            num_epochs_to_unlock: num_epochs_to_unlock.unwrap_or(DEFAULT_NUM_EPOCHS_TO_UNLOCK),
+            restricted_mode: false,
+            delegators: IterableSet::new(b"d".to_vec()),
+            fee_recipient: None,
+            proposed_owner_id: None,
+            max_num_accounts: None,
+            epochs_per_year,
+            total_unstaked_balance: UncToken::from_attounc(0),
+            min_reward_to_distribute: UncToken::from_attounc(0),
+            fee_split: vec![],
+            last_staked_balance: None,
         };
         // Staking with the current pool to make sure the staking key is valid.
-        this.internal_restake();
+        this.internal_stake_action();
         this
     }
 
-    /// Distributes rewards and restakes if needed.
+    /// Distributes rewards and restakes if needed. Can be called by anyone.
+    /// Emits the `reward_distributed` event if the reward was distributed.
     pub fn ping(&mut self) {
         if self.internal_ping() {
+            if let Some(snapshot) = self
+                .reward_history
+                .last()
+                .filter(|snapshot| snapshot.epoch_height == env::epoch_height())
+            {
+                StakingPoolEvent::RewardDistributed {
+                    epoch_height: snapshot.epoch_height,
+                    total_reward: snapshot.total_reward.as_attounc().into(),
+                    total_staked_balance: snapshot.total_staked_balance.as_attounc().into(),
+                }
+                .emit();
+            }
             self.internal_restake();
         }
     }
 
+    /// Distributes the attached deposit to the delegators as a bonus reward on top of the staking
+    /// rewards, e.g. from grants. The reward fee is charged the same way as on `ping`.
+    /// Can be called by anyone. Emits the `reward_distributed` event.
+    #[payable]
+    pub fn add_reward(&mut self) {
+        let amount = env::attached_deposit();
+        assert!(
+            amount.as_attounc() > 0,
+            "{}",
+            StakingError::ZeroRewardAmount
+        );
+        self.internal_ping();
+
+        self.internal_distribute_reward(env::epoch_height(), amount);
+        self.last_total_balance = self.last_total_balance.saturating_add(amount);
+        StakingPoolEvent::RewardDistributed {
+            epoch_height: env::epoch_height(),
+            total_reward: amount.as_attounc().into(),
+            total_staked_balance: self.total_staked_balance.as_attounc().into(),
+        }
+        .emit();
+
+        self.internal_restake();
+    }
+
     /// Deposits the attached amount into the inner account of the predecessor.
     #[payable]
     pub fn deposit(&mut self) {
@@ -217,13 +444,28 @@ impl StakingContract {
         }
     }
 
+    /// Deposits the attached amount into the inner account of the given account ID, e.g. to fund
+    /// the position of a delegator without their signature.
+    #[payable]
+    pub fn deposit_for(&mut self, account_id: AccountId) {
+        let need_to_restake = self.internal_ping();
+
+        self.internal_deposit_for(&account_id);
+
+        if need_to_restake {
+            self.internal_restake();
+        }
+    }
+
     /// Deposits the attached amount into the inner account of the predecessor and stakes it.
+    /// Panics if the attached amount is too small to receive at least one "stake" share, so the
+    /// deposit is refunded instead of being left unstaked.
     #[payable]
     pub fn deposit_and_stake(&mut self) {
         self.internal_ping();
 
         let amount = self.internal_deposit();
-        self.internal_stake(UncToken::from_attounc(amount));
+        self.internal_stake(UncToken::from_attounc(amount), true);
 
         self.internal_restake();
     }
@@ -235,7 +477,54 @@ impl StakingContract {
 
         let account_id = env::predecessor_account_id();
         let account = self.internal_get_account(&account_id);
-        self.internal_withdraw(account.unstaked);
+        self.internal_withdraw(&account_id, account.unstaked);
+
+        if need_to_restake {
+            self.internal_restake();
+        }
+    }
+
+    /// Withdraws the entire unstaked balance of the given account to this account.
+    /// Can only be called by the owner of the staking pool or by the account itself.
+    /// It's only allowed if the `unstake` action was not performed in the four most recent epochs.
+    pub fn withdraw_all_for(&mut self, account_id: AccountId) {
+        let predecessor_account_id = env::predecessor_account_id();
+        assert!(
+            predecessor_account_id == account_id || predecessor_account_id == self.owner_id,
+            "{}",
+            StakingError::NotOwnerOrAccount
+        );
+        let need_to_restake = self.internal_ping();
+
+        let account = self.internal_get_account(&account_id);
+        self.internal_withdraw(&account_id, account.unstaked);
+
+        if need_to_restake {
+            self.internal_restake();
+        }
+    }
+
+    /// Withdraws the entire unstaked balance of each of the given accounts to the account itself.
+    /// Can be called by anyone. The accounts without unstaked balance or with the unstaked balance
+    /// that is not yet available due to unstaking delay are skipped.
+    /// Accepts at most 5 accounts per call.
+    pub fn withdraw_matured(&mut self, account_ids: Vec<AccountId>) {
+        assert!(
+            account_ids.len() <= MAX_WITHDRAW_MATURED_ACCOUNTS,
+            "{}, the maximum is {}",
+            StakingError::TooManyAccountsToWithdraw,
+            MAX_WITHDRAW_MATURED_ACCOUNTS
+        );
+        let need_to_restake = self.internal_ping();
+
+        for account_id in account_ids {
+            let account = self.internal_get_account(&account_id);
+            if account.unstaked.as_attounc() > 0
+                && account.unstaked_available_epoch_height <= env::epoch_height()
+            {
+                self.internal_withdraw(&account_id, account.unstaked);
+            }
+        }
 
         if need_to_restake {
             self.internal_restake();
@@ -248,7 +537,7 @@ impl StakingContract {
         let need_to_restake = self.internal_ping();
 
         let amount: UncToken = UncToken::from_attounc(amount.into());
-        self.internal_withdraw(amount);
+        self.internal_withdraw(&env::predecessor_account_id(), amount);
 
         if need_to_restake {
             self.internal_restake();
@@ -256,13 +545,15 @@ impl StakingContract {
     }
 
     /// Stakes all available unstaked balance from the inner account of the predecessor.
+    /// NOTE: The minimum stake amount is not enforced here, so a remainder that is below the
+    /// minimum (e.g. left after rounding) can still be staked.
     pub fn stake_all(&mut self) {
         // Stake action always restakes
         self.internal_ping();
 
         let account_id = env::predecessor_account_id();
         let account = self.internal_get_account(&account_id);
-        self.internal_stake(account.unstaked);
+        self.internal_stake(account.unstaked, false);
 
         self.internal_restake();
     }
@@ -274,13 +565,14 @@ impl StakingContract {
         self.internal_ping();
 
         let amount: UncToken = UncToken::from_attounc(amount.into());
-        self.internal_stake(amount);
+        self.internal_stake(amount, true);
 
         self.internal_restake();
     }
 
     /// Unstakes all staked balance from the inner account of the predecessor.
-    /// The new total unstaked balance will be available for withdrawal in four epochs.
+    /// The new total unstaked balance will be available for withdrawal in four epochs, unless a
+    /// different number of epochs was configured on init.
     pub fn unstake_all(&mut self) {
         // Unstake action always restakes
         self.internal_ping();
@@ -295,12 +587,29 @@ impl StakingContract {
 
     /// Unstakes the given amount from the inner account of the predecessor.
     /// The inner account should have enough staked balance.
-    /// The new total unstaked balance will be available for withdrawal in four epochs.
+    /// The staked balance is rounded down from the "stake" shares, so the amount within the
+    /// rounding tolerance of the whole staked balance unstakes everything instead. The tolerance
+    /// is the value of a single "stake" share, rounded up.
+    /// The new total unstaked balance will be available for withdrawal in four epochs, unless a
+    /// different number of epochs was configured on init.
     pub fn unstake(&mut self, amount: U128) {
         // Unstake action always restakes
         self.internal_ping();
 
-        self.inner_unstake(amount.into());
+        let account = self.internal_get_account(&env::predecessor_account_id());
+        let mut amount: u128 = amount.into();
+        if account.stake_shares.as_attounc() > 0 {
+            let staked_amount = self
+                .staked_amount_from_num_shares_rounded_down(account.stake_shares)
+                .as_attounc();
+            let tolerance = self
+                .staked_amount_from_num_shares_rounded_up(UncToken::from_attounc(1))
+                .as_attounc();
+            if amount.abs_diff(staked_amount) <= tolerance {
+                amount = staked_amount;
+            }
+        }
+        self.inner_unstake(amount);
 
         self.internal_restake();
     }
@@ -332,21 +641,50 @@ impl StakingContract {
         self.get_account(account_id).can_withdraw
     }
 
+    /// Returns the epoch height at which the unstaked balance of the given account becomes
+    /// available for withdrawal.
+    pub fn get_account_unstaked_available_epoch(&self, account_id: &AccountId) -> EpochHeight {
+        self.internal_get_account(account_id).unstaked_available_epoch_height
+    }
+
+    /// Returns the number of epochs left until the given account can withdraw its unstaked
+    /// balance, or `0` if it can withdraw in the current epoch.
+    pub fn get_account_epochs_until_withdraw(&self, account_id: &AccountId) -> EpochHeight {
+        self.get_account_unstaked_available_epoch(account_id)
+            .saturating_sub(env::epoch_height())
+    }
+
     /// Returns the total staking balance.
     pub fn get_total_staked_balance(&self) -> U128 {
         self.total_staked_balance.as_attounc().into()
     }
 
+    /// Returns the sum of the unstaked balances of all accounts, i.e. the balance that is waiting
+    /// to be withdrawn or staked again.
+    pub fn get_total_unstaked_balance(&self) -> U128 {
+        self.total_unstaked_balance.as_attounc().into()
+    }
+
     /// Returns account ID of the staking pool owner.
     pub fn get_owner_id(&self) -> AccountId {
         self.owner_id.clone()
     }
 
+    /// Returns the account ID proposed to become the new owner, or `null` if there is no proposal.
+    pub fn get_proposed_owner(&self) -> Option<AccountId> {
+        self.proposed_owner_id.clone()
+    }
+
     /// Returns the current reward fee as a fraction.
     pub fn get_reward_fee_fraction(&self) -> RewardFeeFraction {
         self.reward_fee_fraction.clone()
     }
 
+    /// Returns the current reward fee in basis points, e.g. 500 for 5%. Rounded down.
+    pub fn get_reward_fee_basis_points(&self) -> u16 {
+        self.reward_fee_fraction.basis_points()
+    }
+
     /// Returns the staking public key
     pub fn get_staking_key(&self) -> PublicKey {
         self.stake_public_key.clone().try_into().unwrap()
@@ -357,6 +695,201 @@ impl StakingContract {
         self.paused
     }
 
+    /// Returns true if the pool is staking, i.e. the staking is not paused and the total staked
+    /// balance is positive. It doesn't check whether the stake is enough for a validator seat.
+    pub fn get_is_staking(&self) -> bool {
+        !self.paused && !self.total_staked_balance.is_zero()
+    }
+
+    /// Returns the epoch height when the pool was pinged last time.
+    pub fn get_last_epoch_height(&self) -> EpochHeight {
+        self.last_epoch_height
+    }
+
+    /// Returns the account that receives the reward fee instead of the owner, or `null` if the
+    /// owner receives it.
+    pub fn get_fee_recipient(&self) -> Option<AccountId> {
+        self.fee_recipient.clone()
+    }
+
+    /// Returns the recipients the reward fee is split among, with the fraction of the fee each of
+    /// them receives. Empty if the fee recipient receives the whole fee.
+    pub fn get_fee_split(&self) -> Vec<(AccountId, RewardFeeFraction)> {
+        self.fee_split.clone()
+    }
+
+    /// Returns true if only the allowed delegators can deposit.
+    pub fn is_restricted_mode(&self) -> bool {
+        self.restricted_mode
+    }
+
+    /// Returns up to the limit of delegators allowed to deposit in the restricted mode, starting
+    /// from the given index.
+    pub fn get_delegators(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        self.delegators.iter().skip(from_index as usize).take(limit as usize).cloned().collect()
+    }
+
+    /// Returns the cap on the total staked balance, or `null` if the pool has no cap.
+    pub fn get_max_total_staked_balance(&self) -> Option<U128> {
+        self.max_total_staked_balance
+            .map(|max_total_staked_balance| max_total_staked_balance.as_attounc().into())
+    }
+
+    /// Returns the cap on the number of accounts, or `null` if the pool has no cap.
+    pub fn get_max_num_accounts(&self) -> Option<u64> {
+        self.max_num_accounts
+    }
+
+    /// Returns the minimum amount that can be staked with `stake` or `deposit_and_stake`.
+    pub fn get_min_stake_amount(&self) -> U128 {
+        self.min_stake_amount.as_attounc().into()
+    }
+
+    /// Returns the minimum reward `ping` distributes. Smaller rewards accumulate until they reach it.
+    pub fn get_min_reward_to_distribute(&self) -> U128 {
+        self.min_reward_to_distribute.as_attounc().into()
+    }
+
+    /// Returns the number of epochs the unstaked balance stays locked after an unstake action.
+    pub fn get_num_epochs_to_unlock(&self) -> EpochHeight {
+        self.num_epochs_to_unlock
+    }
+
+    /// Returns the number of epochs in a year used by the reward projections.
+    pub fn get_epochs_per_year(&self) -> u64 {
+        self.epochs_per_year
+    }
+
+    /// Returns the rewards distributed in the most recent epochs, oldest first.
+    pub fn get_reward_history(&self) -> Vec<RewardSnapshot> {
+        self.reward_history.clone()
+    }
+
+    /// Returns the estimated annual yield of the delegators in basis points, e.g. 500 is 5%.
+    /// The average reward rate per epoch is taken from the reward history and multiplied by the
+    /// number of epochs in a year without compounding, then the current reward fee is deducted.
+    /// Returns 0 until the history has at least two snapshots, since the first snapshot has no
+    /// preceding epoch to measure the rate against.
+    pub fn get_estimated_apy(&self) -> u32 {
+        let scale = U256::from(STAKE_SHARE_PRICE_UNIT.as_attounc());
+        let mut sum_of_rates = U256::zero();
+        let mut num_epochs: u64 = 0;
+        for window in self.reward_history.windows(2) {
+            let (previous, snapshot) = (&window[0], &window[1]);
+            let staked_before_reward =
+                snapshot.total_staked_balance.saturating_sub(snapshot.total_reward);
+            if staked_before_reward.is_zero() {
+                continue;
+            }
+            sum_of_rates += U256::from(snapshot.total_reward.as_attounc()) * scale
+                / U256::from(staked_before_reward.as_attounc());
+            num_epochs += snapshot.epoch_height - previous.epoch_height;
+        }
+        if num_epochs == 0 {
+            return 0;
+        }
+        let fee = &self.reward_fee_fraction;
+        let apy = sum_of_rates
+            * U256::from(self.epochs_per_year)
+            * U256::from(MAX_BASIS_POINTS)
+            * U256::from(fee.denominator - fee.numerator)
+            / (scale * U256::from(num_epochs) * U256::from(fee.denominator));
+        apy.min(U256::from(u32::MAX)).as_u32()
+    }
+
+    /// Returns the number of "stake" shares the given amount would buy at the current price,
+    /// including the reward that is not distributed by `ping` yet.
+    pub fn estimate_shares_for_amount(&self, amount: U128) -> U128 {
+        let amount = UncToken::from_attounc(amount.0);
+        let (total_staked_balance, total_stake_shares) = self.internal_projected_totals();
+        // Without any shares the first staker gets the shares 1:1.
+        if total_stake_shares.is_zero() || total_staked_balance.is_zero() {
+            return amount.as_attounc().into();
+        }
+        internal::num_shares_from_staked_amount_rounded_down(
+            total_staked_balance,
+            total_stake_shares,
+            amount,
+        )
+        .as_attounc()
+        .into()
+    }
+
+    /// Returns the staked amount the given number of "stake" shares is worth at the current price,
+    /// including the reward that is not distributed by `ping` yet.
+    pub fn estimate_amount_for_shares(&self, shares: U128) -> U128 {
+        let shares = UncToken::from_attounc(shares.0);
+        let (total_staked_balance, total_stake_shares) = self.internal_projected_totals();
+        // Without any shares the first staker gets the shares 1:1.
+        if total_stake_shares.is_zero() || total_staked_balance.is_zero() {
+            return shares.as_attounc().into();
+        }
+        internal::staked_amount_from_num_shares_rounded_down(
+            total_staked_balance,
+            total_stake_shares,
+            shares,
+        )
+        .as_attounc()
+        .into()
+    }
+
+    /// Returns the smallest amount that buys at least one "stake" share at the current price,
+    /// including the reward that is not distributed by `ping` yet. Staking a smaller amount
+    /// panics. It doesn't account for `min_stake_amount`.
+    pub fn minimum_stakeable_amount(&self) -> U128 {
+        let (total_staked_balance, total_stake_shares) = self.internal_projected_totals();
+        // Without any shares the first staker gets the shares 1:1.
+        if total_stake_shares.is_zero() || total_staked_balance.is_zero() {
+            return 1.into();
+        }
+        // `num_shares = total_stake_shares * amount / total_staked_balance` rounded down is
+        // positive once the amount reaches the price of one share rounded up.
+        internal::staked_amount_from_num_shares_rounded_up(
+            total_staked_balance,
+            total_stake_shares,
+            UncToken::from_attounc(1),
+        )
+        .as_attounc()
+        .into()
+    }
+
+    /// Panics if the accounting of the pool is inconsistent, e.g. after slashing or a migration.
+    /// Iterates over all accounts, so it might run out of gas for a pool with many accounts.
+    pub fn check_invariants(&self) {
+        self.assert_invariants();
+    }
+
+    /// Returns the preview of `handle_slashing` without changing the state: the amount the total
+    /// balance dropped below the last total balance, and the resulting staked balance, share price
+    /// and loss of every delegator.
+    pub fn preview_slashing_impact(&self) -> SlashingPreview {
+        let slashed_amount = self.internal_slashed_amount();
+        let total_staked_balance = self.total_staked_balance.saturating_sub(slashed_amount);
+        // Without any shares the first staker gets the shares 1:1.
+        let stake_share_price = if self.total_stake_shares.is_zero() {
+            STAKE_SHARE_PRICE_UNIT
+        } else {
+            internal::staked_amount_from_num_shares_rounded_down(
+                total_staked_balance,
+                self.total_stake_shares,
+                STAKE_SHARE_PRICE_UNIT,
+            )
+        };
+        let loss_basis_points = if self.total_staked_balance.is_zero() {
+            0
+        } else {
+            (U256::from(slashed_amount.min(self.total_staked_balance).as_attounc()) * U256::from(MAX_BASIS_POINTS)
+                / U256::from(self.total_staked_balance.as_attounc()))
+            .as_u32()
+        };
+        SlashingPreview {
+            slashed_amount: slashed_amount.as_attounc().into(),
+            total_staked_balance: total_staked_balance.as_attounc().into(),
+            stake_share_price: stake_share_price.as_attounc().into(),
+            loss_basis_points,
+        }
+    }
+
     /// Returns human readable representation of the account for the given account ID.
     pub fn get_account(&self, account_id: &AccountId) -> HumanReadableAccount {
         let account = self.internal_get_account(account_id);
@@ -393,19 +926,26 @@ impl StakingContract {
         assert_eq!(
             env::current_account_id(),
             env::predecessor_account_id(),
-            "Can be called only as a callback"
+            "{}",
+            StakingError::NotCallback
         );
 
         assert_eq!(
             env::promise_results_count(),
             1,
-            "Contract expected a result on the callback"
+            "{}",
+            StakingError::NoCallbackResult
         );
         let stake_action_succeeded = match env::promise_result(0) {
             PromiseResult::Successful(_) => true,
             _ => false,
         };
 
+        // If the stake action failed, the stake at the validator is unknown, so the next restake
+        // has to issue the stake action again.
+        if !stake_action_succeeded {
+            self.last_staked_balance = None;
+        }
         // If the stake action failed and the current locked amount is positive, then the contract
         // has to unstake.
         if !stake_action_succeeded && env::account_locked_balance() > UncToken::from_attounc(0) {
@@ -413,10 +953,108 @@ impl StakingContract {
         }
     }
 
+    pub fn on_withdraw(&mut self, account_id: AccountId, amount: U128) {
+        assert_eq!(
+            env::current_account_id(),
+            env::predecessor_account_id(),
+            "{}",
+            StakingError::NotCallback
+        );
+
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            StakingError::NoCallbackResult
+        );
+        let transfer_succeeded = matches!(env::promise_result(0), PromiseResult::Successful(_));
+
+        if !transfer_succeeded {
+            let amount = UncToken::from_attounc(amount.0);
+            let mut account = self.internal_get_account(&account_id);
+            account.unstaked = account.unstaked.saturating_add(amount);
+            self.internal_save_account(&account_id, &account);
+            // The failed transfer is refunded to this contract, so it's not a reward.
+            self.last_total_balance = self.last_total_balance.saturating_add(amount);
+
+            env::log_str(
+                format!(
+                    "Failed to transfer {} to @{}. New unstaked balance is {}",
+                    amount, account_id, account.unstaked
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    pub fn on_migrate_staking_key_action(&mut self, new_key: PublicKey) {
+        assert_eq!(
+            env::current_account_id(),
+            env::predecessor_account_id(),
+            "{}",
+            StakingError::NotCallback
+        );
+
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "{}",
+            StakingError::NoCallbackResult
+        );
+        let stake_action_succeeded =
+            matches!(env::promise_result(0), PromiseResult::Successful(_));
+
+        // If the stake action failed, the stake is still under the old key, so the stored key is
+        // kept as is.
+        if stake_action_succeeded {
+            env::log_str(format!("Migrated the stake to the staking key {:?}", new_key).as_str());
+            self.stake_public_key = new_key;
+        } else {
+            self.last_staked_balance = None;
+            env::log_str(
+                format!(
+                    "Failed to migrate the stake to the staking key {:?}. Keeping the current key",
+                    new_key
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    /// Accepts the ownership proposed by the owner with `propose_owner`.
+    /// Can only be called by the proposed owner.
+    pub fn accept_owner(&mut self) {
+        let predecessor_account_id = env::predecessor_account_id();
+        assert_eq!(
+            Some(&predecessor_account_id),
+            self.proposed_owner_id.as_ref(),
+            "{}",
+            StakingError::NotProposedOwner
+        );
+        env::log_str(
+            format!(
+                "The ownership is transferred from @{} to @{}",
+                self.owner_id, predecessor_account_id
+            )
+            .as_str(),
+        );
+        self.owner_id = predecessor_account_id;
+        self.proposed_owner_id = None;
+    }
+
     /*******************/
     /* Owner's methods */
     /*******************/
 
+    /// Owner's method.
+    /// Proposes the given account ID as the new owner. The owner doesn't change until the proposed
+    /// account calls `accept_owner`, so a mistyped account ID can't take the ownership. Proposing
+    /// another account replaces the previous proposal.
+    pub fn propose_owner(&mut self, new_owner_id: AccountId) {
+        self.assert_owner();
+        self.proposed_owner_id = Some(new_owner_id);
+    }
+
     /// Owner's method.
     /// Updates current public key to the new given public key.
     pub fn update_staking_key(&mut self, stake_public_key: PublicKey) {
@@ -424,7 +1062,27 @@ impl StakingContract {
         // When updating the staking key, the contract has to restake.
         let _need_to_restake = self.internal_ping();
         self.stake_public_key = stake_public_key.into();
-        self.internal_restake();
+        self.internal_stake_action();
+    }
+
+    /// Owner's method.
+    /// Moves the whole `total_staked_balance` to the new staking key in a single stake action.
+    /// Unlike `update_staking_key`, the stored key is only replaced once the stake action succeeds.
+    pub fn migrate_staking_key(&mut self, new_key: PublicKey) -> Promise {
+        self.assert_owner();
+        assert!(!self.paused, "{}", StakingError::StakingPaused);
+
+        self.internal_ping();
+        self.last_staked_balance = Some(self.total_staked_balance);
+        // The stake action sets the absolute amount for the account, so staking with the new key
+        // effectively unstakes from the current key.
+        Promise::new(env::current_account_id())
+            .stake(self.total_staked_balance, new_key.clone())
+            .then(ext_self::ext(env::current_account_id())
+                .with_static_gas(ON_STAKE_ACTION_GAS)
+                .with_attached_deposit(NO_DEPOSIT)
+                .on_migrate_staking_key_action(new_key),
+            )
     }
 
     /// Owner's method.
@@ -441,66 +1099,369 @@ impl StakingContract {
     }
 
     /// Owner's method.
-    /// Calls `vote(is_vote)` on the given voting contract account ID on behalf of the pool.
-    pub fn vote(&mut self, voting_account_id: AccountId, is_vote: bool) -> Promise {
+    /// Updates current reward fee to the given number of basis points, e.g. 500 for 5%.
+    pub fn set_reward_fee_basis_points(&mut self, bps: u16) {
+        self.update_reward_fee_fraction(RewardFeeFraction::from_basis_points(bps));
+    }
+
+    /// Owner's method.
+    /// Updates the account that receives the reward fee. Passing `null` makes the owner receive
+    /// it. The reward that is not distributed yet goes to the previous recipient.
+    pub fn set_fee_recipient(&mut self, fee_recipient: Option<AccountId>) {
+        self.assert_owner();
+
+        let need_to_restake = self.internal_ping();
+        self.fee_recipient = fee_recipient;
+        if need_to_restake {
+            self.internal_restake();
+        }
+    }
+
+    /// Owner's method.
+    /// Splits the reward fee among the given recipients, each receiving the given fraction of the
+    /// fee. The fractions have to sum to 1. The split takes precedence over the fee recipient.
+    /// Passing an empty list makes the fee recipient receive the whole fee again. The reward that
+    /// is not distributed yet goes to the previous recipients.
+    pub fn set_fee_split(&mut self, fee_split: Vec<(AccountId, RewardFeeFraction)>) {
         self.assert_owner();
         assert!(
-            env::is_valid_account_id(voting_account_id.as_bytes()),
-            "Invalid voting account ID"
+            fee_split.len() <= MAX_FEE_SPLIT_RECIPIENTS,
+            "{}, the maximum is {}",
+            StakingError::TooManyFeeSplitRecipients,
+            MAX_FEE_SPLIT_RECIPIENTS
         );
+        if !fee_split.is_empty() {
+            // Summing the fractions exactly, reducing the sum after every step.
+            let (mut numerator, mut denominator) = (U256::from(0), U256::from(1));
+            for (_, fraction) in fee_split.iter() {
+                fraction.assert_valid();
+                numerator = numerator * U256::from(fraction.denominator)
+                    + U256::from(fraction.numerator) * denominator;
+                denominator *= U256::from(fraction.denominator);
+                let gcd = internal::gcd(numerator, denominator);
+                numerator /= gcd;
+                denominator /= gcd;
+            }
+            assert_eq!(
+                numerator,
+                denominator,
+                "{}",
+                StakingError::FeeSplitNotSummingToOne
+            );
+        }
 
-        ext_voting::ext(voting_account_id.clone())
-            .with_static_gas(VOTE_GAS)
-            .with_attached_deposit(NO_DEPOSIT)
-            .vote(is_vote)
+        let need_to_restake = self.internal_ping();
+        self.fee_split = fee_split;
+        if need_to_restake {
+            self.internal_restake();
+        }
     }
 
     /// Owner's method.
-    /// Pauses pool staking.
-    pub fn pause_staking(&mut self) {
+    /// Updates the cap on the total staked balance. Passing `null` removes the cap.
+    /// It doesn't affect the balance that is already staked.
+    pub fn update_max_total_staked_balance(&mut self, max_total_staked_balance: Option<U128>) {
         self.assert_owner();
-        assert!(!self.paused, "The staking is already paused");
-
-        self.internal_ping();
-        self.paused = true;
-        Promise::new(env::current_account_id()).stake(UncToken::from_attounc(0), self.stake_public_key.clone());
+        self.max_total_staked_balance = max_total_staked_balance
+            .map(|max_total_staked_balance| UncToken::from_attounc(max_total_staked_balance.0));
     }
 
     /// Owner's method.
-    /// Resumes pool staking.
-    pub fn resume_staking(&mut self) {
+    /// Updates the cap on the number of accounts. Passing `null` removes the cap.
+    /// It doesn't affect the existing accounts, even if there are more of them than the new cap.
+    pub fn update_max_num_accounts(&mut self, max_num_accounts: Option<u64>) {
         self.assert_owner();
-        assert!(self.paused, "The staking is not paused");
+        self.max_num_accounts = max_num_accounts;
+    }
 
-        self.internal_ping();
-        self.paused = false;
-        self.internal_restake();
+    /// Owner's method.
+    /// Updates the minimum amount that can be staked with `stake` or `deposit_and_stake`.
+    pub fn update_min_stake_amount(&mut self, min_stake_amount: U128) {
+        self.assert_owner();
+        self.min_stake_amount = UncToken::from_attounc(min_stake_amount.0);
     }
-}
 
-#[cfg(all(test, not(target_arch = "wasm32")))]
-mod tests {
-    use super::*;
+    /// Owner's method.
+    /// Updates the minimum reward `ping` distributes. The reward accumulated so far is distributed
+    /// by the first `ping` in the next epoch if it reaches the new minimum.
+    pub fn update_min_reward_to_distribute(&mut self, min_reward_to_distribute: U128) {
+        self.assert_owner();
+        self.min_reward_to_distribute = UncToken::from_attounc(min_reward_to_distribute.0);
+    }
 
-    use unc_sdk::{serde_json, testing_env, test_vm_config, RuntimeFeesConfig, VMContext};
-    use unc_sdk::test_utils::VMContextBuilder;
-    use unc_sdk::test_utils::get_created_receipts;
-    use crate::test_utils::*;
-    struct Emulator {
-        pub contract: StakingContract,
-        pub epoch_height: EpochHeight,
-        pub amount: UncToken,
-        pub locked_amount: UncToken,
-        last_total_staked_balance: UncToken,
-        last_total_stake_shares: UncToken,
-        pub context: VMContext,
+    /// Owner's method.
+    /// Updates the number of epochs the unstaked balance stays locked after an unstake action,
+    /// e.g. after the protocol changes the unstaking period. It only applies to new unstake
+    /// actions, so the balance that is already unstaked keeps its unlock epoch.
+    pub fn set_num_epochs_to_unlock(&mut self, epochs: EpochHeight) {
+        self.assert_owner();
+        assert!(
+            epochs > 0 && epochs <= MAX_NUM_EPOCHS_TO_UNLOCK,
+            "{}, it should be between 1 and {}",
+            StakingError::NumEpochsToUnlockOutOfRange,
+            MAX_NUM_EPOCHS_TO_UNLOCK
+        );
+        self.num_epochs_to_unlock = epochs;
     }
 
-    fn zero_fee() -> RewardFeeFraction {
-        RewardFeeFraction {
-            numerator: 0,
-            denominator: 1,
-        }
+    /// Owner's method.
+    /// Enables or disables the restricted mode. In the restricted mode only the allowed delegators
+    /// can deposit.
+    pub fn set_restricted_mode(&mut self, restricted_mode: bool) {
+        self.assert_owner();
+        self.restricted_mode = restricted_mode;
+    }
+
+    /// Owner's method.
+    /// Allows the given account to deposit in the restricted mode.
+    /// Returns `false` if the account was already allowed.
+    pub fn add_delegator(&mut self, account_id: AccountId) -> bool {
+        self.assert_owner();
+        self.delegators.insert(account_id)
+    }
+
+    /// Owner's method.
+    /// Disallows the given account to deposit in the restricted mode. It doesn't affect the balance
+    /// of the account.
+    /// Returns `false` if the account was not allowed.
+    pub fn remove_delegator(&mut self, account_id: AccountId) -> bool {
+        self.assert_owner();
+        self.delegators.remove(&account_id)
+    }
+
+    /// Owner's method.
+    /// Moves the given number of "stake" shares from the owner's account to the given account,
+    /// e.g. as a loyalty bonus. The total staked balance and the total number of shares don't
+    /// change. Emits the `shares_gifted` event.
+    pub fn gift_shares(&mut self, account_id: AccountId, shares: U128) {
+        self.assert_owner();
+        assert!(shares.0 > 0, "{}", StakingError::ZeroSharesToGift);
+        assert_ne!(account_id, self.owner_id, "{}", StakingError::GiftToOwner);
+        if let Some(max_num_accounts) = self.max_num_accounts {
+            assert!(
+                self.accounts.contains_key(&account_id)
+                    || u64::from(self.accounts.len()) < max_num_accounts,
+                "{}",
+                StakingError::MaxNumAccountsReached
+            );
+        }
+
+        // Distributes the pending reward first, so the owner can gift the fee it earned.
+        let need_to_restake = self.internal_ping();
+
+        let owner_id = self.owner_id.clone();
+        let shares_to_gift = UncToken::from_attounc(shares.0);
+        let mut owner_account = self.internal_get_account(&owner_id);
+        assert!(
+            owner_account.stake_shares >= shares_to_gift,
+            "{}",
+            StakingError::NotEnoughSharesToGift
+        );
+        owner_account.stake_shares = owner_account.stake_shares.saturating_sub(shares_to_gift);
+        self.internal_save_account(&owner_id, &owner_account);
+
+        let mut account = self.internal_get_account(&account_id);
+        account.stake_shares = account.stake_shares.saturating_add(shares_to_gift);
+        self.internal_save_account(&account_id, &account);
+
+        env::log_str(
+            format!(
+                "@{} gifted {} shares to @{}. Total {} shares",
+                owner_id, shares.0, account_id, account.stake_shares
+            )
+            .as_str(),
+        );
+        StakingPoolEvent::SharesGifted { owner_id, account_id, shares }.emit();
+
+        if need_to_restake {
+            self.internal_restake();
+        }
+    }
+
+    /// Owner's method.
+    /// Recovers the pool after the validator was slashed.
+    /// After slashing the total balance of the account is less than the last total balance, so
+    /// `ping` and every method that calls it panic until this method is called.
+    /// The slashed amount is taken from the total staked balance, which lowers the price of the
+    /// "stake" shares, so every delegator loses proportionally to their stake.
+    pub fn handle_slashing(&mut self) {
+        self.assert_owner();
+
+        let epoch_height = env::epoch_height();
+        let slashed_amount = self.internal_slashed_amount();
+        assert!(
+            slashed_amount.as_attounc() > 0,
+            "{}",
+            StakingError::NoSlashing
+        );
+        self.total_staked_balance = self.total_staked_balance.saturating_sub(slashed_amount);
+        self.last_total_balance = self.internal_total_balance();
+        self.last_epoch_height = epoch_height;
+
+        env::log_str(
+            format!(
+                "Epoch {}: Contract was slashed by {} tokens. New total staked balance is {}. Total number of shares {}",
+                epoch_height, slashed_amount, self.total_staked_balance, self.total_stake_shares,
+            )
+            .as_str(),
+        );
+        StakingPoolEvent::Slashed {
+            epoch_height,
+            slashed_amount: slashed_amount.as_attounc().into(),
+            total_staked_balance: self.total_staked_balance.as_attounc().into(),
+        }
+        .emit();
+        self.internal_restake();
+    }
+
+    /// Owner's method.
+    /// Reconciles `last_total_balance` with the real balance of the account, e.g. after tokens
+    /// were transferred to the pool account directly. Otherwise the surplus would be distributed
+    /// as a reward by the first `ping` in the next epoch.
+    /// The staking rewards are credited to the locked balance, so the part of the locked balance
+    /// above the last stake action is not a surplus. It's distributed by `ping` as usual, including
+    /// the rewards deferred by `min_reward_to_distribute`.
+    /// If `treat_surplus_as_reward` is `true`, the surplus is distributed as a reward right away,
+    /// including the reward fee. Otherwise, the surplus is transferred to the given `receiver_id`,
+    /// e.g. to return it to the sender.
+    pub fn sync_balance(&mut self, treat_surplus_as_reward: bool, receiver_id: Option<AccountId>) {
+        self.assert_owner();
+        assert!(
+            treat_surplus_as_reward || receiver_id.is_some(),
+            "{}",
+            StakingError::NoSurplusReceiver
+        );
+
+        // The surplus is measured before `ping`, so it isn't distributed as a reward of the epoch.
+        let total_balance = self.internal_total_balance();
+        assert!(
+            total_balance >= self.last_total_balance,
+            "{}",
+            StakingError::TotalBalanceDecreased
+        );
+        let pending_reward = env::account_locked_balance()
+            .saturating_sub(self.last_staked_balance.unwrap_or(self.total_staked_balance));
+        let surplus = total_balance
+            .saturating_sub(self.last_total_balance)
+            .saturating_sub(pending_reward);
+        self.last_total_balance = self.last_total_balance.saturating_add(surplus);
+        self.internal_ping();
+
+        if surplus.is_zero() {
+            env::log_str("The last total balance is in sync");
+        } else if treat_surplus_as_reward {
+            self.internal_distribute_reward(env::epoch_height(), surplus);
+        } else {
+            let receiver_id = receiver_id.unwrap();
+            env::log_str(
+                format!("Transferring the surplus of {} to @{}", surplus, receiver_id).as_str(),
+            );
+            Promise::new(receiver_id).transfer(surplus);
+            self.last_total_balance = self.last_total_balance.saturating_sub(surplus);
+        }
+
+        // Restakes only if the total staked balance differs from the current stake.
+        self.internal_restake();
+    }
+
+    /// Owner's method.
+    /// Calls `vote(is_vote)` on the given voting contract account ID on behalf of the pool.
+    pub fn vote(&mut self, voting_account_id: AccountId, is_vote: bool) -> Promise {
+        self.assert_owner();
+        assert!(
+            env::is_valid_account_id(voting_account_id.as_bytes()),
+            "{}",
+            StakingError::InvalidVotingAccountId
+        );
+
+        ext_voting::ext(voting_account_id.clone())
+            .with_static_gas(VOTE_GAS)
+            .with_attached_deposit(NO_DEPOSIT)
+            .vote(is_vote)
+    }
+
+    /// Owner's method.
+    /// Pauses pool staking.
+    pub fn pause_staking(&mut self) {
+        self.assert_owner();
+        assert!(!self.paused, "{}", StakingError::StakingAlreadyPaused);
+
+        self.internal_ping();
+        self.paused = true;
+        self.last_staked_balance = Some(UncToken::from_attounc(0));
+        Promise::new(env::current_account_id()).stake(UncToken::from_attounc(0), self.stake_public_key.clone());
+    }
+
+    /// Owner's method.
+    /// Resumes pool staking.
+    pub fn resume_staking(&mut self) {
+        self.assert_owner();
+        assert!(self.paused, "{}", StakingError::StakingNotPaused);
+
+        self.internal_ping();
+        self.paused = false;
+        self.internal_stake_action();
+    }
+
+    /// Owner's method.
+    /// Emergency unstake: drops the whole validator stake to zero and pauses the staking.
+    /// The balances and the "stake" shares of the accounts are kept as is.
+    /// Unlike `pause_staking`, it can be called while the staking is already paused, e.g. to
+    /// retry a stake action that failed to land.
+    pub fn unstake_all_from_validator(&mut self) -> Promise {
+        self.assert_owner();
+
+        self.internal_ping();
+        self.paused = true;
+        self.last_staked_balance = Some(UncToken::from_attounc(0));
+        env::log_str("The validator stake is dropped to zero and the staking is paused");
+        Promise::new(env::current_account_id())
+            .stake(UncToken::from_attounc(0), self.stake_public_key.clone())
+    }
+
+    /// Owner's method.
+    /// Resumes the staking after `unstake_all_from_validator` by staking the whole
+    /// `total_staked_balance` again.
+    pub fn restake_all(&mut self) -> Promise {
+        self.assert_owner();
+        assert!(self.paused, "{}", StakingError::StakingNotPaused);
+
+        self.internal_ping();
+        self.paused = false;
+        self.last_staked_balance = Some(self.total_staked_balance);
+        Promise::new(env::current_account_id())
+            .stake(self.total_staked_balance, self.stake_public_key.clone())
+            .then(ext_self::ext(env::current_account_id())
+                .with_static_gas(ON_STAKE_ACTION_GAS)
+                .with_attached_deposit(NO_DEPOSIT)
+                .on_stake_action(),
+            )
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    use unc_sdk::{serde_json, testing_env, test_vm_config, RuntimeFeesConfig, VMContext};
+    use unc_sdk::test_utils::VMContextBuilder;
+    use unc_sdk::test_utils::{get_created_receipts, get_logs};
+    use crate::test_utils::*;
+    struct Emulator {
+        pub contract: StakingContract,
+        pub epoch_height: EpochHeight,
+        pub amount: UncToken,
+        pub locked_amount: UncToken,
+        last_total_staked_balance: UncToken,
+        last_total_stake_shares: UncToken,
+        pub context: VMContext,
+    }
+
+    fn zero_fee() -> RewardFeeFraction {
+        RewardFeeFraction {
+            numerator: 0,
+            denominator: 1,
+        }
     }
 
     impl Emulator {
@@ -508,6 +1469,31 @@ mod tests {
             owner: AccountId,
             stake_public_key: PublicKey,
             reward_fee_fraction: RewardFeeFraction,
+        ) -> Self {
+            Self::new_with_num_epochs_to_unlock(owner, stake_public_key, reward_fee_fraction, None)
+        }
+
+        pub fn new_with_num_epochs_to_unlock(
+            owner: AccountId,
+            stake_public_key: PublicKey,
+            reward_fee_fraction: RewardFeeFraction,
+            num_epochs_to_unlock: Option<EpochHeight>,
+        ) -> Self {
+            Self::new_with_init_args(
+                owner,
+                stake_public_key,
+                reward_fee_fraction,
+                num_epochs_to_unlock,
+                None,
+            )
+        }
+
+        pub fn new_with_init_args(
+            owner: AccountId,
+            stake_public_key: PublicKey,
+            reward_fee_fraction: RewardFeeFraction,
+            num_epochs_to_unlock: Option<EpochHeight>,
+            epochs_per_year: Option<u64>,
         ) -> Self {
             let context = VMContextBuilder::new()
                 .current_account_id(owner.clone())
@@ -518,6 +1504,8 @@ mod tests {
                 owner,
                 stake_public_key,
                 reward_fee_fraction,
+                num_epochs_to_unlock,
+                epochs_per_year,
             );
             let last_total_staked_balance = contract.total_staked_balance;
             let last_total_stake_shares = contract.total_stake_shares;
@@ -585,7 +1573,7 @@ mod tests {
             zero_fee(),
         );
         emulator.update_context(bob(), UncToken::from_attounc(0));
-        emulator.contract.internal_restake();
+        emulator.contract.internal_stake_action();
         let receipts = get_created_receipts();
         assert_eq!(receipts.len(), 2);
         // Mocked Receipt fields are private, so can't check directly.
@@ -617,7 +1605,7 @@ mod tests {
     }
 
     #[test]
-    fn test_deposit_withdraw() {
+    fn test_restake_skipped_when_stake_unchanged() {
         let mut emulator = Emulator::new(
             owner(),
             "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
@@ -625,105 +1613,2283 @@ mod tests {
         );
         let deposit_amount = ntoy(1_000_000);
         emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
-        emulator.contract.deposit();
+        emulator.contract.deposit_and_stake();
         emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+
+        // Next epoch without any rewards, so the staked balance did not change.
+        emulator.epoch_height += 1;
         emulator.update_context(bob(), UncToken::from_attounc(0));
-        assert_eq!(
-            emulator.contract.get_account_unstaked_balance(&bob()).0,
-            deposit_amount
-        );
-        emulator.contract.withdraw(deposit_amount.into());
-        assert_eq!(
-            emulator.contract.get_account_unstaked_balance(&bob()).0,
-            0u128
-        );
+        emulator.contract.ping();
+        assert!(get_created_receipts().is_empty());
+
+        // The stake changes, so the stake action has to be issued.
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake(ntoy(1_000).into());
+        assert_eq!(get_created_receipts().len(), 2);
     }
 
     #[test]
-    fn test_stake_with_fee() {
+    fn test_restake_after_unstake_not_yet_applied() {
         let mut emulator = Emulator::new(
             owner(),
             "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
-            RewardFeeFraction {
-                numerator: 10,
-                denominator: 100,
-            },
+            zero_fee(),
         );
         let deposit_amount = ntoy(1_000_000);
         emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
-        emulator.contract.deposit();
+        emulator.contract.deposit_and_stake();
         emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
-        emulator.update_context(bob(), UncToken::from_attounc(0));
-        emulator.contract.stake(deposit_amount.into());
         emulator.simulate_stake_call();
-        assert_eq!(
-            emulator.contract.get_account_staked_balance(&bob()).0,
-            deposit_amount
-        );
-
         let locked_amount = emulator.locked_amount;
-        let n_locked_amount = yton(locked_amount.as_attounc());
-        emulator.skip_epochs(10);
-        // Overriding rewards (+ 100K reward)
-        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(100_000)));
+
         emulator.update_context(bob(), UncToken::from_attounc(0));
-        emulator.contract.ping();
-        let expected_amount = deposit_amount
-            + ntoy((yton(deposit_amount) * 90_000 + n_locked_amount / 2) / n_locked_amount);
-        assert_eq_in_unc!(
-            emulator.contract.get_account_staked_balance(&bob()).0,
-            expected_amount
-        );
-        // Owner got 10% of the rewards
-        assert_eq_in_unc!(
-            emulator.contract.get_account_staked_balance(&owner()).0,
-            ntoy(10_000)
+        emulator.contract.unstake(ntoy(1_000).into());
+        assert_eq!(get_created_receipts().len(), 2);
+
+        // The unstake hasn't taken effect yet, so the locked balance is still the same. Staking
+        // the amount back has to restake, since the last stake action sent the smaller amount.
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.stake(ntoy(1_000).into());
+        assert_eq!(emulator.contract.total_staked_balance, locked_amount);
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert!(serde_json::to_string(&receipts[0].actions)
+            .unwrap()
+            .contains(&format!("\"stake\":\"{}\"", locked_amount.as_attounc())));
+    }
+
+    fn migrate_staking_key_with_result(promise_result: PromiseResult) -> Emulator {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
         );
+        let new_key: PublicKey = "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp"
+            .parse()
+            .unwrap();
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.migrate_staking_key(new_key.clone());
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert!(serde_json::to_string(&receipts[0].actions)
+            .unwrap()
+            .contains("[{\"Stake\":{\"receipt_index\":0,\"stake\":\"29999999999999000000000000\",\"public_key\":\"ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp\"}}]"));
+        // The key is not updated until the stake action succeeds.
+        assert_ne!(emulator.contract.get_staking_key(), new_key);
 
-        let locked_amount = emulator.locked_amount;
-        let n_locked_amount = yton(locked_amount.as_attounc());
-        emulator.skip_epochs(10);
-        // Overriding rewards (another 100K reward)
-        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(100_000)));
+        emulator.update_context(staking(), UncToken::from_attounc(0));
+        testing_env!(
+            emulator.context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![promise_result],
+        );
+        emulator.contract.on_migrate_staking_key_action(new_key);
+        emulator
+    }
 
-        emulator.update_context(bob(), UncToken::from_attounc(0));
-        emulator.contract.ping();
-        // previous balance plus (1_090_000 / 1_100_030)% of the 90_000 reward (rounding to uncest).
-        assert_eq_in_unc!(
-            emulator.contract.get_account_staked_balance(&bob()).0,
-            expected_amount
-                + ntoy((yton(expected_amount) * 90_000 + n_locked_amount / 2) / n_locked_amount)
+    #[test]
+    fn test_migrate_staking_key() {
+        let emulator = migrate_staking_key_with_result(PromiseResult::Successful(vec![]));
+        assert_eq!(
+            emulator.contract.get_staking_key(),
+            "ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap()
         );
-        // owner earns 10% with the fee and also small percentage from restaking.
-        assert_eq_in_unc!(
-            emulator.contract.get_account_staked_balance(&owner()).0,
-            ntoy(10_000)
-                + ntoy(10_000)
-                + ntoy((10_000u128 * 90_000 + n_locked_amount / 2) / n_locked_amount)
+    }
+
+    #[test]
+    fn test_migrate_staking_key_fail() {
+        let emulator = migrate_staking_key_with_result(PromiseResult::Failed);
+        assert_eq!(
+            emulator.contract.get_staking_key(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap()
         );
+        assert!(get_created_receipts().is_empty());
+    }
 
-        assert_eq!(emulator.contract.get_number_of_accounts(), 2);
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_migrate_staking_key_not_owner() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator
+            .contract
+            .migrate_staking_key("ed25519:6E8sCci9badyRkXb3JoRpBj5p8C6Tw41ELDZoiihKEtp".parse().unwrap());
     }
 
     #[test]
-    fn test_stake_unstake() {
+    fn test_deposit_and_stake() {
         let mut emulator = Emulator::new(
             owner(),
-            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
             zero_fee(),
         );
         let deposit_amount = ntoy(1_000_000);
+        let total_staked_balance = emulator.contract.total_staked_balance;
         emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
-        emulator.contract.deposit();
-        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
-        emulator.update_context(bob(), UncToken::from_attounc(0));
-        emulator.contract.stake(deposit_amount.into());
-        emulator.simulate_stake_call();
+        emulator.contract.deposit_and_stake();
+        // Stake action and the `on_stake_action` callback.
+        assert_eq!(get_created_receipts().len(), 2);
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, 0);
         assert_eq!(
             emulator.contract.get_account_staked_balance(&bob()).0,
             deposit_amount
         );
-        let locked_amount = emulator.locked_amount;
+        assert_eq!(
+            emulator.contract.total_staked_balance,
+            total_staked_balance.saturating_add(UncToken::from_attounc(deposit_amount))
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The calculated number of \"stake\" shares received for staking should be positive"
+    )]
+    fn test_deposit_and_stake_dust() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.simulate_stake_call();
+        // Rewards raise the "stake" share price above 1.
+        emulator.skip_epochs(10);
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+
+        emulator.update_context(bob(), UncToken::from_attounc(1));
+        emulator.contract.deposit_and_stake();
+    }
+
+    fn stake_up_to_cap() -> Emulator {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let max_total_staked_balance =
+            emulator.contract.total_staked_balance.as_attounc() + ntoy(1_000);
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator
+            .contract
+            .update_max_total_staked_balance(Some(max_total_staked_balance.into()));
+        assert_eq!(
+            emulator.contract.get_max_total_staked_balance(),
+            Some(max_total_staked_balance.into())
+        );
+
+        emulator.update_context(bob(), UncToken::from_attounc(ntoy(1_000)));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(1_000)));
+        emulator.simulate_stake_call();
+        assert_eq!(
+            emulator.contract.get_total_staked_balance().0,
+            max_total_staked_balance
+        );
+        emulator
+    }
+
+    #[test]
+    fn test_stake_up_to_max_total_staked_balance() {
+        let mut emulator = stake_up_to_cap();
+        // Unstaking is always allowed.
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake(ntoy(100).into());
+        emulator.simulate_stake_call();
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, ntoy(100));
+    }
+
+    #[test]
+    #[should_panic(expected = "would exceed the maximum total staked balance")]
+    fn test_stake_over_max_total_staked_balance() {
+        let mut emulator = stake_up_to_cap();
+        emulator.update_context(alice(), UncToken::from_attounc(ntoy(1)));
+        emulator.contract.deposit_and_stake();
+    }
+
+    #[test]
+    fn test_deposit_up_to_max_num_accounts() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.update_max_num_accounts(Some(2));
+        assert_eq!(emulator.contract.get_max_num_accounts(), Some(2));
+
+        for account_id in [alice(), bob()] {
+            emulator.update_context(account_id, UncToken::from_attounc(ntoy(100)));
+            emulator.contract.deposit();
+            emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(100)));
+        }
+        assert_eq!(emulator.contract.get_number_of_accounts(), 2);
+
+        // The existing accounts can still deposit.
+        emulator.update_context(alice(), UncToken::from_attounc(ntoy(100)));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(100)));
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&alice()).0, ntoy(200));
+    }
+
+    #[test]
+    #[should_panic(expected = "The maximum number of accounts is reached")]
+    fn test_deposit_over_max_num_accounts() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.update_max_num_accounts(Some(1));
+        emulator.update_context(alice(), UncToken::from_attounc(ntoy(100)));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(100)));
+
+        emulator.update_context(bob(), UncToken::from_attounc(ntoy(100)));
+        emulator.contract.deposit();
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_update_max_num_accounts_not_owner() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.update_max_num_accounts(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_update_max_total_staked_balance_not_owner() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.update_max_total_staked_balance(None);
+    }
+
+    #[test]
+    fn test_set_reward_fee_basis_points() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        assert_eq!(emulator.contract.get_reward_fee_basis_points(), 0);
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.set_reward_fee_basis_points(500);
+        assert_eq!(emulator.contract.get_reward_fee_basis_points(), 500);
+        let reward_fee_fraction = emulator.contract.get_reward_fee_fraction();
+        assert_eq!(
+            reward_fee_fraction.multiply(UncToken::from_attounc(ntoy(100))).as_attounc(),
+            ntoy(5)
+        );
+
+        emulator.contract.update_reward_fee_fraction(RewardFeeFraction {
+            numerator: 1,
+            denominator: 3,
+        });
+        assert_eq!(emulator.contract.get_reward_fee_basis_points(), 3333);
+    }
+
+    #[test]
+    #[should_panic(expected = "The reward fee is too high, it must be less or equal to 10000 basis points")]
+    fn test_set_reward_fee_basis_points_above_max() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.set_reward_fee_basis_points(12_000);
+    }
+
+    fn with_min_stake_amount(min_stake_amount: u128) -> Emulator {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.update_min_stake_amount(min_stake_amount.into());
+        assert_eq!(emulator.contract.get_min_stake_amount().0, min_stake_amount);
+        emulator
+    }
+
+    #[test]
+    fn test_stake_at_min_stake_amount() {
+        let mut emulator = with_min_stake_amount(ntoy(10));
+        emulator.update_context(bob(), UncToken::from_attounc(ntoy(10)));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(10)));
+        emulator.simulate_stake_call();
+        assert_eq!(emulator.contract.get_account_staked_balance(&bob()).0, ntoy(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "is less than the minimum stake amount")]
+    fn test_deposit_and_stake_below_min_stake_amount() {
+        let mut emulator = with_min_stake_amount(ntoy(10));
+        emulator.update_context(bob(), UncToken::from_attounc(ntoy(9)));
+        emulator.contract.deposit_and_stake();
+    }
+
+    #[test]
+    #[should_panic(expected = "is less than the minimum stake amount")]
+    fn test_stake_below_min_stake_amount() {
+        let mut emulator = with_min_stake_amount(ntoy(10));
+        emulator.update_context(bob(), UncToken::from_attounc(ntoy(15)));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(15)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.stake(ntoy(5).into());
+    }
+
+    #[test]
+    fn test_stake_all_remainder_below_min_stake_amount() {
+        let mut emulator = with_min_stake_amount(ntoy(10));
+        emulator.update_context(bob(), UncToken::from_attounc(ntoy(15)));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(15)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.stake(ntoy(10).into());
+        emulator.simulate_stake_call();
+        // The remaining 5 is below the minimum, but `stake_all` is exempt.
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.stake_all();
+        emulator.simulate_stake_call();
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, 0);
+        assert_eq!(emulator.contract.get_account_staked_balance(&bob()).0, ntoy(15));
+    }
+
+    #[test]
+    fn test_deposit_distributes_rewards() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+        let total_staked_balance = emulator.contract.total_staked_balance;
+        let bob_staked_balance = emulator.contract.get_account_staked_balance(&bob()).0;
+
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(100)));
+        // The deposit itself distributes the reward without a separate `ping`.
+        emulator.update_context(alice(), UncToken::from_attounc(ntoy(10)));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(10)));
+
+        assert_eq!(
+            emulator.contract.total_staked_balance,
+            total_staked_balance.saturating_add(UncToken::from_attounc(ntoy(100)))
+        );
+        assert!(emulator.contract.get_account_staked_balance(&bob()).0 > bob_staked_balance);
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&alice()).0, ntoy(10));
+    }
+
+    #[test]
+    fn test_ping_emits_reward_distributed() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let total_staked_balance = emulator.contract.total_staked_balance;
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(10)));
+        // Anyone can ping.
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        assert_eq!(
+            emulator.contract.total_staked_balance,
+            total_staked_balance.saturating_add(UncToken::from_attounc(ntoy(10)))
+        );
+        let event = format!(
+            "EVENT_JSON:{{\"standard\":\"staking_pool\",\"version\":\"1.0.0\",\"event\":\"reward_distributed\",\"data\":{{\"epoch_height\":1,\"total_reward\":\"{}\",\"total_staked_balance\":\"{}\"}}}}",
+            ntoy(10),
+            emulator.contract.total_staked_balance.as_attounc()
+        );
+        assert!(get_logs().contains(&event));
+
+        // Pinging again in the same epoch doesn't distribute anything.
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        assert!(get_logs().is_empty());
+
+        // A new epoch without rewards doesn't emit the event.
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount;
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        assert!(!get_logs().iter().any(|log| log.starts_with("EVENT_JSON:")));
+    }
+
+    fn unstake_all_for_bob() -> Emulator {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake_all();
+        emulator.simulate_stake_call();
+        emulator
+    }
+
+    #[test]
+    #[should_panic(expected = "The unstaked balance is not yet available due to unstaking delay")]
+    fn test_withdraw_all_before_unlock() {
+        let mut emulator = unstake_all_for_bob();
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.withdraw_all();
+    }
+
+    #[test]
+    fn test_withdraw_all_after_unlock() {
+        let mut emulator = unstake_all_for_bob();
+        let unstaked_balance = emulator.contract.get_account_unstaked_balance(&bob()).0;
+        assert_eq!(emulator.contract.get_number_of_accounts(), 1);
+        emulator.skip_epochs(DEFAULT_NUM_EPOCHS_TO_UNLOCK);
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.withdraw_all();
+
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, bob());
+        assert_eq!(
+            serde_json::to_string(&receipts[0].actions).unwrap(),
+            format!("[{{\"Transfer\":{{\"receipt_index\":0,\"deposit\":\"{}\"}}}}]", unstaked_balance)
+        );
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, 0);
+        assert_eq!(emulator.contract.get_number_of_accounts(), 0);
+    }
+
+    #[test]
+    fn test_withdraw_all_for_by_owner() {
+        let mut emulator = unstake_all_for_bob();
+        emulator.skip_epochs(DEFAULT_NUM_EPOCHS_TO_UNLOCK);
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.withdraw_all_for(bob());
+
+        // The balance is transferred to the account, not to the caller.
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, bob());
+        assert_eq!(emulator.contract.get_number_of_accounts(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner or the account itself")]
+    fn test_withdraw_all_for_by_other_account() {
+        let mut emulator = unstake_all_for_bob();
+        emulator.skip_epochs(DEFAULT_NUM_EPOCHS_TO_UNLOCK);
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.withdraw_all_for(bob());
+    }
+
+    #[test]
+    fn test_withdraw_matured() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        for account_id in [alice(), bob()] {
+            emulator.update_context(account_id, UncToken::from_attounc(ntoy(1_000)));
+            emulator.contract.deposit();
+            emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(1_000)));
+        }
+        // Bob's unstaked balance is locked by the unstaking delay.
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.stake(ntoy(100).into());
+        emulator.simulate_stake_call();
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake(ntoy(100).into());
+        emulator.simulate_stake_call();
+
+        emulator.update_context(charlie(), UncToken::from_attounc(0));
+        emulator.contract.withdraw_matured(vec![alice(), bob(), charlie()]);
+        let receipts = get_created_receipts();
+        // Only Alice's transfer and its callback.
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, alice());
+        assert_eq!(receipts[1].receiver_id, staking());
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&alice()).0, 0);
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, ntoy(1_000));
+        emulator.amount = emulator.amount.saturating_sub(UncToken::from_attounc(ntoy(1_000)));
+
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(DEFAULT_NUM_EPOCHS_TO_UNLOCK);
+        emulator.locked_amount = locked_amount;
+        emulator.update_context(charlie(), UncToken::from_attounc(0));
+        emulator.contract.withdraw_matured(vec![alice(), bob()]);
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, bob());
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many accounts to withdraw at once, the maximum is 5")]
+    fn test_withdraw_matured_too_many_accounts() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(charlie(), UncToken::from_attounc(0));
+        emulator.contract.withdraw_matured(vec![alice(); MAX_WITHDRAW_MATURED_ACCOUNTS + 1]);
+    }
+
+    #[test]
+    fn test_withdraw_matured_full_batch_fits_in_gas() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let account_ids: Vec<AccountId> = (0..MAX_WITHDRAW_MATURED_ACCOUNTS)
+            .map(|i| format!("account{}", i).parse().unwrap())
+            .collect();
+        for account_id in account_ids.iter() {
+            emulator.update_context(account_id.clone(), UncToken::from_attounc(ntoy(1_000)));
+            emulator.contract.deposit();
+            emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(1_000)));
+        }
+        emulator.simulate_stake_call();
+
+        // The rewards of the new epoch have to be restaked as well.
+        emulator.skip_epochs(1);
+        emulator.update_context(charlie(), UncToken::from_attounc(0));
+        emulator.contract.withdraw_matured(account_ids);
+        // A transfer and its callback for every account, plus the stake action and its callback.
+        assert_eq!(get_created_receipts().len(), 2 * MAX_WITHDRAW_MATURED_ACCOUNTS + 2);
+        // At least a quarter of the gas limit is left as a margin.
+        assert!(env::used_gas().as_gas() <= env::prepaid_gas().as_gas() / 4 * 3);
+    }
+
+    fn withdraw_with_result(promise_result: PromiseResult) -> Emulator {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.withdraw(deposit_amount.into());
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, 0);
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, bob());
+        assert_eq!(receipts[1].receiver_id, staking());
+
+        if let PromiseResult::Successful(_) = promise_result {
+            emulator.amount = emulator.amount.saturating_sub(UncToken::from_attounc(deposit_amount));
+        }
+        emulator.update_context(staking(), UncToken::from_attounc(0));
+        testing_env!(
+            emulator.context.clone(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![promise_result],
+        );
+        emulator.contract.on_withdraw(bob(), deposit_amount.into());
+        emulator
+    }
+
+    #[test]
+    fn test_withdraw_failed_transfer() {
+        let mut emulator = withdraw_with_result(PromiseResult::Failed);
+        // The failed transfer is refunded to the pool and credited back to the account.
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, ntoy(1_000));
+        assert_eq!(
+            emulator.contract.last_total_balance,
+            emulator.amount.saturating_add(emulator.locked_amount)
+        );
+
+        // The refund is not distributed as a reward.
+        let total_staked_balance = emulator.contract.total_staked_balance;
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount;
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        assert_eq!(emulator.contract.total_staked_balance, total_staked_balance);
+    }
+
+    #[test]
+    fn test_withdraw_successful_transfer() {
+        let emulator = withdraw_with_result(PromiseResult::Successful(vec![]));
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, 0);
+        assert_eq!(emulator.contract.get_number_of_accounts(), 0);
+    }
+
+    fn staked_emulator() -> Emulator {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            RewardFeeFraction {
+                numerator: 10,
+                denominator: 100,
+            },
+        );
+        for (account_id, amount) in [(alice(), ntoy(1_000)), (bob(), ntoy(2_000))] {
+            emulator.update_context(account_id.clone(), UncToken::from_attounc(amount));
+            emulator.contract.deposit();
+            emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(amount));
+            emulator.update_context(account_id, UncToken::from_attounc(0));
+            emulator.contract.stake((amount / 3).into());
+            emulator.simulate_stake_call();
+        }
+        emulator
+    }
+
+    fn assert_single_stake_action(receipt: &unc_sdk::mock::Receipt, stake: UncToken) {
+        assert!(serde_json::to_string(&receipt.actions).unwrap().contains(&format!(
+            "[{{\"Stake\":{{\"receipt_index\":0,\"stake\":\"{}\",\"public_key\":\"ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7\"}}}}]",
+            stake.as_attounc()
+        )));
+    }
+
+    #[test]
+    fn test_unstake_all_from_validator() {
+        let mut emulator = staked_emulator();
+        let alice_staked = emulator.contract.get_account_staked_balance(&alice());
+        let total_staked = emulator.contract.get_total_staked_balance();
+
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.unstake_all_from_validator();
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_single_stake_action(&receipts[0], UncToken::from_attounc(0));
+        assert!(emulator.contract.is_staking_paused());
+        // The accounting of the delegators is not affected.
+        assert_eq!(emulator.contract.get_account_staked_balance(&alice()), alice_staked);
+        assert_eq!(emulator.contract.get_total_staked_balance(), total_staked);
+        emulator.locked_amount = UncToken::from_attounc(0);
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(total_staked.0));
+
+        // Can be repeated while paused.
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.unstake_all_from_validator();
+        assert_eq!(get_created_receipts().len(), 1);
+
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.restake_all();
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_single_stake_action(&receipts[0], UncToken::from_attounc(total_staked.0));
+        assert!(!emulator.contract.is_staking_paused());
+        emulator.simulate_stake_call();
+        assert_eq!(emulator.contract.get_account_staked_balance(&alice()), alice_staked);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_unstake_all_from_validator_not_owner() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.unstake_all_from_validator();
+    }
+
+    #[test]
+    #[should_panic(expected = "The staking is not paused")]
+    fn test_restake_all_not_paused() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.restake_all();
+    }
+
+    #[test]
+    fn test_sync_balance_surplus_as_reward() {
+        let mut emulator = staked_emulator();
+        let total_staked = emulator.contract.total_staked_balance;
+        let alice_staked = emulator.contract.get_account_staked_balance(&alice()).0;
+
+        // Tokens transferred to the pool account directly.
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(100)));
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.sync_balance(true, None);
+        assert_eq!(
+            emulator.contract.total_staked_balance,
+            total_staked.saturating_add(UncToken::from_attounc(ntoy(100)))
+        );
+        assert_eq!(
+            emulator.contract.last_total_balance,
+            emulator.amount.saturating_add(emulator.locked_amount)
+        );
+        // The delegators receive the reward without the 10% fee, which goes to the owner.
+        assert!(emulator.contract.get_account_staked_balance(&alice()).0 > alice_staked);
+        assert!(emulator.contract.get_account_staked_balance(&owner()).0 > 0);
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&owner()).0, 0);
+        emulator.simulate_stake_call();
+        emulator.contract.check_invariants();
+    }
+
+    #[test]
+    fn test_sync_balance_surplus_returned() {
+        let mut emulator = staked_emulator();
+        let total_staked = emulator.contract.total_staked_balance;
+        let alice_staked = emulator.contract.get_account_staked_balance(&alice()).0;
+
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(100)));
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.sync_balance(false, Some(charlie()));
+        assert_eq!(emulator.contract.total_staked_balance, total_staked);
+        assert_eq!(emulator.contract.get_account_staked_balance(&alice()).0, alice_staked);
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&owner()).0, 0);
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, charlie());
+        assert_eq!(
+            serde_json::to_string(&receipts[0].actions).unwrap(),
+            format!("[{{\"Transfer\":{{\"receipt_index\":0,\"deposit\":\"{}\"}}}}]", ntoy(100))
+        );
+        emulator.amount = emulator.amount.saturating_sub(UncToken::from_attounc(ntoy(100)));
+        assert_eq!(
+            emulator.contract.last_total_balance,
+            emulator.amount.saturating_add(emulator.locked_amount)
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.check_invariants();
+
+        // Nothing is left to be distributed as a reward in the next epoch.
+        emulator.skip_epochs(1);
+        emulator.locked_amount = total_staked;
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        assert_eq!(emulator.contract.total_staked_balance, total_staked);
+    }
+
+    #[test]
+    fn test_sync_balance_surplus_returned_in_next_epoch() {
+        let mut emulator = staked_emulator();
+        let total_staked = emulator.contract.total_staked_balance;
+
+        // Tokens transferred to the pool account directly, and the staking rewards of the next
+        // epoch credited to the locked balance before anyone pinged.
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(100)));
+        emulator.skip_epochs(1);
+        let reward = emulator.locked_amount.saturating_sub(total_staked);
+        assert!(!reward.is_zero());
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.sync_balance(false, Some(charlie()));
+
+        // Only the transferred tokens are returned, the reward is distributed to the delegators.
+        let receipts = get_created_receipts();
+        assert_eq!(receipts[0].receiver_id, charlie());
+        assert_eq!(
+            serde_json::to_string(&receipts[0].actions).unwrap(),
+            format!("[{{\"Transfer\":{{\"receipt_index\":0,\"deposit\":\"{}\"}}}}]", ntoy(100))
+        );
+        assert_eq!(emulator.contract.total_staked_balance, total_staked.saturating_add(reward));
+        assert_eq!(
+            emulator.contract.reward_history.last().unwrap().total_reward,
+            reward
+        );
+    }
+
+    #[test]
+    fn test_sync_balance_keeps_deferred_reward() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.update_min_reward_to_distribute(ntoy(10).into());
+        let total_staked = emulator.contract.total_staked_balance;
+
+        // The reward of 4 is below the minimum, so it's deferred.
+        emulator.epoch_height += 1;
+        emulator.locked_amount = emulator.locked_amount.saturating_add(UncToken::from_attounc(ntoy(4)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        assert_eq!(emulator.contract.total_staked_balance, total_staked);
+
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(100)));
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.sync_balance(false, Some(charlie()));
+        let receipts = get_created_receipts();
+        assert_eq!(
+            serde_json::to_string(&receipts[0].actions).unwrap(),
+            format!("[{{\"Transfer\":{{\"receipt_index\":0,\"deposit\":\"{}\"}}}}]", ntoy(100))
+        );
+        emulator.amount = emulator.amount.saturating_sub(UncToken::from_attounc(ntoy(100)));
+
+        // The deferred reward is still distributed once it reaches the minimum.
+        emulator.epoch_height += 1;
+        emulator.locked_amount = emulator.locked_amount.saturating_add(UncToken::from_attounc(ntoy(8)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        assert_eq!(
+            emulator.contract.total_staked_balance,
+            total_staked.saturating_add(UncToken::from_attounc(ntoy(12)))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The receiver of the surplus should be specified")]
+    fn test_sync_balance_without_receiver() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.sync_balance(false, None);
+    }
+
+    #[test]
+    fn test_is_staking() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(staking())
+            .account_balance(STAKE_SHARE_PRICE_GUARANTEE_FUND)
+            .epoch_height(5)
+            .build());
+        let mut contract = StakingContract::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+            None,
+            None,
+        );
+        // Nothing is staked yet.
+        assert!(!contract.get_is_staking());
+        assert_eq!(contract.get_last_epoch_height(), 5);
+
+        // The pool can't sell "stake" shares without a staked balance, so the first tokens are
+        // transferred to the pool account and staked as a reward.
+        let amount = UncToken::from_attounc(ntoy(1_000));
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(staking())
+            .predecessor_account_id(owner())
+            .account_balance(STAKE_SHARE_PRICE_GUARANTEE_FUND.saturating_add(amount))
+            .epoch_height(6)
+            .build());
+        contract.sync_balance(true, None);
+        assert!(contract.get_is_staking());
+        assert_eq!(contract.get_last_epoch_height(), 6);
+
+        contract.pause_staking();
+        assert!(!contract.get_is_staking());
+        contract.resume_staking();
+        assert!(contract.get_is_staking());
+    }
+
+    #[test]
+    fn test_add_reward() {
+        let mut emulator = staked_emulator();
+        let share_price = |contract: &StakingContract| {
+            internal::staked_amount_from_num_shares_rounded_down(
+                contract.total_staked_balance,
+                contract.total_stake_shares,
+                STAKE_SHARE_PRICE_UNIT,
+            )
+        };
+        let initial_share_price = share_price(&emulator.contract);
+        let alice_balance = emulator.contract.get_account_total_balance(&alice()).0;
+        let bob_balance = emulator.contract.get_account_total_balance(&bob()).0;
+        let total_staked = emulator.contract.total_staked_balance;
+
+        let bonus = ntoy(100);
+        emulator.update_context(charlie(), UncToken::from_attounc(bonus));
+        emulator.contract.add_reward();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(bonus));
+        assert_eq!(
+            emulator.contract.total_staked_balance,
+            total_staked.saturating_add(UncToken::from_attounc(bonus))
+        );
+        assert_eq!(
+            emulator.contract.last_total_balance,
+            emulator.amount.saturating_add(emulator.locked_amount)
+        );
+        assert!(share_price(&emulator.contract) > initial_share_price);
+        assert!(emulator.contract.get_account_total_balance(&alice()).0 > alice_balance);
+        assert!(emulator.contract.get_account_total_balance(&bob()).0 > bob_balance);
+        // The owner receives the 10% fee in "stake" shares.
+        assert!(emulator.contract.get_account_staked_balance(&owner()).0 > 0);
+        assert_eq!(emulator.contract.get_reward_history().last().unwrap().total_reward.as_attounc(), bonus);
+        emulator.simulate_stake_call();
+        emulator.contract.check_invariants();
+    }
+
+    #[test]
+    fn test_add_reward_after_ping_does_not_inflate_apy() {
+        let run = |bonus: u128| {
+            let mut emulator = staked_emulator();
+            emulator.skip_epochs(1);
+            emulator.update_context(bob(), UncToken::from_attounc(0));
+            emulator.contract.ping();
+            emulator.simulate_stake_call();
+            if bonus > 0 {
+                // A bonus reward in the same epoch as the `ping`.
+                emulator.update_context(charlie(), UncToken::from_attounc(bonus));
+                emulator.contract.add_reward();
+                emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(bonus));
+                emulator.simulate_stake_call();
+            }
+            emulator.skip_epochs(1);
+            emulator.update_context(bob(), UncToken::from_attounc(0));
+            emulator.contract.ping();
+            emulator.simulate_stake_call();
+            emulator.contract
+        };
+        let baseline = run(0);
+        let contract = run(ntoy(1_000));
+
+        let epochs: Vec<EpochHeight> =
+            contract.get_reward_history().iter().map(|snapshot| snapshot.epoch_height).collect();
+        assert_eq!(epochs, vec![1, 2]);
+        assert_eq!(
+            contract.get_reward_history()[0].total_reward,
+            baseline.get_reward_history()[0].total_reward.saturating_add(UncToken::from_attounc(ntoy(1_000)))
+        );
+        assert!(baseline.get_estimated_apy() > 0);
+        assert!(contract.get_estimated_apy() <= baseline.get_estimated_apy());
+    }
+
+    #[test]
+    fn test_staking_error_messages() {
+        assert_eq!(
+            StakingError::NotOwner.as_ref(),
+            "Can only be called by the owner"
+        );
+        assert_eq!(
+            StakingError::UnstakedBalanceNotAvailable.to_string(),
+            "The unstaked balance is not yet available due to unstaking delay"
+        );
+        assert_eq!(
+            StakingError::TotalBalanceDecreased.to_string(),
+            StakingError::TotalBalanceDecreased.as_ref()
+        );
+        assert_eq!(
+            StakingError::StakeAmountBelowMinimum.to_string(),
+            "The staking amount is less than the minimum stake amount"
+        );
+        assert_eq!(
+            StakingError::MaxTotalStakedBalanceExceeded.to_string(),
+            "The staking would exceed the maximum total staked balance"
+        );
+        assert_eq!(
+            StakingError::ZeroRewardAmount.as_ref(),
+            "Reward amount should be positive"
+        );
+        assert_eq!(
+            StakingError::NotCallback.to_string(),
+            "Can be called only as a callback"
+        );
+        assert_eq!(
+            StakingError::RewardFeeAboveOne.to_string(),
+            "The reward fee must be less or equal to 1"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Withdrawal amount should be positive")]
+    fn test_withdraw_zero() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.withdraw(0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough unstaked balance to withdraw")]
+    fn test_withdraw_more_than_unstaked() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.withdraw(ntoy(1_000).into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Staking amount should be positive")]
+    fn test_stake_zero() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.stake(0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough unstaked balance to stake")]
+    fn test_stake_more_than_unstaked() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.stake(ntoy(1_000).into());
+    }
+
+    /// Distributes a reward that makes the "stake" share price fractional, so the staked balances
+    /// are rounded.
+    fn rounding_emulator() -> Emulator {
+        let mut emulator = staked_emulator();
+        emulator.skip_epochs(1);
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        emulator
+    }
+
+    #[test]
+    fn test_unstake_full_rounded_down_staked_balance() {
+        let mut emulator = rounding_emulator();
+        let account = emulator.contract.internal_get_account(&alice());
+        let staked_amount = emulator
+            .contract
+            .staked_amount_from_num_shares_rounded_down(account.stake_shares);
+
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.unstake(staked_amount.as_attounc().into());
+        assert_eq!(emulator.contract.get_account_staked_balance(&alice()).0, 0);
+        assert_eq!(emulator.contract.internal_get_account(&alice()).stake_shares.as_attounc(), 0);
+        emulator.contract.check_invariants();
+    }
+
+    #[test]
+    fn test_unstake_within_rounding_tolerance() {
+        let mut emulator = rounding_emulator();
+        let staked_amount = emulator.contract.get_account_staked_balance(&alice()).0;
+
+        // Slightly above the staked balance, e.g. the rounded up value from a client.
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.unstake((staked_amount + 1).into());
+        assert_eq!(emulator.contract.get_account_staked_balance(&alice()).0, 0);
+
+        // Slightly below the staked balance.
+        let staked_amount = emulator.contract.get_account_staked_balance(&bob()).0;
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake((staked_amount - 1).into());
+        assert_eq!(emulator.contract.get_account_staked_balance(&bob()).0, 0);
+        emulator.contract.check_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "Unstaking amount should be positive")]
+    fn test_unstake_zero() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.unstake(0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough staked balance to unstake")]
+    fn test_unstake_more_than_staked() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.unstake(ntoy(1_000).into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Reward amount should be positive")]
+    fn test_add_reward_without_deposit() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(charlie(), UncToken::from_attounc(0));
+        emulator.contract.add_reward();
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_sync_balance_not_owner() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.sync_balance(true, None);
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        let mut emulator = staked_emulator();
+        emulator.contract.check_invariants();
+
+        // The reward is distributed and the owner receives the fee.
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(100)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake(ntoy(100).into());
+        emulator.simulate_stake_call();
+        emulator.contract.check_invariants();
+    }
+
+    fn total_unstaked_balance_of_accounts(contract: &StakingContract) -> u128 {
+        contract.accounts.values().map(|account| account.unstaked.as_attounc()).sum()
+    }
+
+    #[test]
+    fn test_total_unstaked_balance() {
+        let mut emulator = staked_emulator();
+        assert_eq!(
+            emulator.contract.get_total_unstaked_balance().0,
+            total_unstaked_balance_of_accounts(&emulator.contract)
+        );
+
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake(ntoy(100).into());
+        emulator.simulate_stake_call();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.stake(ntoy(50).into());
+        emulator.simulate_stake_call();
+        emulator.update_context(charlie(), UncToken::from_attounc(ntoy(30)));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(30)));
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.withdraw(ntoy(100).into());
+        emulator.amount = emulator.amount.saturating_sub(UncToken::from_attounc(ntoy(100)));
+
+        // The reward changes the staked balances, but not the unstaked ones.
+        emulator.skip_epochs(1);
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake_all();
+        emulator.simulate_stake_call();
+
+        let total_unstaked_balance = total_unstaked_balance_of_accounts(&emulator.contract);
+        assert_eq!(emulator.contract.get_total_unstaked_balance().0, total_unstaked_balance);
+        assert_eq!(
+            total_unstaked_balance,
+            emulator.contract.get_account_unstaked_balance(&alice()).0
+                + emulator.contract.get_account_unstaked_balance(&bob()).0
+                + ntoy(30)
+        );
+        emulator.contract.check_invariants();
+    }
+
+    #[test]
+    fn test_min_reward_to_distribute() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.update_min_reward_to_distribute(ntoy(10).into());
+        assert_eq!(emulator.contract.get_min_reward_to_distribute().0, ntoy(10));
+
+        let total_staked_balance = emulator.contract.total_staked_balance;
+        let last_total_balance = emulator.contract.last_total_balance;
+        // Each epoch brings a reward of 4, which is below the minimum until the third epoch.
+        for _ in 0..2 {
+            emulator.epoch_height += 1;
+            emulator.locked_amount = emulator.locked_amount.saturating_add(UncToken::from_attounc(ntoy(4)));
+            emulator.update_context(bob(), UncToken::from_attounc(0));
+            assert!(emulator.contract.internal_ping());
+            assert_eq!(emulator.contract.total_staked_balance, total_staked_balance);
+            assert_eq!(emulator.contract.last_total_balance, last_total_balance);
+            emulator.contract.check_invariants();
+        }
+        assert_eq!(emulator.contract.get_total_staked_balance().0, total_staked_balance.as_attounc());
+
+        emulator.epoch_height += 1;
+        emulator.locked_amount = emulator.locked_amount.saturating_add(UncToken::from_attounc(ntoy(4)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert!(emulator.contract.internal_ping());
+        // The accumulated reward is distributed in full.
+        assert_eq!(
+            emulator.contract.total_staked_balance,
+            total_staked_balance.saturating_add(UncToken::from_attounc(ntoy(12)))
+        );
+        assert_eq!(
+            emulator.contract.last_total_balance,
+            last_total_balance.saturating_add(UncToken::from_attounc(ntoy(12)))
+        );
+        assert_eq!(emulator.contract.reward_history.last().unwrap().total_reward.as_attounc(), ntoy(12));
+        emulator.contract.check_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_update_min_reward_to_distribute_by_non_owner() {
+        let mut emulator = staked_emulator();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.update_min_reward_to_distribute(ntoy(10).into());
+    }
+
+    #[test]
+    #[should_panic(expected = "exceed the last total balance")]
+    fn test_check_invariants_with_corrupted_balance() {
+        let mut emulator = staked_emulator();
+        let mut account = emulator.contract.internal_get_account(&bob());
+        // The guarantee fund kept on init is not accounted to anyone, so a tiny drift would be
+        // covered by it.
+        account.unstaked = account.unstaked.saturating_add(UncToken::from_attounc(ntoy(1)));
+        emulator.contract.internal_save_account(&bob(), &account);
+        emulator.contract.check_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "exceed the total number of stake shares")]
+    fn test_check_invariants_with_corrupted_shares() {
+        let mut emulator = staked_emulator();
+        let mut account = emulator.contract.internal_get_account(&bob());
+        account.stake_shares =
+            account.stake_shares.saturating_add(emulator.contract.total_stake_shares);
+        emulator.contract.internal_save_account(&bob(), &account);
+        emulator.contract.check_invariants();
+    }
+
+    #[test]
+    fn test_deposit_withdraw() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert_eq!(
+            emulator.contract.get_account_unstaked_balance(&bob()).0,
+            deposit_amount
+        );
+        emulator.contract.withdraw(deposit_amount.into());
+        assert_eq!(
+            emulator.contract.get_account_unstaked_balance(&bob()).0,
+            0u128
+        );
+    }
+
+    #[test]
+    fn test_estimate_shares_bootstrap() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.total_staked_balance = UncToken::from_attounc(0);
+        emulator.contract.total_stake_shares = UncToken::from_attounc(0);
+        assert_eq!(emulator.contract.estimate_shares_for_amount(ntoy(100).into()).0, ntoy(100));
+        assert_eq!(emulator.contract.estimate_amount_for_shares(ntoy(100).into()).0, ntoy(100));
+    }
+
+    #[test]
+    fn test_minimum_stakeable_amount() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        // The share price is 1 at the initialization.
+        assert_eq!(emulator.contract.minimum_stakeable_amount().0, 1);
+
+        for (total_staked_balance, total_stake_shares) in [
+            (3, 2),
+            (ntoy(1_500_000) + 7, ntoy(1_000_000)),
+            (ntoy(1_000_000), 3),
+            (ntoy(1_000_000), ntoy(1_000_000) - 1),
+        ] {
+            emulator.contract.total_staked_balance = UncToken::from_attounc(total_staked_balance);
+            emulator.contract.total_stake_shares = UncToken::from_attounc(total_stake_shares);
+            let minimum_amount = emulator.contract.minimum_stakeable_amount().0;
+            assert_eq!(
+                emulator.contract.estimate_shares_for_amount((minimum_amount - 1).into()).0,
+                0
+            );
+            assert_eq!(emulator.contract.estimate_shares_for_amount(minimum_amount.into()).0, 1);
+            assert!(
+                emulator.contract.estimate_shares_for_amount((minimum_amount * 2).into()).0 >= 2
+            );
+        }
+    }
+
+    #[test]
+    fn test_stake_minimum_stakeable_amount() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(ntoy(1_000_000)));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(1_000_000)));
+        emulator.simulate_stake_call();
+
+        // The reward raises the share price above 1.
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(1_000)));
+        emulator.update_context(alice(), UncToken::from_attounc(ntoy(1)));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(ntoy(1)));
+        let minimum_amount = emulator.contract.minimum_stakeable_amount().0;
+        assert_eq!(minimum_amount, 2);
+
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            emulator.contract.stake((minimum_amount - 1).into());
+        }));
+        assert!(result.is_err());
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.stake(minimum_amount.into());
+        assert_eq!(emulator.contract.internal_get_account(&alice()).stake_shares.as_attounc(), 1);
+    }
+
+    #[test]
+    fn test_estimate_shares_after_reward() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            RewardFeeFraction {
+                numerator: 10,
+                denominator: 100,
+            },
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+        // The share price is 1 before any rewards.
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert_eq!(emulator.contract.estimate_shares_for_amount(ntoy(1_000).into()).0, ntoy(1_000));
+
+        // The reward is received, but not distributed by `ping` yet.
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(100_000)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        let total_staked_balance = emulator.contract.total_staked_balance;
+        let estimated_shares = emulator.contract.estimate_shares_for_amount(ntoy(1_000).into()).0;
+        assert!(estimated_shares < ntoy(1_000));
+        assert_eq!(emulator.contract.total_staked_balance, total_staked_balance);
+        let estimated_amount = emulator.contract.estimate_amount_for_shares(estimated_shares.into()).0;
+        assert!(estimated_amount <= ntoy(1_000));
+        assert_eq_in_unc!(estimated_amount, ntoy(1_000));
+
+        // Staking after the reward is distributed buys exactly the estimated number of shares.
+        emulator.update_context(alice(), UncToken::from_attounc(ntoy(1_000)));
+        emulator.contract.deposit_and_stake();
+        assert_eq!(
+            emulator.contract.internal_get_account(&alice()).stake_shares.as_attounc(),
+            estimated_shares
+        );
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+        emulator.update_context(alice(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.update_context(staking(), UncToken::from_attounc(0));
+
+        let total_staked_balance = emulator.contract.total_staked_balance;
+        let total_stake_shares = emulator.contract.total_stake_shares;
+        let last_total_balance = emulator.contract.last_total_balance;
+        let bob_account = emulator.contract.internal_get_account(&bob());
+
+        // Writing the state in the old layout.
+        let contract = emulator.contract;
+        env::state_write(&migrate::OldStakingContract {
+            owner_id: contract.owner_id,
+            stake_public_key: contract.stake_public_key,
+            last_epoch_height: contract.last_epoch_height,
+            last_total_balance: contract.last_total_balance,
+            total_stake_shares: contract.total_stake_shares,
+            total_staked_balance: contract.total_staked_balance,
+            reward_fee_fraction: contract.reward_fee_fraction,
+            accounts: contract.accounts,
+            paused: contract.paused,
+        });
+
+        let migrated = StakingContract::migrate();
+        assert_eq!(migrated.owner_id, owner());
+        assert_eq!(migrated.total_staked_balance, total_staked_balance);
+        assert_eq!(migrated.total_stake_shares, total_stake_shares);
+        assert_eq!(migrated.last_total_balance, last_total_balance);
+        assert_eq!(migrated.get_number_of_accounts(), 2);
+        assert_eq!(migrated.internal_get_account(&bob()), bob_account);
+        assert_eq!(migrated.get_account_staked_balance(&bob()).0, deposit_amount);
+        assert_eq!(migrated.get_account_unstaked_balance(&alice()).0, deposit_amount);
+        assert_eq!(migrated.get_num_epochs_to_unlock(), DEFAULT_NUM_EPOCHS_TO_UNLOCK);
+        assert!(migrated.get_reward_history().is_empty());
+        assert_eq!(migrated.get_epochs_per_year(), DEFAULT_EPOCHS_PER_YEAR);
+        assert_eq!(migrated.get_total_unstaked_balance().0, deposit_amount);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can be called only by the contract itself")]
+    fn test_migrate_not_self() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        StakingContract::migrate();
+    }
+
+    fn restricted_emulator() -> Emulator {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        assert!(emulator.contract.add_delegator(alice()));
+        assert!(!emulator.contract.add_delegator(alice()));
+        assert!(emulator.contract.add_delegator(bob()));
+        emulator.contract.set_restricted_mode(true);
+        assert!(emulator.contract.is_restricted_mode());
+        assert_eq!(emulator.contract.get_delegators(0, 10), vec![alice(), bob()]);
+        assert_eq!(emulator.contract.get_delegators(1, 10), vec![bob()]);
+        emulator
+    }
+
+    #[test]
+    fn test_restricted_mode_allowed_deposit() {
+        let mut emulator = restricted_emulator();
+        let deposit_amount = ntoy(1_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+        assert_eq!(emulator.contract.get_account_staked_balance(&bob()).0, deposit_amount);
+
+        // Removing the delegator doesn't prevent unstaking and withdrawing.
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        assert!(emulator.contract.remove_delegator(bob()));
+        assert!(!emulator.contract.remove_delegator(bob()));
+        assert_eq!(emulator.contract.get_delegators(0, 10), vec![alice()]);
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake_all();
+        emulator.simulate_stake_call();
+        emulator.skip_epochs(4);
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.withdraw_all();
+        assert_eq!(emulator.contract.get_account_total_balance(&bob()).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The account is not allowed to deposit in the restricted mode")]
+    fn test_restricted_mode_disallowed_deposit() {
+        let mut emulator = restricted_emulator();
+        emulator.update_context(charlie(), UncToken::from_attounc(ntoy(1_000)));
+        emulator.contract.deposit();
+    }
+
+    #[test]
+    fn test_unrestricted_mode_deposit() {
+        let mut emulator = restricted_emulator();
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.set_restricted_mode(false);
+
+        let deposit_amount = ntoy(1_000);
+        emulator.update_context(charlie(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&charlie()).0, deposit_amount);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_add_delegator_not_owner() {
+        let mut emulator = restricted_emulator();
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.add_delegator(charlie());
+    }
+
+    #[test]
+    fn test_deposit_for() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000);
+        emulator.update_context(alice(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_for(bob());
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        assert_eq!(
+            get_logs(),
+            vec![format!(
+                "@alice deposited {} for @bob. New unstaked balance is {}",
+                UncToken::from_attounc(deposit_amount),
+                UncToken::from_attounc(deposit_amount)
+            )]
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert_eq!(
+            emulator.contract.get_account_unstaked_balance(&bob()).0,
+            deposit_amount
+        );
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&alice()).0, 0);
+        assert_eq!(emulator.contract.get_number_of_accounts(), 1);
+
+        // Bob can withdraw the deposit.
+        emulator.contract.withdraw(deposit_amount.into());
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, 0);
+    }
+
+    #[test]
+    fn test_account_total_balance() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        // Bob has both staked and unstaked balances.
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.stake(ntoy(400_000).into());
+        emulator.simulate_stake_call();
+        // Alice only has the unstaked balance.
+        emulator.update_context(alice(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert_eq!(emulator.contract.get_account_total_balance(&bob()).0, deposit_amount);
+        assert_eq!(emulator.contract.get_account_total_balance(&alice()).0, deposit_amount);
+
+        // The rewards are reflected in the total balance of the staked account only.
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(10)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        let bob_total_balance = emulator.contract.get_account_total_balance(&bob()).0;
+        assert!(bob_total_balance > deposit_amount);
+        assert_eq!(
+            bob_total_balance,
+            emulator.contract.get_account_staked_balance(&bob()).0
+                + emulator.contract.get_account_unstaked_balance(&bob()).0
+        );
+        assert_eq_in_unc!(bob_total_balance, deposit_amount + ntoy(10));
+        assert_eq!(emulator.contract.get_account_total_balance(&alice()).0, deposit_amount);
+    }
+
+    #[test]
+    fn test_stake_with_fee_recipient() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            RewardFeeFraction {
+                numerator: 10,
+                denominator: 100,
+            },
+        );
+        let treasury: AccountId = "treasury".parse().unwrap();
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        assert_eq!(emulator.contract.get_fee_recipient(), None);
+        emulator.contract.set_fee_recipient(Some(treasury.clone()));
+        assert_eq!(emulator.contract.get_fee_recipient(), Some(treasury.clone()));
+
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(10);
+        // Overriding rewards (+ 100K reward)
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(100_000)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+
+        // The treasury got 10% of the rewards, while the owner got nothing.
+        assert_eq_in_unc!(
+            emulator.contract.get_account_staked_balance(&treasury).0,
+            ntoy(10_000)
+        );
+        assert_eq!(emulator.contract.get_account_staked_balance(&owner()).0, 0);
+    }
+
+    #[test]
+    fn test_transfer_ownership() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        assert_eq!(emulator.contract.get_proposed_owner(), None);
+        emulator.contract.propose_owner(alice());
+        emulator.contract.propose_owner(bob());
+        assert_eq!(emulator.contract.get_proposed_owner(), Some(bob()));
+        // The owner doesn't change until the proposal is accepted.
+        assert_eq!(emulator.contract.get_owner_id(), owner());
+
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.accept_owner();
+        assert_eq!(emulator.contract.get_owner_id(), bob());
+        assert_eq!(emulator.contract.get_proposed_owner(), None);
+        emulator.contract.update_min_stake_amount(ntoy(1).into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the proposed owner")]
+    fn test_accept_owner_not_proposed() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.propose_owner(bob());
+
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.accept_owner();
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_propose_owner_not_owner() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.propose_owner(alice());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_set_fee_recipient_not_owner() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.set_fee_recipient(Some(bob()));
+    }
+
+    #[test]
+    fn test_first_stake_into_empty_pool() {
+        // The whole balance of the pool is the guarantee fund, so there are no shares yet.
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(staking())
+            .account_balance(STAKE_SHARE_PRICE_GUARANTEE_FUND)
+            .build());
+        let mut contract = StakingContract::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+            None,
+            None,
+        );
+        assert_eq!(contract.total_staked_balance.as_attounc(), 0);
+        assert_eq!(contract.total_stake_shares.as_attounc(), 0);
+
+        let deposit_amount = ntoy(1_000);
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(staking())
+            .predecessor_account_id(alice())
+            .attached_deposit(UncToken::from_attounc(deposit_amount))
+            .account_balance(
+                STAKE_SHARE_PRICE_GUARANTEE_FUND.saturating_add(UncToken::from_attounc(deposit_amount))
+            )
+            .build());
+        contract.deposit_and_stake();
+        assert_eq!(contract.internal_get_account(&alice()).stake_shares.as_attounc(), deposit_amount);
+        assert_eq!(contract.get_account_staked_balance(&alice()).0, deposit_amount);
+        assert_eq!(contract.get_account_unstaked_balance(&alice()).0, 0);
+        assert_eq!(contract.total_staked_balance.as_attounc(), deposit_amount);
+        assert_eq!(contract.total_stake_shares.as_attounc(), deposit_amount);
+
+        // The next stake uses the share price set by the first one.
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(staking())
+            .predecessor_account_id(bob())
+            .attached_deposit(UncToken::from_attounc(deposit_amount))
+            .account_balance(
+                STAKE_SHARE_PRICE_GUARANTEE_FUND
+                    .saturating_add(UncToken::from_attounc(2 * deposit_amount))
+            )
+            .build());
+        contract.deposit_and_stake();
+        assert_eq!(contract.internal_get_account(&bob()).stake_shares.as_attounc(), deposit_amount);
+        assert_eq!(contract.total_staked_balance.as_attounc(), 2 * deposit_amount);
+    }
+
+    #[test]
+    fn test_stake_with_fee_split() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            RewardFeeFraction {
+                numerator: 10,
+                denominator: 100,
+            },
+        );
+        let operator1: AccountId = "operator1".parse().unwrap();
+        let operator2: AccountId = "operator2".parse().unwrap();
+        let fee_split = vec![
+            (operator1.clone(), RewardFeeFraction { numerator: 1, denominator: 2 }),
+            (operator2.clone(), RewardFeeFraction { numerator: 50, denominator: 100 }),
+        ];
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        assert_eq!(emulator.contract.get_fee_split(), vec![]);
+        emulator.contract.set_fee_split(fee_split.clone());
+        assert_eq!(emulator.contract.get_fee_split(), fee_split);
+
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(10);
+        // Overriding rewards (+ 100K reward)
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(100_000)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+
+        // Each operator got half of the 10% fee, while the owner got nothing.
+        let shares1 = emulator.contract.internal_get_account(&operator1).stake_shares;
+        let shares2 = emulator.contract.internal_get_account(&operator2).stake_shares;
+        assert!(shares1.as_attounc().abs_diff(shares2.as_attounc()) <= 1);
+        assert_eq_in_unc!(emulator.contract.get_account_staked_balance(&operator1).0, ntoy(5_000));
+        assert_eq_in_unc!(emulator.contract.get_account_staked_balance(&operator2).0, ntoy(5_000));
+        assert_eq!(emulator.contract.get_account_staked_balance(&owner()).0, 0);
+        emulator.contract.check_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "The fee split fractions must sum to 1")]
+    fn test_set_fee_split_not_summing_to_one() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.set_fee_split(vec![
+            (alice(), RewardFeeFraction { numerator: 1, denominator: 2 }),
+            (bob(), RewardFeeFraction { numerator: 1, denominator: 3 }),
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_set_fee_split_not_owner() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.set_fee_split(vec![(bob(), RewardFeeFraction { numerator: 1, denominator: 1 })]);
+    }
+
+    #[test]
+    fn test_gift_shares() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(owner(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+
+        let total_staked_balance = emulator.contract.total_staked_balance;
+        let total_stake_shares = emulator.contract.total_stake_shares;
+        let owner_shares = emulator.contract.internal_get_account(&owner()).stake_shares;
+        let gift = owner_shares.as_attounc() / 4;
+
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.gift_shares(bob(), gift.into());
+        assert_eq!(
+            emulator.contract.internal_get_account(&owner()).stake_shares.as_attounc(),
+            owner_shares.as_attounc() - gift
+        );
+        assert_eq!(emulator.contract.internal_get_account(&bob()).stake_shares.as_attounc(), gift);
+        assert_eq_in_unc!(emulator.contract.get_account_staked_balance(&bob()).0, deposit_amount / 4);
+        assert_eq_in_unc!(
+            emulator.contract.get_account_staked_balance(&owner()).0,
+            deposit_amount * 3 / 4
+        );
+        // The totals are conserved.
+        assert_eq!(emulator.contract.total_staked_balance, total_staked_balance);
+        assert_eq!(emulator.contract.total_stake_shares, total_stake_shares);
+        let event = format!(
+            "EVENT_JSON:{{\"standard\":\"staking_pool\",\"version\":\"1.0.0\",\"event\":\"shares_gifted\",\"data\":{{\"owner_id\":\"{}\",\"account_id\":\"{}\",\"shares\":\"{}\"}}}}",
+            owner(),
+            bob(),
+            gift
+        );
+        assert!(get_logs().contains(&event));
+        emulator.contract.check_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "The owner doesn't have enough shares to gift")]
+    fn test_gift_shares_more_than_owned() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(alice(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+
+        // The shares of the other delegators can't be gifted.
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.gift_shares(bob(), ntoy(1).into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_gift_shares_not_owner() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.gift_shares(bob(), ntoy(1).into());
+    }
+
+    #[test]
+    fn test_stake_with_fee() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            RewardFeeFraction {
+                numerator: 10,
+                denominator: 100,
+            },
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.stake(deposit_amount.into());
+        emulator.simulate_stake_call();
+        assert_eq!(
+            emulator.contract.get_account_staked_balance(&bob()).0,
+            deposit_amount
+        );
+
+        let locked_amount = emulator.locked_amount;
+        let n_locked_amount = yton(locked_amount.as_attounc());
+        emulator.skip_epochs(10);
+        // Overriding rewards (+ 100K reward)
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(100_000)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        let expected_amount = deposit_amount
+            + ntoy((yton(deposit_amount) * 90_000 + n_locked_amount / 2) / n_locked_amount);
+        assert_eq_in_unc!(
+            emulator.contract.get_account_staked_balance(&bob()).0,
+            expected_amount
+        );
+        // Owner got 10% of the rewards
+        assert_eq_in_unc!(
+            emulator.contract.get_account_staked_balance(&owner()).0,
+            ntoy(10_000)
+        );
+
+        let locked_amount = emulator.locked_amount;
+        let n_locked_amount = yton(locked_amount.as_attounc());
+        emulator.skip_epochs(10);
+        // Overriding rewards (another 100K reward)
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(100_000)));
+
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        // previous balance plus (1_090_000 / 1_100_030)% of the 90_000 reward (rounding to uncest).
+        assert_eq_in_unc!(
+            emulator.contract.get_account_staked_balance(&bob()).0,
+            expected_amount
+                + ntoy((yton(expected_amount) * 90_000 + n_locked_amount / 2) / n_locked_amount)
+        );
+        // owner earns 10% with the fee and also small percentage from restaking.
+        assert_eq_in_unc!(
+            emulator.contract.get_account_staked_balance(&owner()).0,
+            ntoy(10_000)
+                + ntoy(10_000)
+                + ntoy((10_000u128 * 90_000 + n_locked_amount / 2) / n_locked_amount)
+        );
+
+        assert_eq!(emulator.contract.get_number_of_accounts(), 2);
+    }
+
+    #[test]
+    fn test_rewards_with_zero_fee() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.stake(deposit_amount.into());
+        emulator.simulate_stake_call();
+        let owner_stake_shares = emulator.contract.internal_get_account(&owner()).stake_shares;
+
+        for _ in 0..5 {
+            let bob_balance = emulator.contract.get_account_total_balance(&bob()).0;
+            let total_staked_balance = emulator.contract.total_staked_balance;
+            let total_stake_shares = emulator.contract.total_stake_shares;
+            let locked_amount = emulator.locked_amount;
+            emulator.skip_epochs(10);
+            // Overriding rewards (+ 100K reward)
+            let reward = ntoy(100_000);
+            emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(reward));
+            emulator.update_context(bob(), UncToken::from_attounc(0));
+            emulator.contract.ping();
+
+            // The whole reward is distributed without minting any shares.
+            assert_eq!(
+                emulator.contract.total_staked_balance,
+                total_staked_balance.saturating_add(UncToken::from_attounc(reward))
+            );
+            assert_eq!(emulator.contract.total_stake_shares, total_stake_shares);
+            assert_eq!(
+                emulator.contract.internal_get_account(&owner()).stake_shares,
+                owner_stake_shares
+            );
+            // Delegators split the full reward proportionally to their stake.
+            let n_total_staked_balance = yton(total_staked_balance.as_attounc());
+            assert_eq_in_unc!(
+                emulator.contract.get_account_total_balance(&bob()).0,
+                bob_balance
+                    + ntoy((yton(bob_balance) * 100_000 + n_total_staked_balance / 2) / n_total_staked_balance)
+            );
+        }
+        assert_eq!(emulator.contract.get_account_staked_balance(&owner()).0, 0);
+    }
+
+    fn slashed_emulator() -> Emulator {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        for account_id in [bob(), alice()] {
+            emulator.update_context(account_id, UncToken::from_attounc(deposit_amount));
+            emulator.contract.deposit_and_stake();
+            emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+            emulator.simulate_stake_call();
+        }
+
+        // The validator loses 10% of the stake.
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount.saturating_sub(locked_amount.saturating_div(10));
+        emulator
+    }
+
+    #[test]
+    #[should_panic(expected = "The new total balance should not be less than the old total balance")]
+    fn test_ping_after_slashing() {
+        let mut emulator = slashed_emulator();
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+    }
+
+    #[test]
+    fn test_handle_slashing() {
+        let mut emulator = slashed_emulator();
+        let staked_balance = emulator.contract.get_account_staked_balance(&bob()).0;
+        let total_stake_shares = emulator.contract.total_stake_shares;
+        let slashed_amount = emulator.contract.total_staked_balance.saturating_sub(emulator.locked_amount);
+
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.handle_slashing();
+        assert_eq!(emulator.contract.total_staked_balance, emulator.locked_amount);
+        assert_eq!(emulator.contract.total_stake_shares, total_stake_shares);
+        let event = format!(
+            "EVENT_JSON:{{\"standard\":\"staking_pool\",\"version\":\"1.0.0\",\"event\":\"slashed\",\"data\":{{\"epoch_height\":1,\"slashed_amount\":\"{}\",\"total_staked_balance\":\"{}\"}}}}",
+            slashed_amount.as_attounc(),
+            emulator.locked_amount.as_attounc()
+        );
+        assert!(get_logs().contains(&event));
+        // The share price decreased by 10%, so every delegator lost 10% of the stake.
+        assert_eq_in_unc!(
+            emulator.contract.get_account_staked_balance(&bob()).0,
+            staked_balance - staked_balance / 10
+        );
+        assert_eq!(
+            emulator.contract.get_account_staked_balance(&bob()).0,
+            emulator.contract.get_account_staked_balance(&alice()).0
+        );
+
+        // The share price has dropped on purpose, so the emulator's guarantee starts over.
+        emulator.last_total_staked_balance = emulator.contract.total_staked_balance;
+        emulator.last_total_stake_shares = emulator.contract.total_stake_shares;
+        // The pool keeps working with the new balance.
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(10)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.ping();
+        assert_eq!(
+            emulator.contract.total_staked_balance,
+            locked_amount.saturating_add(UncToken::from_attounc(ntoy(10)))
+        );
+    }
+
+    #[test]
+    fn test_preview_slashing_impact() {
+        let mut emulator = slashed_emulator();
+        let total_staked_balance = emulator.contract.total_staked_balance;
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        let preview = emulator.contract.preview_slashing_impact();
+        assert_eq!(
+            preview.slashed_amount.0,
+            total_staked_balance.saturating_sub(emulator.locked_amount).as_attounc()
+        );
+        assert_eq!(preview.total_staked_balance.0, emulator.locked_amount.as_attounc());
+        assert_eq!(preview.loss_basis_points, 1_000);
+        // The share price was 1 before the slashing.
+        assert_eq!(preview.stake_share_price.0, ntoy(1) * 9 / 10);
+        // The preview doesn't change the state.
+        assert_eq!(emulator.contract.total_staked_balance, total_staked_balance);
+
+        // The preview matches the recovery.
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.handle_slashing();
+        assert_eq!(
+            emulator.contract.total_staked_balance.as_attounc(),
+            preview.total_staked_balance.0
+        );
+        assert_eq!(
+            emulator
+                .contract
+                .staked_amount_from_num_shares_rounded_down(STAKE_SHARE_PRICE_UNIT)
+                .as_attounc(),
+            preview.stake_share_price.0
+        );
+        assert_eq!(emulator.contract.preview_slashing_impact().slashed_amount.0, 0);
+    }
+
+    #[test]
+    fn test_preview_slashing_impact_without_shares() {
+        // The whole balance of the pool is the guarantee fund, so there are no shares yet.
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(staking())
+            .account_balance(STAKE_SHARE_PRICE_GUARANTEE_FUND)
+            .build());
+        let contract = StakingContract::new(
+            owner(),
+            "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+            None,
+            None,
+        );
+        assert_eq!(contract.total_stake_shares.as_attounc(), 0);
+
+        let preview = contract.preview_slashing_impact();
+        assert_eq!(preview.slashed_amount.0, 0);
+        assert_eq!(preview.total_staked_balance.0, 0);
+        assert_eq!(preview.stake_share_price.0, STAKE_SHARE_PRICE_UNIT.as_attounc());
+        assert_eq!(preview.loss_basis_points, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The total balance is not less than the last total balance, there is no slashing")]
+    fn test_handle_slashing_without_slashing() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.handle_slashing();
+    }
+
+    #[test]
+    fn test_ping_twice_in_same_epoch() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            RewardFeeFraction {
+                numerator: 10,
+                denominator: 100,
+            },
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+
+        let locked_amount = emulator.locked_amount;
+        emulator.skip_epochs(1);
+        emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(10)));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert!(emulator.contract.internal_ping());
+
+        // The stake action changes the locked balance within the same epoch.
+        emulator.contract.unstake(ntoy(1_000).into());
+        emulator.simulate_stake_call();
+        let total_staked_balance = emulator.contract.total_staked_balance;
+        let total_stake_shares = emulator.contract.total_stake_shares;
+        let last_total_balance = emulator.contract.last_total_balance;
+        let owner_stake_shares = emulator.contract.internal_get_account(&owner()).stake_shares;
+
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert!(!emulator.contract.internal_ping());
+        assert_eq!(emulator.contract.total_staked_balance, total_staked_balance);
+        assert_eq!(emulator.contract.total_stake_shares, total_stake_shares);
+        assert_eq!(emulator.contract.last_total_balance, last_total_balance);
+        assert_eq!(
+            emulator.contract.internal_get_account(&owner()).stake_shares,
+            owner_stake_shares
+        );
+        assert_eq!(emulator.contract.get_reward_history().len(), 1);
+    }
+
+    #[test]
+    fn test_reward_history() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+        assert!(emulator.contract.get_reward_history().is_empty());
+
+        let num_rewards = REWARD_HISTORY_LENGTH as u128 + 5;
+        for i in 1..=num_rewards {
+            let locked_amount = emulator.locked_amount;
+            emulator.skip_epochs(1);
+            emulator.locked_amount = locked_amount.saturating_add(UncToken::from_attounc(ntoy(i)));
+            emulator.update_context(bob(), UncToken::from_attounc(0));
+            emulator.contract.ping();
+        }
+
+        let reward_history = emulator.contract.get_reward_history();
+        assert_eq!(reward_history.len(), REWARD_HISTORY_LENGTH);
+        // Only the most recent rewards are kept, oldest first.
+        for (snapshot, i) in reward_history.iter().zip(6..=num_rewards) {
+            assert_eq!(snapshot.epoch_height, i as EpochHeight);
+            assert_eq!(snapshot.total_reward.as_attounc(), ntoy(i));
+        }
+        assert_eq!(
+            reward_history.last().unwrap().total_staked_balance,
+            emulator.contract.total_staked_balance
+        );
+    }
+
+    #[test]
+    fn test_estimated_apy() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            RewardFeeFraction { numerator: 10, denominator: 100 },
+        );
+        // Cold start: no rate can be measured from less than two snapshots.
+        assert_eq!(emulator.contract.get_estimated_apy(), 0);
+        let snapshot = |epoch_height, total_reward, total_staked_balance| RewardSnapshot {
+            epoch_height,
+            total_reward: UncToken::from_attounc(total_reward),
+            total_staked_balance: UncToken::from_attounc(total_staked_balance),
+        };
+        emulator.contract.reward_history = vec![snapshot(10, 50, 100_000_000)];
+        assert_eq!(emulator.contract.get_estimated_apy(), 0);
+
+        // 0.02% over 2 epochs and 0.01% over 1 epoch, i.e. 0.01% per epoch on average.
+        emulator.contract.reward_history = vec![
+            snapshot(10, 50, 100_000_000),
+            snapshot(12, 20_000, 100_020_000),
+            snapshot(13, 10_002, 100_030_002),
+        ];
+        // 0.01% * 730 epochs = 7.3% = 730 basis points, minus the 10% fee.
+        assert_eq!(emulator.contract.get_estimated_apy(), 657);
+
+        emulator.contract.reward_fee_fraction = zero_fee();
+        assert_eq!(emulator.contract.get_estimated_apy(), 730);
+    }
+
+    #[test]
+    fn test_configured_epochs_per_year() {
+        let emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        assert_eq!(emulator.contract.get_epochs_per_year(), DEFAULT_EPOCHS_PER_YEAR);
+
+        // A testnet with 6 hour epochs.
+        let mut emulator = Emulator::new_with_init_args(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+            None,
+            Some(1460),
+        );
+        assert_eq!(emulator.contract.get_epochs_per_year(), 1460);
+        emulator.contract.reward_history = vec![
+            RewardSnapshot {
+                epoch_height: 10,
+                total_reward: UncToken::from_attounc(50),
+                total_staked_balance: UncToken::from_attounc(100_000_000),
+            },
+            RewardSnapshot {
+                epoch_height: 11,
+                total_reward: UncToken::from_attounc(10_000),
+                total_staked_balance: UncToken::from_attounc(100_010_000),
+            },
+        ];
+        // 0.01% per epoch * 1460 epochs = 14.6%.
+        assert_eq!(emulator.contract.get_estimated_apy(), 1460);
+    }
+
+    #[test]
+    fn test_stake_unstake() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.stake(deposit_amount.into());
+        emulator.simulate_stake_call();
+        assert_eq!(
+            emulator.contract.get_account_staked_balance(&bob()).0,
+            deposit_amount
+        );
+        let locked_amount = emulator.locked_amount;
         // 10 epochs later, unstake half of the money.
         emulator.skip_epochs(10);
         // Overriding rewards
@@ -736,11 +3902,10 @@ mod tests {
         );
         emulator.contract.unstake((deposit_amount / 2).into());
         emulator.simulate_stake_call();
-        //FIXME: The contract should restake the amount, but it doesn't.
-        // assert_eq_in_unc!(
-        //     emulator.contract.get_account_staked_balance(&bob()).0,
-        //     deposit_amount / 2 + ntoy(10)
-        // );
+        assert_eq_in_unc!(
+            emulator.contract.get_account_staked_balance(&bob()).0,
+            deposit_amount / 2 + ntoy(10)
+        );
         assert_eq_in_unc!(
             emulator.contract.get_account_unstaked_balance(&bob()).0,
             deposit_amount / 2
@@ -748,7 +3913,7 @@ mod tests {
         let acc = emulator.contract.get_account(&bob());
         assert_eq!(acc.account_id, bob());
         assert_eq_in_unc!(acc.unstaked_balance.0, deposit_amount / 2);
-        //assert_eq_in_unc!(acc.staked_balance.0, deposit_amount / 2 + ntoy(10));
+        assert_eq_in_unc!(acc.staked_balance.0, deposit_amount / 2 + ntoy(10));
         assert!(!acc.can_withdraw);
 
         assert!(!emulator
@@ -761,6 +3926,172 @@ mod tests {
             .is_account_unstaked_balance_available(&bob()),);
     }
 
+    #[test]
+    fn test_unstake_all_removes_stake_shares() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+        let total_stake_shares = emulator.contract.total_stake_shares;
+
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake_all();
+        emulator.simulate_stake_call();
+        // The unstaked shares are taken from the account, so nothing is left staked.
+        assert_eq!(emulator.contract.get_account_staked_balance(&bob()).0, 0);
+        assert_eq!(emulator.contract.get_account_unstaked_balance(&bob()).0, deposit_amount);
+        assert_eq!(
+            emulator.contract.total_stake_shares,
+            total_stake_shares.saturating_sub(UncToken::from_attounc(deposit_amount))
+        );
+    }
+
+    #[test]
+    fn test_unstaked_available_epoch() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+        assert_eq!(emulator.contract.get_account_epochs_until_withdraw(&bob()), 0);
+
+        emulator.skip_epochs(3);
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake((deposit_amount / 2).into());
+        emulator.simulate_stake_call();
+        assert_eq!(
+            emulator.contract.get_account_unstaked_available_epoch(&bob()),
+            3 + DEFAULT_NUM_EPOCHS_TO_UNLOCK
+        );
+        assert_eq!(
+            emulator.contract.get_account_epochs_until_withdraw(&bob()),
+            DEFAULT_NUM_EPOCHS_TO_UNLOCK
+        );
+
+        for epochs_left in (0..DEFAULT_NUM_EPOCHS_TO_UNLOCK).rev() {
+            emulator.skip_epochs(1);
+            emulator.update_context(bob(), UncToken::from_attounc(0));
+            assert_eq!(
+                emulator.contract.get_account_epochs_until_withdraw(&bob()),
+                epochs_left
+            );
+        }
+        assert!(emulator
+            .contract
+            .is_account_unstaked_balance_available(&bob()));
+
+        // The countdown saturates at zero.
+        emulator.skip_epochs(2);
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert_eq!(emulator.contract.get_account_epochs_until_withdraw(&bob()), 0);
+    }
+
+    #[test]
+    fn test_configured_num_epochs_to_unlock() {
+        let mut emulator = Emulator::new_with_num_epochs_to_unlock(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+            Some(2),
+        );
+        assert_eq!(emulator.contract.get_num_epochs_to_unlock(), 2);
+        let deposit_amount = ntoy(1_000);
+        emulator.update_context(bob(), UncToken::from_attounc(deposit_amount));
+        emulator.contract.deposit_and_stake();
+        emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+        emulator.simulate_stake_call();
+
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake_all();
+        emulator.simulate_stake_call();
+        assert_eq!(emulator.contract.get_account_unstaked_available_epoch(&bob()), 2);
+
+        emulator.skip_epochs(1);
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert!(!emulator.contract.is_account_unstaked_balance_available(&bob()));
+        emulator.skip_epochs(1);
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert!(emulator.contract.is_account_unstaked_balance_available(&bob()));
+    }
+
+    #[test]
+    fn test_set_num_epochs_to_unlock() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        let deposit_amount = ntoy(1_000);
+        for account_id in [alice(), bob()] {
+            emulator.update_context(account_id, UncToken::from_attounc(deposit_amount));
+            emulator.contract.deposit_and_stake();
+            emulator.amount = emulator.amount.saturating_add(UncToken::from_attounc(deposit_amount));
+            emulator.simulate_stake_call();
+        }
+
+        emulator.update_context(alice(), UncToken::from_attounc(0));
+        emulator.contract.unstake_all();
+        emulator.simulate_stake_call();
+        assert_eq!(
+            emulator.contract.get_account_unstaked_available_epoch(&alice()),
+            DEFAULT_NUM_EPOCHS_TO_UNLOCK
+        );
+
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.set_num_epochs_to_unlock(8);
+        assert_eq!(emulator.contract.get_num_epochs_to_unlock(), 8);
+
+        // The in-flight unstake keeps its unlock epoch, while the new one uses the updated delay.
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.unstake_all();
+        emulator.simulate_stake_call();
+        assert_eq!(
+            emulator.contract.get_account_unstaked_available_epoch(&alice()),
+            DEFAULT_NUM_EPOCHS_TO_UNLOCK
+        );
+        assert_eq!(emulator.contract.get_account_unstaked_available_epoch(&bob()), 8);
+
+        emulator.skip_epochs(DEFAULT_NUM_EPOCHS_TO_UNLOCK);
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        assert!(emulator.contract.is_account_unstaked_balance_available(&alice()));
+        assert!(!emulator.contract.is_account_unstaked_balance_available(&bob()));
+    }
+
+    #[test]
+    #[should_panic(expected = "The number of epochs to unlock is out of range, it should be between 1 and 60")]
+    fn test_set_num_epochs_to_unlock_above_max() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(owner(), UncToken::from_attounc(0));
+        emulator.contract.set_num_epochs_to_unlock(MAX_NUM_EPOCHS_TO_UNLOCK + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the owner")]
+    fn test_set_num_epochs_to_unlock_not_owner() {
+        let mut emulator = Emulator::new(
+            owner(),
+            "KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
+            zero_fee(),
+        );
+        emulator.update_context(bob(), UncToken::from_attounc(0));
+        emulator.contract.set_num_epochs_to_unlock(2);
+    }
+
     #[test]
     fn test_stake_all_unstake_all() {
         let mut emulator = Emulator::new(
@@ -0,0 +1,66 @@
+use crate::*;
+
+/// The layout of the contract state before the staking cap, the minimum stake amount, the reward
+/// history, the configurable number of epochs to unlock, the restricted mode, the fee recipient,
+/// the proposed owner, the maximum number of accounts, the number of epochs per year and the
+/// total unstaked balance were added.
+#[unc(serializers=[borsh])]
+pub struct OldStakingContract {
+    pub owner_id: AccountId,
+    pub stake_public_key: PublicKey,
+    pub last_epoch_height: EpochHeight,
+    pub last_total_balance: UncToken,
+    pub total_stake_shares: NumStakeShares,
+    pub total_staked_balance: UncToken,
+    pub reward_fee_fraction: RewardFeeFraction,
+    pub accounts: IterableMap<AccountId, Account>,
+    pub paused: bool,
+}
+
+#[unc]
+impl StakingContract {
+    /// Migrates the state from the previous layout after the contract code is upgraded.
+    /// Should be called with the `deploy_contract` action in the same transaction.
+    /// The accounts and the balances are kept, the new fields get their default values.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        assert_eq!(
+            env::current_account_id(),
+            env::predecessor_account_id(),
+            "{}",
+            StakingError::NotSelf
+        );
+        let old_state: OldStakingContract =
+            env::state_read().expect("The contract is not initialized");
+        let total_unstaked_balance = old_state
+            .accounts
+            .values()
+            .fold(UncToken::from_attounc(0), |total, account| total.saturating_add(account.unstaked));
+
+        Self {
+            owner_id: old_state.owner_id,
+            stake_public_key: old_state.stake_public_key,
+            last_epoch_height: old_state.last_epoch_height,
+            last_total_balance: old_state.last_total_balance,
+            total_stake_shares: old_state.total_stake_shares,
+            total_staked_balance: old_state.total_staked_balance,
+            reward_fee_fraction: old_state.reward_fee_fraction,
+            accounts: old_state.accounts,
+            paused: old_state.paused,
+            max_total_staked_balance: None,
+            min_stake_amount: UncToken::from_attounc(0),
+            reward_history: vec![],
+            num_epochs_to_unlock: DEFAULT_NUM_EPOCHS_TO_UNLOCK,
+            restricted_mode: false,
+            delegators: IterableSet::new(b"d".to_vec()),
+            fee_recipient: None,
+            proposed_owner_id: None,
+            max_num_accounts: None,
+            epochs_per_year: DEFAULT_EPOCHS_PER_YEAR,
+            total_unstaked_balance,
+            min_reward_to_distribute: UncToken::from_attounc(0),
+            fee_split: vec![],
+            last_staked_balance: None,
+        }
+    }
+}
@@ -10,6 +10,9 @@ pub fn alice() -> AccountId {
 pub fn bob() -> AccountId {
     "bob".parse().unwrap()
 }
+pub fn charlie() -> AccountId {
+    "charlie".parse().unwrap()
+}
 pub fn owner() -> AccountId {
     "owner".parse().unwrap()
 }
@@ -1,6 +1,23 @@
 use unc_sdk::store::LookupSet;
 use unc_sdk::{env, unc, AccountId};
 
+/// Events emitted by the whitelist contract.
+#[unc(event_json(standard = "whitelist"))]
+pub enum WhitelistEvent {
+    /// The foundation added staking pools to the whitelist in bulk.
+    #[event_version("1.0.0")]
+    StakingPoolsAdded {
+        /// The staking pools that were not whitelisted before.
+        account_ids: Vec<AccountId>,
+    },
+    /// The foundation removed staking pools from the whitelist in bulk.
+    #[event_version("1.0.0")]
+    StakingPoolsRemoved {
+        /// The staking pools that were whitelisted before.
+        account_ids: Vec<AccountId>,
+    },
+}
+
 #[unc(contract_state)]
 pub struct WhitelistContract {
     /// The account ID of the UNC Foundation. It allows to whitelist new staking pool accounts.
@@ -98,6 +115,48 @@ impl WhitelistContract {
         self.whitelist.remove(&staking_pool_account_id)
     }
 
+    /// Adds the given staking pool account IDs to the whitelist.
+    /// Returns the number of staking pools that were not in the whitelist before.
+    /// Emits a single event with the newly whitelisted staking pools.
+    /// This method can only be called by the UNC foundation.
+    pub fn add_staking_pools(&mut self, staking_pool_account_ids: Vec<AccountId>) -> u32 {
+        self.assert_called_by_foundation();
+        let mut account_ids = vec![];
+        for staking_pool_account_id in staking_pool_account_ids {
+            assert!(
+                env::is_valid_account_id(staking_pool_account_id.as_bytes()),
+                "The given account ID is invalid"
+            );
+            if self.whitelist.insert(staking_pool_account_id.clone()) {
+                account_ids.push(staking_pool_account_id);
+            }
+        }
+        let num_added = account_ids.len() as u32;
+        WhitelistEvent::StakingPoolsAdded { account_ids }.emit();
+        num_added
+    }
+
+    /// Removes the given staking pool account IDs from the whitelist.
+    /// Returns the number of staking pools that were present in the whitelist before.
+    /// Emits a single event with the removed staking pools.
+    /// This method can only be called by the UNC foundation.
+    pub fn remove_staking_pools(&mut self, staking_pool_account_ids: Vec<AccountId>) -> u32 {
+        self.assert_called_by_foundation();
+        let mut account_ids = vec![];
+        for staking_pool_account_id in staking_pool_account_ids {
+            assert!(
+                env::is_valid_account_id(staking_pool_account_id.as_bytes()),
+                "The given account ID is invalid"
+            );
+            if self.whitelist.remove(&staking_pool_account_id) {
+                account_ids.push(staking_pool_account_id);
+            }
+        }
+        let num_removed = account_ids.len() as u32;
+        WhitelistEvent::StakingPoolsRemoved { account_ids }.emit();
+        num_removed
+    }
+
     /// Adds the given staking pool factory contract account ID to the factory whitelist.
     /// Returns `true` if the factory was not in the whitelist before, `false` otherwise.
     /// This method can only be called by the UNC foundation.
@@ -141,7 +200,7 @@ mod tests {
 
     use super::*;
     use unc_primitives_core::config::ViewConfig;
-    use unc_sdk::test_utils::VMContextBuilder;
+    use unc_sdk::test_utils::{get_logs, VMContextBuilder};
     use unc_sdk::{testing_env, VMContext};
 
     mod test_utils;
@@ -363,4 +422,59 @@ mod tests {
             .build());
         assert!(!contract.is_factory_whitelisted(account_factory()));
     }
+
+    #[test]
+    fn test_bulk_whitelist() {
+        let context = basic_context();
+        testing_env!(context.clone());
+        let mut contract = WhitelistContract::new(account_unc());
+        let pool_a: AccountId = "pool-a".parse().unwrap();
+        let pool_b: AccountId = "pool-b".parse().unwrap();
+        assert!(contract.add_staking_pool(account_pool()));
+
+        // Only the pools that were not whitelisted before are counted, including a duplicate.
+        testing_env!(context.clone());
+        assert_eq!(
+            contract.add_staking_pools(vec![
+                account_pool(),
+                pool_a.clone(),
+                pool_b.clone(),
+                pool_a.clone()
+            ]),
+            2
+        );
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"whitelist","version":"1.0.0","event":"staking_pools_added","data":{"account_ids":["pool-a","pool-b"]}}"#]
+        );
+        assert!(contract.is_whitelisted(pool_a.clone()));
+        assert!(contract.is_whitelisted(pool_b.clone()));
+
+        testing_env!(context.clone());
+        assert_eq!(
+            contract.remove_staking_pools(vec![pool_a.clone(), account_factory(), pool_a.clone()]),
+            1
+        );
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"whitelist","version":"1.0.0","event":"staking_pools_removed","data":{"account_ids":["pool-a"]}}"#]
+        );
+        assert!(!contract.is_whitelisted(pool_a));
+        assert!(contract.is_whitelisted(pool_b));
+        assert!(contract.is_whitelisted(account_pool()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by UNC Foundation")]
+    fn test_bulk_whitelist_by_factory() {
+        testing_env!(basic_context());
+        let mut contract = WhitelistContract::new(account_unc());
+        assert!(contract.add_factory(account_factory()));
+
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account_whitelist())
+            .predecessor_account_id(account_factory())
+            .build());
+        contract.add_staking_pools(vec![account_pool()]);
+    }
 }